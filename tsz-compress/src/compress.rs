@@ -1,5 +1,4 @@
 use bitvec::prelude::*;
-pub use tsz_macro::*;
 
 ///
 /// Use `u8` as the storage type for a `BitVec` to represent
@@ -143,7 +142,7 @@ impl<T: Compress> Compressor<T> {
     pub fn len(&self) -> usize {
         // Round up to the nearest byte
         let num_bits = self.output.len();
-        (num_bits + 7) / 8
+        num_bits.div_ceil(8)
     }
 
     ///
@@ -398,6 +397,7 @@ mod tests {
 
         // How to bit pack a delta
         impl IntoCompressBits for TestRowDelta {
+            #[allow(clippy::unnecessary_cast, clippy::absurd_extreme_comparisons)]
             fn into_bits(self, out: &mut BitBuffer) {
                 if self.ts < i64::MIN as i128 && self.ts > i64::MAX as i128 {
                     unimplemented!()
@@ -530,8 +530,7 @@ mod tests {
             }
 
             fn into_delta(self, prev_row: &Self) -> Self::Delta {
-                let r = self - *prev_row;
-                r
+                self - *prev_row
             }
 
             fn into_deltadelta(self, prev_prev_row: &Self, prev_row: &Self) -> Self::Delta {
@@ -543,9 +542,7 @@ mod tests {
             type Full = TestRow;
             type Delta = TestRowDelta;
 
-            fn from_full<'a>(
-                bits: &'a BitBufferSlice,
-            ) -> Result<(Self, &'a BitBufferSlice), &'static str> {
+            fn from_full(bits: &BitBufferSlice) -> Result<(Self, &BitBufferSlice), &'static str> {
                 TestRow::from_bits(bits).map_err(|_| "failed to unmarshal full row")
             }
 
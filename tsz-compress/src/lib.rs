@@ -4,16 +4,16 @@
 //! This crate implements a specialized compression algorithm for time-series data that meets the following requirements:
 //!
 //! 0. Timestamps are integral values (signed or unsigned).
-//!   a. Data points may have one or more timestamps (SoC time, UTC time, etc.)
+//!    a. Data points may have one or more timestamps (SoC time, UTC time, etc.)
 //! 1. Data point values are integral values (signed or unsigned).
-//!   a. Separate value columns may have variable bit-widths, but all values in a column must have the same bit-width.
+//!    a. Separate value columns may have variable bit-widths, but all values in a column must have the same bit-width.
 //! 2. Data points are sorted by timestamp.
 //! 3. Data points are sampled at a regular interval, but not required to have exactly the same interval.
 //! 4. Data points are compressed in a lossless manner.
 //! 5. Data points are compressed as a stream, without requiring the entire time-series to be available at once.
 //! 6. Compressed payloads are small and can be transmitted over a BLE network.
-//!   a. Typical payloads are 100-251 byte packets.
-//!   b. Payloads will be precisely at most MTU-sized packets that not require fragmentation.
+//!    a. Typical payloads are 100-251 byte packets.
+//!    b. Payloads will be precisely at most MTU-sized packets that not require fragmentation.
 //!
 //! ## Targetted Use Case
 //!
@@ -142,6 +142,9 @@
 //!
 //! ### Framing and Nullability
 //!
+//! The V2 scheme implements nullability with `Option<T>` fields, where a presence column of run lengths precedes the present values of each nullable column.
+//! The original proposal follows.
+//!
 //! In order to parse the data, we need to know the number of columns to parse as each timestamp or row is read. This information is encoded in a framing header
 //! at the beginning of each packet. Nullifying a column is done by setting the corresponding bit in the header to 0. All delta-delta values will skip nullified rows.
 //! This can greatly reduce the number of bits required to encode a row for rows that may be interpolated or have missing data across packets.
@@ -168,17 +171,25 @@ use core::sync::atomic::{AtomicBool, Ordering};
 #[macro_use]
 extern crate std;
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
+#[cfg(feature = "alloc")]
 pub mod compress;
+#[cfg(feature = "alloc")]
 pub mod delta;
+#[cfg(feature = "alloc")]
 pub mod svlq;
+#[cfg(feature = "alloc")]
 pub mod uvlq;
 
 pub mod prelude {
+    #[cfg(feature = "alloc")]
     pub use crate::compress::*;
     pub use crate::v2::*;
+    #[cfg(feature = "alloc")]
     pub use bitvec::prelude as bv;
+    pub use tsz_macro::*;
 }
 
 // A static variable to flag initialization once.
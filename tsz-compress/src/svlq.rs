@@ -20,7 +20,7 @@ pub struct SvlqRef<'a>(pub &'a BitBufferSlice);
 /// When constructing a Svlq:
 /// * The first bit is 1 if the value is negative, 0 if positive.
 /// * Take the minimum trailing bits of the magnitude of the input value,
-///     starting from the MSB that is set.
+///   starting from the MSB that is set.
 /// * At least one Svlq byte is always produced.
 ///
 /// When constructing an signed:
@@ -265,188 +265,188 @@ mod tests {
     fn can_encode_0() {
         let svlq = super::Svlq::from(0i8);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], false);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(!svlq.bits[7]);
 
         let svlq = super::Svlq::from(0i16);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], false);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(!svlq.bits[7]);
 
         let svlq = super::Svlq::from(0i32);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], false);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(!svlq.bits[7]);
 
         let svlq = super::Svlq::from(0i64);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], false);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(!svlq.bits[7]);
     }
 
     #[test]
     fn can_encode_1() {
         let svlq = super::Svlq::from(1i8);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(1i16);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(1i32);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(1i64);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
     }
 
     #[test]
     fn can_encode_neg_1() {
         let svlq = super::Svlq::from(-1i8);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], true);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(-1i16);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], true);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(-1i32);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], true);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(-1i64);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], true);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], true);
+        assert!(svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(svlq.bits[7]);
     }
 
     #[test]
     fn can_encode_63() {
         let svlq = super::Svlq::from(63i8);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], true);
-        assert_eq!(svlq.bits[3], true);
-        assert_eq!(svlq.bits[4], true);
-        assert_eq!(svlq.bits[5], true);
-        assert_eq!(svlq.bits[6], true);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(svlq.bits[2]);
+        assert!(svlq.bits[3]);
+        assert!(svlq.bits[4]);
+        assert!(svlq.bits[5]);
+        assert!(svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(63i16);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], true);
-        assert_eq!(svlq.bits[3], true);
-        assert_eq!(svlq.bits[4], true);
-        assert_eq!(svlq.bits[5], true);
-        assert_eq!(svlq.bits[6], true);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(svlq.bits[2]);
+        assert!(svlq.bits[3]);
+        assert!(svlq.bits[4]);
+        assert!(svlq.bits[5]);
+        assert!(svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(63i32);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], true);
-        assert_eq!(svlq.bits[3], true);
-        assert_eq!(svlq.bits[4], true);
-        assert_eq!(svlq.bits[5], true);
-        assert_eq!(svlq.bits[6], true);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(svlq.bits[2]);
+        assert!(svlq.bits[3]);
+        assert!(svlq.bits[4]);
+        assert!(svlq.bits[5]);
+        assert!(svlq.bits[6]);
+        assert!(svlq.bits[7]);
 
         let svlq = super::Svlq::from(63i64);
         assert_eq!(svlq.bits.len(), 8);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], false);
-        assert_eq!(svlq.bits[2], true);
-        assert_eq!(svlq.bits[3], true);
-        assert_eq!(svlq.bits[4], true);
-        assert_eq!(svlq.bits[5], true);
-        assert_eq!(svlq.bits[6], true);
-        assert_eq!(svlq.bits[7], true);
+        assert!(!svlq.bits[0]);
+        assert!(!svlq.bits[1]);
+        assert!(svlq.bits[2]);
+        assert!(svlq.bits[3]);
+        assert!(svlq.bits[4]);
+        assert!(svlq.bits[5]);
+        assert!(svlq.bits[6]);
+        assert!(svlq.bits[7]);
     }
 
     #[test]
@@ -455,60 +455,60 @@ mod tests {
         // 0b0100 0000 0b0000 0010
         let svlq = super::Svlq::from(128i16);
         assert_eq!(svlq.bits.len(), 16);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], true);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], false);
-        assert_eq!(svlq.bits[8], false);
-        assert_eq!(svlq.bits[9], false);
-        assert_eq!(svlq.bits[10], false);
-        assert_eq!(svlq.bits[11], false);
-        assert_eq!(svlq.bits[12], false);
-        assert_eq!(svlq.bits[13], false);
-        assert_eq!(svlq.bits[14], true);
-        assert_eq!(svlq.bits[15], false);
+        assert!(!svlq.bits[0]);
+        assert!(svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(!svlq.bits[7]);
+        assert!(!svlq.bits[8]);
+        assert!(!svlq.bits[9]);
+        assert!(!svlq.bits[10]);
+        assert!(!svlq.bits[11]);
+        assert!(!svlq.bits[12]);
+        assert!(!svlq.bits[13]);
+        assert!(svlq.bits[14]);
+        assert!(!svlq.bits[15]);
 
         let svlq = super::Svlq::from(128i32);
         assert_eq!(svlq.bits.len(), 16);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], true);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], false);
-        assert_eq!(svlq.bits[8], false);
-        assert_eq!(svlq.bits[9], false);
-        assert_eq!(svlq.bits[10], false);
-        assert_eq!(svlq.bits[11], false);
-        assert_eq!(svlq.bits[12], false);
-        assert_eq!(svlq.bits[13], false);
-        assert_eq!(svlq.bits[14], true);
-        assert_eq!(svlq.bits[15], false);
+        assert!(!svlq.bits[0]);
+        assert!(svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(!svlq.bits[7]);
+        assert!(!svlq.bits[8]);
+        assert!(!svlq.bits[9]);
+        assert!(!svlq.bits[10]);
+        assert!(!svlq.bits[11]);
+        assert!(!svlq.bits[12]);
+        assert!(!svlq.bits[13]);
+        assert!(svlq.bits[14]);
+        assert!(!svlq.bits[15]);
 
         let svlq = super::Svlq::from(128i64);
         assert_eq!(svlq.bits.len(), 16);
-        assert_eq!(svlq.bits[0], false);
-        assert_eq!(svlq.bits[1], true);
-        assert_eq!(svlq.bits[2], false);
-        assert_eq!(svlq.bits[3], false);
-        assert_eq!(svlq.bits[4], false);
-        assert_eq!(svlq.bits[5], false);
-        assert_eq!(svlq.bits[6], false);
-        assert_eq!(svlq.bits[7], false);
-        assert_eq!(svlq.bits[8], false);
-        assert_eq!(svlq.bits[9], false);
-        assert_eq!(svlq.bits[10], false);
-        assert_eq!(svlq.bits[11], false);
-        assert_eq!(svlq.bits[12], false);
-        assert_eq!(svlq.bits[13], false);
-        assert_eq!(svlq.bits[14], true);
-        assert_eq!(svlq.bits[15], false);
+        assert!(!svlq.bits[0]);
+        assert!(svlq.bits[1]);
+        assert!(!svlq.bits[2]);
+        assert!(!svlq.bits[3]);
+        assert!(!svlq.bits[4]);
+        assert!(!svlq.bits[5]);
+        assert!(!svlq.bits[6]);
+        assert!(!svlq.bits[7]);
+        assert!(!svlq.bits[8]);
+        assert!(!svlq.bits[9]);
+        assert!(!svlq.bits[10]);
+        assert!(!svlq.bits[11]);
+        assert!(!svlq.bits[12]);
+        assert!(!svlq.bits[13]);
+        assert!(svlq.bits[14]);
+        assert!(!svlq.bits[15]);
     }
 
     #[test]
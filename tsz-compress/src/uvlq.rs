@@ -18,7 +18,7 @@ pub struct UvlqRef<'a>(pub &'a BitBufferSlice);
 ///
 /// When constructing a Uvlq:
 /// * Take the minimum trailing bits of the input value,
-/// starting from the MSB that is set.
+///   starting from the MSB that is set.
 /// * At least one Uvlq byte is always produced.
 ///
 /// When constructing an unsigned:
@@ -155,116 +155,116 @@ mod tests {
     fn can_encode_0() {
         let uvlq = super::Uvlq::from(0u32);
         assert_eq!(uvlq.bits.len(), 8);
-        assert_eq!(uvlq.bits[0], false);
-        assert_eq!(uvlq.bits[1], false);
-        assert_eq!(uvlq.bits[2], false);
-        assert_eq!(uvlq.bits[3], false);
-        assert_eq!(uvlq.bits[4], false);
-        assert_eq!(uvlq.bits[5], false);
-        assert_eq!(uvlq.bits[6], false);
-        assert_eq!(uvlq.bits[7], false);
+        assert!(!uvlq.bits[0]);
+        assert!(!uvlq.bits[1]);
+        assert!(!uvlq.bits[2]);
+        assert!(!uvlq.bits[3]);
+        assert!(!uvlq.bits[4]);
+        assert!(!uvlq.bits[5]);
+        assert!(!uvlq.bits[6]);
+        assert!(!uvlq.bits[7]);
 
         let uvlq = super::Uvlq::from(0u64);
         assert_eq!(uvlq.bits.len(), 8);
-        assert_eq!(uvlq.bits[0], false);
-        assert_eq!(uvlq.bits[1], false);
-        assert_eq!(uvlq.bits[2], false);
-        assert_eq!(uvlq.bits[3], false);
-        assert_eq!(uvlq.bits[4], false);
-        assert_eq!(uvlq.bits[5], false);
-        assert_eq!(uvlq.bits[6], false);
-        assert_eq!(uvlq.bits[7], false);
+        assert!(!uvlq.bits[0]);
+        assert!(!uvlq.bits[1]);
+        assert!(!uvlq.bits[2]);
+        assert!(!uvlq.bits[3]);
+        assert!(!uvlq.bits[4]);
+        assert!(!uvlq.bits[5]);
+        assert!(!uvlq.bits[6]);
+        assert!(!uvlq.bits[7]);
     }
 
     #[test]
     fn can_encode_1() {
         let uvlq = super::Uvlq::from(1u32);
         assert_eq!(uvlq.bits.len(), 8);
-        assert_eq!(uvlq.bits[0], false);
-        assert_eq!(uvlq.bits[1], false);
-        assert_eq!(uvlq.bits[2], false);
-        assert_eq!(uvlq.bits[3], false);
-        assert_eq!(uvlq.bits[4], false);
-        assert_eq!(uvlq.bits[5], false);
-        assert_eq!(uvlq.bits[6], false);
-        assert_eq!(uvlq.bits[7], true);
+        assert!(!uvlq.bits[0]);
+        assert!(!uvlq.bits[1]);
+        assert!(!uvlq.bits[2]);
+        assert!(!uvlq.bits[3]);
+        assert!(!uvlq.bits[4]);
+        assert!(!uvlq.bits[5]);
+        assert!(!uvlq.bits[6]);
+        assert!(uvlq.bits[7]);
 
         let uvlq = super::Uvlq::from(1u64);
         assert_eq!(uvlq.bits.len(), 8);
-        assert_eq!(uvlq.bits[0], false);
-        assert_eq!(uvlq.bits[1], false);
-        assert_eq!(uvlq.bits[2], false);
-        assert_eq!(uvlq.bits[3], false);
-        assert_eq!(uvlq.bits[4], false);
-        assert_eq!(uvlq.bits[5], false);
-        assert_eq!(uvlq.bits[6], false);
-        assert_eq!(uvlq.bits[7], true);
+        assert!(!uvlq.bits[0]);
+        assert!(!uvlq.bits[1]);
+        assert!(!uvlq.bits[2]);
+        assert!(!uvlq.bits[3]);
+        assert!(!uvlq.bits[4]);
+        assert!(!uvlq.bits[5]);
+        assert!(!uvlq.bits[6]);
+        assert!(uvlq.bits[7]);
     }
 
     #[test]
     fn can_encode_127() {
         let uvlq = super::Uvlq::from(127u32);
         assert_eq!(uvlq.bits.len(), 8);
-        assert_eq!(uvlq.bits[0], false);
-        assert_eq!(uvlq.bits[1], true);
-        assert_eq!(uvlq.bits[2], true);
-        assert_eq!(uvlq.bits[3], true);
-        assert_eq!(uvlq.bits[4], true);
-        assert_eq!(uvlq.bits[5], true);
-        assert_eq!(uvlq.bits[6], true);
-        assert_eq!(uvlq.bits[7], true);
+        assert!(!uvlq.bits[0]);
+        assert!(uvlq.bits[1]);
+        assert!(uvlq.bits[2]);
+        assert!(uvlq.bits[3]);
+        assert!(uvlq.bits[4]);
+        assert!(uvlq.bits[5]);
+        assert!(uvlq.bits[6]);
+        assert!(uvlq.bits[7]);
 
         let uvlq = super::Uvlq::from(127u64);
         assert_eq!(uvlq.bits.len(), 8);
-        assert_eq!(uvlq.bits[0], false);
-        assert_eq!(uvlq.bits[1], true);
-        assert_eq!(uvlq.bits[2], true);
-        assert_eq!(uvlq.bits[3], true);
-        assert_eq!(uvlq.bits[4], true);
-        assert_eq!(uvlq.bits[5], true);
-        assert_eq!(uvlq.bits[6], true);
-        assert_eq!(uvlq.bits[7], true);
+        assert!(!uvlq.bits[0]);
+        assert!(uvlq.bits[1]);
+        assert!(uvlq.bits[2]);
+        assert!(uvlq.bits[3]);
+        assert!(uvlq.bits[4]);
+        assert!(uvlq.bits[5]);
+        assert!(uvlq.bits[6]);
+        assert!(uvlq.bits[7]);
     }
 
     #[test]
     fn can_encode_128() {
         let uvlq = super::Uvlq::from(128u32);
         assert_eq!(uvlq.bits.len(), 16);
-        assert_eq!(uvlq.bits[0], true);
-        assert_eq!(uvlq.bits[1], false);
-        assert_eq!(uvlq.bits[2], false);
-        assert_eq!(uvlq.bits[3], false);
-        assert_eq!(uvlq.bits[4], false);
-        assert_eq!(uvlq.bits[5], false);
-        assert_eq!(uvlq.bits[6], false);
-        assert_eq!(uvlq.bits[7], false);
-        assert_eq!(uvlq.bits[8], false);
-        assert_eq!(uvlq.bits[9], false);
-        assert_eq!(uvlq.bits[10], false);
-        assert_eq!(uvlq.bits[11], false);
-        assert_eq!(uvlq.bits[12], false);
-        assert_eq!(uvlq.bits[13], false);
-        assert_eq!(uvlq.bits[14], false);
-        assert_eq!(uvlq.bits[15], true);
+        assert!(uvlq.bits[0]);
+        assert!(!uvlq.bits[1]);
+        assert!(!uvlq.bits[2]);
+        assert!(!uvlq.bits[3]);
+        assert!(!uvlq.bits[4]);
+        assert!(!uvlq.bits[5]);
+        assert!(!uvlq.bits[6]);
+        assert!(!uvlq.bits[7]);
+        assert!(!uvlq.bits[8]);
+        assert!(!uvlq.bits[9]);
+        assert!(!uvlq.bits[10]);
+        assert!(!uvlq.bits[11]);
+        assert!(!uvlq.bits[12]);
+        assert!(!uvlq.bits[13]);
+        assert!(!uvlq.bits[14]);
+        assert!(uvlq.bits[15]);
 
         let uvlq = super::Uvlq::from(128u64);
         assert_eq!(uvlq.bits.len(), 16);
-        assert_eq!(uvlq.bits[0], true);
-        assert_eq!(uvlq.bits[1], false);
-        assert_eq!(uvlq.bits[2], false);
-        assert_eq!(uvlq.bits[3], false);
-        assert_eq!(uvlq.bits[4], false);
-        assert_eq!(uvlq.bits[5], false);
-        assert_eq!(uvlq.bits[6], false);
-        assert_eq!(uvlq.bits[7], false);
-        assert_eq!(uvlq.bits[8], false);
-        assert_eq!(uvlq.bits[9], false);
-        assert_eq!(uvlq.bits[10], false);
-        assert_eq!(uvlq.bits[11], false);
-        assert_eq!(uvlq.bits[12], false);
-        assert_eq!(uvlq.bits[13], false);
-        assert_eq!(uvlq.bits[14], false);
-        assert_eq!(uvlq.bits[15], true);
+        assert!(uvlq.bits[0]);
+        assert!(!uvlq.bits[1]);
+        assert!(!uvlq.bits[2]);
+        assert!(!uvlq.bits[3]);
+        assert!(!uvlq.bits[4]);
+        assert!(!uvlq.bits[5]);
+        assert!(!uvlq.bits[6]);
+        assert!(!uvlq.bits[7]);
+        assert!(!uvlq.bits[8]);
+        assert!(!uvlq.bits[9]);
+        assert!(!uvlq.bits[10]);
+        assert!(!uvlq.bits[11]);
+        assert!(!uvlq.bits[12]);
+        assert!(!uvlq.bits[13]);
+        assert!(!uvlq.bits[14]);
+        assert!(uvlq.bits[15]);
     }
 
     #[test]
@@ -2,6 +2,18 @@ pub mod headers {
     pub const START_OF_COLUMN: u8 = 0b1001;
     pub const FIRST_ROW: u8 = 0b0110;
     pub const SECOND_ROW: u8 = FIRST_ROW;
+    /// Replaces FIRST_ROW when the rest of the column is delta-delta encoded
+    pub const FIRST_ROW_DELTA_DELTA: u8 = 0b0111;
+    /// Replaces FIRST_ROW when the first row is a zigzag nibble varint
+    pub const FIRST_ROW_VARINT: u8 = 0b0010;
+    /// Replaces FIRST_ROW_DELTA_DELTA when the first row is a zigzag nibble varint
+    pub const FIRST_ROW_DELTA_DELTA_VARINT: u8 = 0b0011;
+    /// Replaces SECOND_ROW when the first delta is a zigzag nibble varint
+    pub const SECOND_ROW_VARINT: u8 = FIRST_ROW_VARINT;
+    /// Replaces FIRST_ROW in a continuation packet, the deltas continue from the previous packet
+    pub const CONTINUE_DELTA: u8 = 0b0100;
+    /// Replaces FIRST_ROW_DELTA_DELTA in a continuation packet, the delta-deltas continue from the previous packet
+    pub const CONTINUE_DELTA_DELTA: u8 = 0b0101;
 
     // DELTA ENCODING
     pub const THREE_BITS_TEN_SAMPLES: u8 = 0b1111;
@@ -11,4 +23,77 @@ pub mod headers {
     pub const SIXTEEN_BITS_TWO_SAMPLES: u8 = 0b1000;
     pub const THIRTY_TWO_BITS_ONE_SAMPLE: u8 = 0b1011;
     pub const SIXTY_FOUR_BITS_ONE_SAMPLE: u8 = 0b1101;
+    /// A nibble varint count of identical deltas, then the zigzag delta as a nibble varint
+    pub const REPEATED_DELTAS: u8 = 0b0001;
+    /// A full bit-width value restarting the deltas, for a delta that overflows the chosen bit-width
+    pub const ESCAPE_DELTA: u8 = FIRST_ROW;
+
+    // DELTA-DELTA ENCODING
+    pub const DELTA_DELTA_ZERO: u8 = 0b0000;
+    /// The low bit of the header is the top bit of the 5-bit value
+    pub const DELTA_DELTA_FIVE_BITS: u8 = 0b0010;
+    /// The low bit of the header is the top bit of the 9-bit value
+    pub const DELTA_DELTA_NINE_BITS: u8 = 0b0100;
+    pub const DELTA_DELTA_SIXTEEN_BITS: u8 = 0b0110;
+    pub const DELTA_DELTA_THIRTY_TWO_BITS: u8 = 0b0111;
+    pub const DELTA_DELTA_SIXTY_FOUR_BITS: u8 = 0b0001;
+    /// Only i128 columns can need more than 64 bits for a delta-delta
+    pub const DELTA_DELTA_ONE_HUNDRED_TWENTY_EIGHT_BITS: u8 = 0b1000;
+    /// A full bit-width value restarting the delta-deltas, for a delta that overflows the chosen bit-width
+    pub const ESCAPE_DELTA_DELTA: u8 = 0b1010;
+
+    // XOR ENCODING, of float columns following a FIRST_ROW of the full bit-width value
+    /// The value is the same as the previous value
+    pub const XOR_REPEATED: u8 = 0b0000;
+    /// The meaningful nibbles of the XOR with the previous value, in the window of the last XOR
+    pub const XOR_REUSE_WINDOW: u8 = 0b0010;
+    /// A nibble of leading zero nibbles and a nibble of one less than the meaningful nibbles,
+    /// then the meaningful nibbles of the XOR with the previous value
+    pub const XOR_NEW_WINDOW: u8 = 0b0011;
+}
+
+///
+/// The optional stream header describing the columns of a packet.
+///
+/// The header is byte-aligned and precedes the sequence field and the row count:
+/// `[MAGIC nibble][VERSION nibble][column count byte][per-column byte]...`
+///
+/// Each per-column byte is the column type code in the upper nibble,
+/// and the delta type code in the lower 3 bits of the lower nibble.
+/// The top bit of the lower nibble is set if the column is delta-delta encoded.
+///
+pub mod schema {
+    pub const MAGIC: u8 = 0b0101;
+    pub const VERSION: u8 = 0b0001;
+
+    // COLUMN TYPES, the top bit is set for unsigned types
+    pub const I8: u8 = 0b0000;
+    pub const I16: u8 = 0b0001;
+    pub const I32: u8 = 0b0010;
+    pub const I64: u8 = 0b0011;
+    pub const I128: u8 = 0b0100;
+    pub const U8: u8 = 0b1000;
+    pub const U16: u8 = 0b1001;
+    pub const U32: u8 = 0b1010;
+    pub const U64: u8 = 0b1011;
+    /// Float columns are XOR encoded, so they use their own code as the delta type code
+    pub const F32: u8 = 0b0101;
+    pub const F64: u8 = 0b0110;
+    /// The presence column that precedes the values of an `Option<T>` column
+    pub const PRESENCE: u8 = 0b0111;
+
+    // ENCODING
+    pub const DELTA_DELTA: u8 = 0b1000;
+}
+
+///
+/// The sequence field of packets of `#[tsz(continuation)]` columns.
+///
+/// The field is two big-endian bytes following the stream header, ahead of the row count.
+/// The top bit is set if the packet continues the columns of the previous packet,
+/// and the lower 15 bits count the packets, wrapping to 0.
+///
+pub mod sequence {
+    pub const CONTINUATION: u16 = 0x8000;
+    pub const MASK: u16 = 0x7FFF;
 }
@@ -0,0 +1,96 @@
+///
+/// The CRC-32 (IEEE 802.3) lookup table, for the reflected polynomial 0xEDB88320.
+///
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+///
+/// A running CRC-32 (IEEE 802.3), for checksums of bytes that arrive in pieces.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub const fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    ///
+    /// Adds the bytes to the checksum.
+    ///
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state =
+                CRC32_TABLE[((self.state ^ *byte as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    ///
+    /// The checksum of the bytes so far.
+    ///
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+///
+/// The CRC-32 (IEEE 802.3) of the bytes, as written in the trailer of a `#[tsz(crc)]` packet.
+///
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_check_known_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+
+    #[test]
+    fn can_check_in_pieces() {
+        let mut bytes = [0u8; 1000];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        for split in [0, 1, 7, 500, 999, 1000] {
+            let mut crc = Crc32::new();
+            crc.update(&bytes[..split]);
+            crc.update(&bytes[split..]);
+            assert_eq!(crc.finish(), crc32(&bytes));
+        }
+    }
+}
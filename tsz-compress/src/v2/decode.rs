@@ -1,5 +1,5 @@
 use crate::prelude::*;
-use crate::v2::consts::headers;
+use crate::v2::consts::{headers, schema, sequence};
 use alloc::vec::Vec;
 ///
 /// An iterator over nibbles in the slice of bytes.
@@ -21,6 +21,13 @@ impl<'it> HalfIter<'it> {
             idx: 0,
         }
     }
+
+    ///
+    /// The number of nibbles taken from the slice so far.
+    ///
+    pub fn nibble_offset(&self) -> usize {
+        self.idx * 2 + !self.upper as usize
+    }
 }
 
 impl<'it> Iterator for HalfIter<'it> {
@@ -110,846 +117,1066 @@ pub fn read_full_i8(buf: &[u8; 1]) -> i8 {
 }
 
 ///
-/// Decodes 8-bit integers according to the delta encoding scheme.
+/// Checks the optional stream header against the schema of the decompressor,
+/// returning the bytes that follow the header.
 ///
-/// This function reads the HalfIter in chunks, decodes the chunks according to the delta encoding scheme,
-/// and writes the decoded values to the Vec<i8>.
+/// `columns` holds one schema byte per column, see `consts::schema`.
+/// The encoding bit of each column is not checked, as the column tags already describe it.
 ///
-pub fn decode_i8(iter: &mut HalfIter<'_>, output: &mut Vec<i8>) -> Result<(), CodingError> {
-    // Check for 0 rows
-    match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::FIRST_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
+pub fn read_header<'a>(bytes: &'a [u8], columns: &[u8]) -> Result<&'a [u8], CodingError> {
+    let header_len = 2 + columns.len();
+    if bytes.len() < header_len {
+        return Err(CodingError::HeaderMismatch);
     }
 
-    // Full 8 bit value
-    let buf = [(iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-        | iter.next().ok_or(CodingError::NotEnoughBits)?];
-    let value = read_full_i8(&buf);
-    output.push(value);
+    if bytes[0] != (schema::MAGIC << 4) | schema::VERSION || bytes[1] as usize != columns.len() {
+        return Err(CodingError::HeaderMismatch);
+    }
 
-    // One row
-    match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::SECOND_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
-    }
-
-    // Delta encoded 16 bit value
-    let buf = [
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-    ];
-    let delta = read_full_i16(&buf);
-    let mut value = (value as i16 + delta) as i8;
-    output.push(value);
-
-    // Every thing is delta or delta-delta encoded from here on out
-    while let Some(tag) = iter.next() {
-        match tag {
-            headers::START_OF_COLUMN => {
-                // Start of column of next column
-                break;
-            }
-            headers::THREE_BITS_TEN_SAMPLES => {
-                // 2 bit pad, 10 samples of 3 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 3;
-                let shift = 32 - padding - bit_width;
-                for i in 0..10 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b111) as i16;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i16 + delta) as i8;
-                    output.push(value);
-                }
-            }
-            headers::SIX_BITS_FIVE_SAMPLES => {
-                // 5 samples of 6 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 6;
-                let shift = 32 - padding - bit_width;
-                for i in 0..5 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111) as i16;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i16 + delta) as i8;
-                    output.push(value);
-                }
+    for (actual, expected) in bytes[2..header_len].iter().zip(columns) {
+        if actual & !schema::DELTA_DELTA != expected & !schema::DELTA_DELTA {
+            return Err(CodingError::HeaderMismatch);
+        }
+    }
+
+    Ok(&bytes[header_len..])
+}
+
+///
+/// The most rows a decompressor accepts in a packet.
+///
+/// The row count is read from the packet itself, so without a limit a crafted packet
+/// could claim a run of more values than can ever be allocated.
+///
+pub const MAX_PACKET_ROWS: usize = 1 << 24;
+
+///
+/// The most values reserved at once while pushing a run.
+///
+const RESERVE_STEP: usize = 4096;
+
+///
+/// Reads the next `N` bytes worth of nibbles from the iterator.
+///
+#[inline(always)]
+fn read_bytes<const N: usize>(iter: &mut HalfIter<'_>) -> Result<[u8; N], CodingError> {
+    let mut buf = [0u8; N];
+    for byte in buf.iter_mut() {
+        *byte = (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
+            | iter.next().ok_or(CodingError::NotEnoughBits)?;
+    }
+    Ok(buf)
+}
+
+///
+/// Reads `count` nibbles from the iterator into a word, most significant nibble first.
+///
+#[inline(always)]
+fn read_nibbles(iter: &mut HalfIter<'_>, count: usize) -> Result<u64, CodingError> {
+    let mut word: u64 = 0;
+    for _ in 0..count {
+        let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
+        word = (word << 4) | half as u64;
+    }
+    Ok(word)
+}
+
+///
+/// Reads an unsigned integer written by `write_nibble_varint`.
+///
+#[inline(always)]
+fn read_nibble_varint(iter: &mut HalfIter<'_>) -> Result<u64, CodingError> {
+    // 22 nibbles hold all 64 bits
+    let mut value: u64 = 0;
+    for _ in 0..22 {
+        let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
+        if value >> 61 != 0 {
+            return Err(CodingError::InvalidBits);
+        }
+        value = (value << 3) | (half & 0b111) as u64;
+        if half & 0b1000 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(CodingError::InvalidBits)
+}
+
+///
+/// Reads the row count of a packet, the nibble varint ahead of the first column.
+///
+/// Returns `CodingError::InvalidBits` if the row count does not fit a usize.
+///
+pub fn read_row_count(iter: &mut HalfIter<'_>) -> Result<usize, CodingError> {
+    let rows = read_nibble_varint(iter)?;
+    usize::try_from(rows).map_err(|_| CodingError::InvalidBits)
+}
+
+///
+/// Checks that the row count of a packet is at most `MAX_PACKET_ROWS`,
+/// returning `CodingError::InvalidRowCount` with the row count otherwise.
+///
+pub fn check_row_count(rows: usize) -> Result<(), CodingError> {
+    match rows > MAX_PACKET_ROWS {
+        true => Err(CodingError::InvalidRowCount(rows)),
+        false => Ok(()),
+    }
+}
+
+///
+/// Reads a first row or first delta written by `write_varint_row`.
+///
+#[inline(always)]
+fn read_varint_row(iter: &mut HalfIter<'_>) -> Result<i128, CodingError> {
+    // 43 nibbles hold all 128 bits
+    let mut zigzag: u128 = 0;
+    for _ in 0..43 {
+        let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
+        if zigzag >> 125 != 0 {
+            return Err(CodingError::InvalidBits);
+        }
+        zigzag = (zigzag << 3) | (half & 0b111) as u128;
+        if half & 0b1000 == 0 {
+            return Ok(((zigzag >> 1) as i128) ^ -((zigzag & 1) as i128));
+        }
+    }
+    Err(CodingError::InvalidBits)
+}
+
+///
+/// Reverses the zigzag encoding of a value.
+///
+#[inline(always)]
+fn unzigzag(zigzag: u64) -> i64 {
+    ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64)
+}
+
+///
+/// A column value that can be reconstructed from its first row value
+/// and a running delta.
+///
+/// Every column of 64 bits or less is reconstructed with wrapping arithmetic
+/// in 64 bits, then truncated back to the bit-width of the column.
+///
+pub trait DecodeColumn: Copy {
+    /// Whether a value of a declared bit-width is sign extended, rather than zero extended.
+    const SIGNED: bool;
+
+    /// Reads the full-width value of the first row.
+    fn read_first(iter: &mut HalfIter<'_>) -> Result<i64, CodingError>;
+
+    /// Reads the double-width delta between the first and second row.
+    fn read_second(iter: &mut HalfIter<'_>) -> Result<i64, CodingError>;
+
+    /// Truncates the running value to the column bit-width.
+    fn from_wrapped(value: i64) -> Self;
+}
+
+impl DecodeColumn for i8 {
+    const SIGNED: bool = true;
+
+    #[inline(always)]
+    fn read_first(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        Ok(read_full_i8(&read_bytes::<1>(iter)?) as i64)
+    }
+
+    #[inline(always)]
+    fn read_second(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        Ok(read_full_i16(&read_bytes::<2>(iter)?) as i64)
+    }
+
+    #[inline(always)]
+    fn from_wrapped(value: i64) -> Self {
+        value as i8
+    }
+}
+
+impl DecodeColumn for i16 {
+    const SIGNED: bool = true;
+
+    #[inline(always)]
+    fn read_first(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        Ok(read_full_i16(&read_bytes::<2>(iter)?) as i64)
+    }
+
+    #[inline(always)]
+    fn read_second(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        Ok(read_full_i32(&read_bytes::<4>(iter)?) as i64)
+    }
+
+    #[inline(always)]
+    fn from_wrapped(value: i64) -> Self {
+        value as i16
+    }
+}
+
+impl DecodeColumn for i32 {
+    const SIGNED: bool = true;
+
+    #[inline(always)]
+    fn read_first(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        Ok(read_full_i32(&read_bytes::<4>(iter)?) as i64)
+    }
+
+    #[inline(always)]
+    fn read_second(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        Ok(read_full_i64(&read_bytes::<8>(iter)?))
+    }
+
+    #[inline(always)]
+    fn from_wrapped(value: i64) -> Self {
+        value as i32
+    }
+}
+
+impl DecodeColumn for i64 {
+    const SIGNED: bool = true;
+
+    #[inline(always)]
+    fn read_first(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        Ok(read_full_i64(&read_bytes::<8>(iter)?))
+    }
+
+    #[inline(always)]
+    fn read_second(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+        // The delta may need 65 bits, but only the bottom 64 bits matter when wrapping
+        Ok(read_full_i128(&read_bytes::<16>(iter)?) as i64)
+    }
+
+    #[inline(always)]
+    fn from_wrapped(value: i64) -> Self {
+        value
+    }
+}
+
+///
+/// Unsigned columns share the bits of the signed column of the same bit-width.
+///
+macro_rules! impl_decode_column_unsigned {
+    ($unsigned:ty, $signed:ty) => {
+        impl DecodeColumn for $unsigned {
+            const SIGNED: bool = false;
+
+            #[inline(always)]
+            fn read_first(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+                <$signed>::read_first(iter)
             }
-            headers::EIGHT_BITS_FOUR_SAMPLES => {
-                // 4 samples of 8 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 8;
-                let shift = 32 - padding - bit_width;
-                for i in 0..4 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b1111_1111) as i16;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i16 + delta) as i8;
-                    output.push(value);
-                }
+
+            #[inline(always)]
+            fn read_second(iter: &mut HalfIter<'_>) -> Result<i64, CodingError> {
+                <$signed>::read_second(iter)
             }
-            headers::TEN_BITS_THREE_SAMPLES => {
-                // 2 bit pad, 3 samples of 10 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 10;
-                let shift = 32 - padding - bit_width;
-                for i in 0..3 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111_1111) as i16;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i16 + delta) as i8;
-                    output.push(value);
-                }
+
+            #[inline(always)]
+            fn from_wrapped(value: i64) -> Self {
+                value as $unsigned
             }
-            headers::SIXTEEN_BITS_TWO_SAMPLES => {
-                // 2 samples of 16 bit
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 16;
-                let shift = 32 - padding - bit_width;
-                for i in 0..2 {
-                    let delta = (word >> (shift - bit_width * i)) as i16;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i16 + delta) as i8;
-                    output.push(value);
-                }
+        }
+    };
+}
+
+impl_decode_column_unsigned!(u8, i8);
+impl_decode_column_unsigned!(u16, i16);
+impl_decode_column_unsigned!(u32, i32);
+impl_decode_column_unsigned!(u64, i64);
+
+///
+/// Makes sure `count` more values can be pushed without the column exceeding `end` values,
+/// and reserves room for the first `RESERVE_STEP` of them.
+///
+#[inline(always)]
+fn reserve_room<T>(output: &mut Vec<T>, end: usize, count: u64) -> Result<(), CodingError> {
+    if count > (end - output.len()) as u64 {
+        return Err(CodingError::InvalidRowCount(end));
+    }
+    try_reserve_step(output, count as usize).map(|_| ())
+}
+
+///
+/// Reserves room for up to `RESERVE_STEP` of `count` more values, returning how many were reserved.
+///
+/// A run is pushed a step at a time, so a failed allocation is reported rather than aborting.
+///
+#[inline(always)]
+fn try_reserve_step<T>(output: &mut Vec<T>, count: usize) -> Result<usize, CodingError> {
+    let step = count.min(RESERVE_STEP);
+    output
+        .try_reserve(step)
+        .map_err(|_| CodingError::AllocationFailed)?;
+    Ok(step)
+}
+
+///
+/// Sign extends the low `width` bits of a word.
+///
+#[inline(always)]
+fn sign_extend(word: u64, width: u32) -> i64 {
+    let shift = 64 - width;
+    ((word << shift) as i64) >> shift
+}
+
+///
+/// Returns true if the value is within `bits` signed or unsigned bits.
+///
+#[inline(always)]
+fn fits_bits(value: i64, bits: u32, signed: bool) -> bool {
+    match signed {
+        true => matches!(value >> (bits - 1), 0 | -1),
+        false => value >= 0 && (value as u64) >> bits == 0,
+    }
+}
+
+///
+/// Reads the first row or an escaped value of a column, as a varint or at the full bit-width.
+///
+/// A column of a declared bit-width is written in just enough nibbles for that bit-width,
+/// so its values are sign or zero extended, and a value outside of it is rejected.
+///
+#[inline(always)]
+fn read_value<T: DecodeColumn>(
+    iter: &mut HalfIter<'_>,
+    varint: bool,
+    bits: Option<u32>,
+) -> Result<i64, CodingError> {
+    let value = match (varint, bits) {
+        (true, _) => read_varint_row(iter)? as i64,
+        (false, None) => return T::read_first(iter),
+        (false, Some(bits)) => {
+            let width = 4 * bits.div_ceil(4);
+            let word = read_nibbles(iter, width as usize / 4)?;
+            match T::SIGNED {
+                true => sign_extend(word, width),
+                false => word as i64,
             }
-            headers::THIRTY_TWO_BITS_ONE_SAMPLE => {
-                // 1 sample of 32 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 32;
-                let shift = 32 - padding - bit_width;
-                for i in 0..1 {
-                    let delta = (word >> (shift - bit_width * i)) as i16;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i16 + delta) as i8;
-                    output.push(value);
+        }
+    };
+    match bits {
+        Some(bits) if !fits_bits(value, bits, T::SIGNED) => Err(CodingError::InvalidBits),
+        _ => Ok(value),
+    }
+}
+
+///
+/// Reads the delta between the first and second row, as a varint or at double the bit-width.
+///
+/// A column of a declared bit-width writes its first delta in one bit more than that bit-width.
+///
+#[inline(always)]
+fn read_first_delta<T: DecodeColumn>(
+    iter: &mut HalfIter<'_>,
+    varint: bool,
+    bits: Option<u32>,
+) -> Result<i64, CodingError> {
+    let delta = match (varint, bits) {
+        (true, _) => read_varint_row(iter)? as i64,
+        (false, None) => return T::read_second(iter),
+        (false, Some(bits)) => {
+            let width = 4 * (bits + 1).div_ceil(4);
+            sign_extend(read_nibbles(iter, width as usize / 4)?, width)
+        }
+    };
+    match bits {
+        Some(bits) if !fits_bits(delta, bits + 1, true) => Err(CodingError::InvalidBits),
+        _ => Ok(delta),
+    }
+}
+
+///
+/// Decodes the deltas of the word following the tag, keeping the last delta in `last_delta`.
+///
+/// The whole word is read before any value is pushed, so a word cut short
+/// leaves the column untouched.
+///
+#[inline(always)]
+fn decode_delta_word<T: DecodeColumn>(
+    iter: &mut HalfIter<'_>,
+    tag: u8,
+    value: &mut i64,
+    last_delta: &mut i64,
+    end: usize,
+    bits: Option<u32>,
+    output: &mut Vec<T>,
+) -> Result<(), CodingError> {
+    // The padding, bit-width, and number of samples in the word
+    let (padding, bit_width, samples) = match tag {
+        headers::THREE_BITS_TEN_SAMPLES => (2, 3, 10),
+        headers::SIX_BITS_FIVE_SAMPLES => (2, 6, 5),
+        headers::EIGHT_BITS_FOUR_SAMPLES => (0, 8, 4),
+        headers::TEN_BITS_THREE_SAMPLES => (2, 10, 3),
+        headers::SIXTEEN_BITS_TWO_SAMPLES => (0, 16, 2),
+        headers::THIRTY_TWO_BITS_ONE_SAMPLE => (0, 32, 1),
+        headers::SIXTY_FOUR_BITS_ONE_SAMPLE => (0, 64, 1),
+        headers::REPEATED_DELTAS => {
+            let count = read_nibble_varint(iter)?;
+            let delta = unzigzag(read_nibble_varint(iter)?);
+            reserve_room(output, end, count)?;
+            let mut remaining = count as usize;
+            while remaining > 0 {
+                let step = try_reserve_step(output, remaining)?;
+                for _ in 0..step {
+                    *value = value.wrapping_add(delta);
+                    output.push(T::from_wrapped(*value));
                 }
+                remaining -= step;
             }
-            _ => {
-                // Delta-Delta encoding, or not implemented
-                todo!("Implement i8 delta-delta");
+            if count > 0 {
+                *last_delta = delta;
             }
+            return Ok(());
+        }
+        headers::ESCAPE_DELTA => {
+            let escaped = read_value::<T>(iter, false, bits)?;
+            reserve_room(output, end, 1)?;
+            *value = escaped;
+            *last_delta = 0;
+            output.push(T::from_wrapped(*value));
+            return Ok(());
         }
+        _ => return Err(CodingError::InvalidTag(tag)),
+    };
+
+    let word_width = if bit_width == 64 { 64 } else { 32 };
+    let word = read_nibbles(iter, word_width / 4)?;
+    reserve_room(output, end, samples as u64)?;
+    let mask = u64::MAX >> (64 - bit_width);
+    let shift = word_width - padding - bit_width;
+    for i in 0..samples {
+        let delta = unzigzag((word >> (shift - bit_width * i)) & mask);
+        *value = value.wrapping_add(delta);
+        *last_delta = delta;
+        output.push(T::from_wrapped(*value));
     }
 
     Ok(())
 }
 
 ///
-/// Decodes 16-bit integers according to the delta encoding scheme.
+/// Reads the zigzag encoded delta-delta that follows a delta-delta prefix of up to 64 bits.
 ///
-/// This function reads the HalfIter in chunks, decodes the chunks according to the delta encoding scheme,
-/// and writes the decoded values to the Vec<i16>.
+#[inline(always)]
+fn read_delta_delta(iter: &mut HalfIter<'_>, tag: u8) -> Result<u64, CodingError> {
+    match tag {
+        headers::DELTA_DELTA_ZERO => Ok(0),
+        headers::DELTA_DELTA_SIXTEEN_BITS => read_nibbles(iter, 4),
+        headers::DELTA_DELTA_THIRTY_TWO_BITS => read_nibbles(iter, 8),
+        headers::DELTA_DELTA_SIXTY_FOUR_BITS => read_nibbles(iter, 16),
+        _ if tag & 0b1110 == headers::DELTA_DELTA_FIVE_BITS => {
+            Ok(((tag as u64 & 1) << 4) | read_nibbles(iter, 1)?)
+        }
+        _ if tag & 0b1110 == headers::DELTA_DELTA_NINE_BITS => {
+            Ok(((tag as u64 & 1) << 8) | read_nibbles(iter, 2)?)
+        }
+        _ => Err(CodingError::InvalidTag(tag)),
+    }
+}
+
 ///
-pub fn decode_i16(iter: &mut HalfIter<'_>, output: &mut Vec<i16>) -> Result<(), CodingError> {
-    // Check for 0 rows
-    match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::FIRST_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
-    }
-
-    // Full 16 bit value
-    let buf = [
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-    ];
-    let value = read_full_i16(&buf);
-    output.push(value);
-
-    // One row
-    match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::SECOND_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
-    }
-
-    // Delta encoded 32 bit value
-    let buf = [
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-    ];
-    let delta = read_full_i32(&buf);
-    let mut value = (value as i32 + delta) as i16;
-    output.push(value);
-
-    while let Some(tag) = iter.next() {
-        match tag {
-            headers::START_OF_COLUMN => {
-                // Start of column of next column
-                break;
-            }
-            headers::THREE_BITS_TEN_SAMPLES => {
-                // 2 bit pad, 10 samples of 3 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 3;
-                let shift = 32 - padding - bit_width;
-                for i in 0..10 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b111) as i32;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i32 + delta) as i16;
-                    output.push(value);
-                }
-            }
-            headers::SIX_BITS_FIVE_SAMPLES => {
-                // 5 samples of 6 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 6;
-                let shift = 32 - padding - bit_width;
-                for i in 0..5 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111) as i32;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i32 + delta) as i16;
-                    output.push(value);
-                }
-            }
-            headers::EIGHT_BITS_FOUR_SAMPLES => {
-                // 4 samples of 8 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 8;
-                let shift = 32 - padding - bit_width;
-                for i in 0..4 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b1111_1111) as i32;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i32 + delta) as i16;
-                    output.push(value);
-                }
-            }
-            headers::TEN_BITS_THREE_SAMPLES => {
-                // 2 bit pad, 3 samples of 10 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 10;
-                let shift = 32 - padding - bit_width;
-                for i in 0..3 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111_1111) as i32;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i32 + delta) as i16;
-                    output.push(value);
-                }
-            }
-            headers::SIXTEEN_BITS_TWO_SAMPLES => {
-                // 2 samples of 16 bit
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 16;
-                let shift = 32 - padding - bit_width;
-                for i in 0..2 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0xffff) as i32;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i32 + delta) as i16;
-                    output.push(value);
-                }
-            }
-            headers::THIRTY_TWO_BITS_ONE_SAMPLE => {
-                // 1 sample of 32 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 32;
-                let shift = 32 - padding - bit_width;
-                for i in 0..1 {
-                    let delta = (word >> (shift - bit_width * i)) as i32;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i32 + delta) as i16;
-                    output.push(value);
-                }
-            }
-            _ => {
-                // Delta-Delta encoding, or not implemented
-                todo!("Implement i16 delta-delta");
-            }
+/// Reads the zigzag encoded delta-delta that follows any delta-delta prefix of an i128 column.
+///
+#[inline(always)]
+fn read_i128_delta_delta(iter: &mut HalfIter<'_>, tag: u8) -> Result<u128, CodingError> {
+    match tag {
+        headers::DELTA_DELTA_ONE_HUNDRED_TWENTY_EIGHT_BITS => {
+            Ok(((read_nibbles(iter, 16)? as u128) << 64) | read_nibbles(iter, 16)? as u128)
         }
+        _ => Ok(read_delta_delta(iter, tag)? as u128),
+    }
+}
+
+///
+/// Applies the delta-delta following the tag to the value and delta of an i128 column,
+/// or restarts them at an escaped full bit-width value.
+///
+#[inline(always)]
+fn step_i128_delta_delta(
+    iter: &mut HalfIter<'_>,
+    tag: u8,
+    value: i128,
+    delta: i128,
+) -> Result<(i128, i128), CodingError> {
+    if tag == headers::ESCAPE_DELTA_DELTA {
+        return Ok((read_full_i128(&read_bytes::<16>(iter)?), 0));
     }
+    let zigzag = read_i128_delta_delta(iter, tag)?;
+    let delta = delta.wrapping_add(((zigzag >> 1) as i128) ^ -((zigzag & 1) as i128));
+    Ok((value.wrapping_add(delta), delta))
+}
+
+///
+/// A float column, XOR encoded against the previous value of the column.
+///
+pub trait XorColumn: Copy {
+    /// The nibbles of the bits of the float.
+    const NIBBLES: usize;
+
+    /// Reinterprets the low bits of a word as a float.
+    fn from_xor_bits(bits: u64) -> Self;
+}
+
+impl XorColumn for f32 {
+    const NIBBLES: usize = 8;
+
+    #[inline(always)]
+    fn from_xor_bits(bits: u64) -> Self {
+        f32::from_bits(bits as u32)
+    }
+}
+
+impl XorColumn for f64 {
+    const NIBBLES: usize = 16;
+
+    #[inline(always)]
+    fn from_xor_bits(bits: u64) -> Self {
+        f64::from_bits(bits)
+    }
+}
+
+///
+/// The bits of the last value of an XOR column, and the window of meaningful nibbles of the last XOR.
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct XorState {
+    bits: u64,
+    leading: usize,
+    meaningful: usize,
+}
+
+///
+/// Decodes the XOR word following a tag into the next value of an XOR column.
+///
+/// The whole word is read before the state is changed, so a word cut short leaves the state as it was.
+/// Returns `CodingError::InvalidBits` if a new window does not fit the float.
+///
+fn decode_xor_word<T: XorColumn>(
+    iter: &mut HalfIter<'_>,
+    tag: u8,
+    state: &mut XorState,
+) -> Result<(), CodingError> {
+    let (leading, meaningful) = match tag {
+        headers::XOR_REPEATED => return Ok(()),
+        headers::XOR_REUSE_WINDOW if state.meaningful > 0 => (state.leading, state.meaningful),
+        headers::XOR_NEW_WINDOW => {
+            let leading = iter.next().ok_or(CodingError::NotEnoughBits)? as usize;
+            let meaningful = iter.next().ok_or(CodingError::NotEnoughBits)? as usize + 1;
+            if leading + meaningful > T::NIBBLES {
+                return Err(CodingError::InvalidBits);
+            }
+            (leading, meaningful)
+        }
+        _ => return Err(CodingError::InvalidTag(tag)),
+    };
 
+    let xor = read_nibbles(iter, meaningful)? << (4 * (T::NIBBLES - leading - meaningful));
+    *state = XorState {
+        bits: state.bits ^ xor,
+        leading,
+        meaningful,
+    };
     Ok(())
 }
 
 ///
-/// Decodes 32-bit integers according to the delta encoding scheme.
+/// Decodes the presence column of an `Option<T>` column.
 ///
-/// This function reads the HalfIter in chunks, decodes the chunks according to the delta encoding scheme,
-/// and writes the decoded values to the Vec<i32>.
+/// The column holds the lengths of alternating runs of present and absent rows,
+/// starting with a (possibly empty) run of present rows, until all rows are covered.
+/// The start of the value column that follows is consumed.
 ///
-pub fn decode_i32(iter: &mut HalfIter<'_>, output: &mut Vec<i32>) -> Result<(), CodingError> {
-    // Check for 0 rows
-    match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::FIRST_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
-    }
-
-    // Full 32 bit value
-    let buf = [
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-    ];
-    let value = read_full_i32(&buf);
-    output.push(value);
-
-    // One row
+pub fn decode_presence(
+    iter: &mut HalfIter<'_>,
+    rows: usize,
+    runs: &mut Vec<usize>,
+) -> Result<(), CodingError> {
+    let mut cursor = PresenceCursor::default();
+    while !step_presence(iter, rows, &mut cursor, runs)? {}
+
     match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::SECOND_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
-    }
-
-    // Delta encoded 64 bit value
-    let buf = [
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-    ];
-    let delta = read_full_i64(&buf);
-    let mut value = (value as i64 + delta) as i32;
-    output.push(value);
-
-    while let Some(tag) = iter.next() {
-        match tag {
-            headers::START_OF_COLUMN => {
-                // Start of column of next column
-                break;
-            }
-            headers::THREE_BITS_TEN_SAMPLES => {
-                // 2 bit pad, 10 samples of 3 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 3;
-                let shift = 32 - padding - bit_width;
-                for i in 0..10 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b111) as i64;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i64 + delta) as i32;
-                    output.push(value);
-                }
-            }
-            headers::SIX_BITS_FIVE_SAMPLES => {
-                // 5 samples of 6 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 6;
-                let shift = 32 - padding - bit_width;
-                for i in 0..5 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111) as i64;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i64 + delta) as i32;
-                    output.push(value);
-                }
+        Some(headers::START_OF_COLUMN) => Ok(()),
+        _ => Err(CodingError::InvalidColumnTag),
+    }
+}
+
+///
+/// Expands the present values of an `Option<T>` column with the runs of its presence column.
+///
+pub fn merge_presence<T: Copy>(
+    runs: &[usize],
+    values: &[T],
+    output: &mut Vec<Option<T>>,
+) -> Result<(), CodingError> {
+    let mut cursor = PresenceCursor::default();
+    merge_presence_partial(runs, values, &mut cursor, output)?;
+
+    // Every run must be expanded, and every present value must have a row
+    if cursor.run < runs.len() || cursor.value != values.len() {
+        return Err(CodingError::InvalidBits);
+    }
+    Ok(())
+}
+
+///
+/// A decoded value that can be reduced to the declared bit-width of a `#[tsz(wrapping = N)]` column.
+///
+pub trait WrapBits: Copy {
+    ///
+    /// Keeps the low `bits` bits of the value, sign extending signed values.
+    ///
+    fn wrap_bits(self, bits: u32) -> Self;
+}
+
+macro_rules! impl_wrap_bits {
+    ($ty:ty) => {
+        impl WrapBits for $ty {
+            #[inline(always)]
+            fn wrap_bits(self, bits: u32) -> Self {
+                let shift = <$ty>::BITS - bits;
+                (self << shift) >> shift
             }
-            headers::EIGHT_BITS_FOUR_SAMPLES => {
-                // 4 samples of 8 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 8;
-                let shift = 32 - padding - bit_width;
-                for i in 0..4 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b1111_1111) as i64;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i64 + delta) as i32;
-                    output.push(value);
+        }
+    };
+}
+
+impl_wrap_bits!(i8);
+impl_wrap_bits!(i16);
+impl_wrap_bits!(i32);
+impl_wrap_bits!(i64);
+impl_wrap_bits!(i128);
+impl_wrap_bits!(u8);
+impl_wrap_bits!(u16);
+impl_wrap_bits!(u32);
+impl_wrap_bits!(u64);
+
+impl<T: WrapBits> WrapBits for Option<T> {
+    #[inline(always)]
+    fn wrap_bits(self, bits: u32) -> Self {
+        self.map(|value| value.wrap_bits(bits))
+    }
+}
+
+///
+/// Reduces decoded values to the declared bit-width of a `#[tsz(wrapping = N)]` column.
+///
+/// The deltas of the column wrap modulo `2^bits`, so the running value is only meaningful in its low bits.
+///
+pub fn wrap_column<T: WrapBits>(values: &mut [T], bits: u32) {
+    for value in values {
+        *value = value.wrap_bits(bits);
+    }
+}
+
+///
+/// The progress of decoding a column word by word, see `StepColumn`.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum ColumnPhase {
+    #[default]
+    FirstRow,
+    SecondRow {
+        delta_delta: bool,
+    },
+    Delta,
+    DeltaDelta,
+}
+
+///
+/// The running value and delta of a column decoded word by word.
+///
+/// The value and delta are kept between packets, so a continuation packet can resume from them.
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColumnCursor<W> {
+    phase: ColumnPhase,
+    value: W,
+    delta: W,
+    continuable: bool,
+}
+
+impl<W> ColumnCursor<W> {
+    ///
+    /// Starts the column of the next packet, keeping the value and delta of the previous packet.
+    ///
+    pub fn next_packet(&mut self) {
+        self.phase = ColumnPhase::FirstRow;
+    }
+
+    ///
+    /// Resumes a column continued from the previous packet,
+    /// returning `CodingError::MissingKeyframe` if the previous packet had no delta for it.
+    ///
+    fn resume(&mut self, delta_delta: bool) -> Result<(), CodingError> {
+        if !self.continuable {
+            return Err(CodingError::MissingKeyframe);
+        }
+        self.phase = match delta_delta {
+            true => ColumnPhase::DeltaDelta,
+            false => ColumnPhase::Delta,
+        };
+        Ok(())
+    }
+}
+
+///
+/// A column value that can be decoded one word at a time,
+/// resuming from a cursor between words.
+///
+pub trait StepColumn: Copy {
+    /// The width of the running value and delta.
+    type Wide: Copy + Default + core::fmt::Debug;
+
+    ///
+    /// Decodes the next word of the column, pushing at most up to `end` values.
+    ///
+    /// A word cut short returns `CodingError::NotEnoughBits` without changing the cursor
+    /// or the output, so it can be retried once more bits are available.
+    ///
+    /// `bits` is the declared bit-width of a `#[tsz(bits = N)]` column, if any.
+    ///
+    fn step(
+        iter: &mut HalfIter<'_>,
+        cursor: &mut ColumnCursor<Self::Wide>,
+        end: usize,
+        bits: Option<u32>,
+        output: &mut Vec<Self>,
+    ) -> Result<(), CodingError>;
+}
+
+///
+/// Decodes the next word of a column of 64 bits or less.
+///
+fn step_column<T: DecodeColumn>(
+    iter: &mut HalfIter<'_>,
+    cursor: &mut ColumnCursor<i64>,
+    end: usize,
+    bits: Option<u32>,
+    output: &mut Vec<T>,
+) -> Result<(), CodingError> {
+    let tag = iter.next().ok_or(CodingError::NotEnoughBits)?;
+    match cursor.phase {
+        ColumnPhase::FirstRow => {
+            let (delta_delta, varint) = match tag {
+                headers::FIRST_ROW => (false, false),
+                headers::FIRST_ROW_DELTA_DELTA => (true, false),
+                headers::FIRST_ROW_VARINT => (false, true),
+                headers::FIRST_ROW_DELTA_DELTA_VARINT => (true, true),
+                headers::CONTINUE_DELTA => return cursor.resume(false),
+                headers::CONTINUE_DELTA_DELTA => return cursor.resume(true),
+                _ => return Err(CodingError::InvalidTag(tag)),
+            };
+            let value = read_value::<T>(iter, varint, bits)?;
+            reserve_room(output, end, 1)?;
+            output.push(T::from_wrapped(value));
+            cursor.value = value;
+            cursor.phase = ColumnPhase::SecondRow { delta_delta };
+        }
+        ColumnPhase::SecondRow { delta_delta } => {
+            let delta = match tag {
+                headers::SECOND_ROW => read_first_delta::<T>(iter, false, bits)?,
+                headers::SECOND_ROW_VARINT => read_first_delta::<T>(iter, true, bits)?,
+                _ => return Err(CodingError::InvalidTag(tag)),
+            };
+            reserve_room(output, end, 1)?;
+            cursor.value = cursor.value.wrapping_add(delta);
+            cursor.delta = delta;
+            cursor.continuable = true;
+            output.push(T::from_wrapped(cursor.value));
+            cursor.phase = match delta_delta {
+                true => ColumnPhase::DeltaDelta,
+                false => ColumnPhase::Delta,
+            };
+        }
+        ColumnPhase::Delta => decode_delta_word(
+            iter,
+            tag,
+            &mut cursor.value,
+            &mut cursor.delta,
+            end,
+            bits,
+            output,
+        )?,
+        ColumnPhase::DeltaDelta => {
+            let (value, delta) = match tag {
+                headers::ESCAPE_DELTA_DELTA => (read_value::<T>(iter, false, bits)?, 0),
+                _ => {
+                    let delta = cursor
+                        .delta
+                        .wrapping_add(unzigzag(read_delta_delta(iter, tag)?));
+                    (cursor.value.wrapping_add(delta), delta)
                 }
+            };
+            reserve_room(output, end, 1)?;
+            cursor.value = value;
+            cursor.delta = delta;
+            output.push(T::from_wrapped(cursor.value));
+        }
+    }
+
+    Ok(())
+}
+
+macro_rules! impl_step_column {
+    ($ty:ty) => {
+        impl StepColumn for $ty {
+            type Wide = i64;
+
+            #[inline(always)]
+            fn step(
+                iter: &mut HalfIter<'_>,
+                cursor: &mut ColumnCursor<i64>,
+                end: usize,
+                bits: Option<u32>,
+                output: &mut Vec<Self>,
+            ) -> Result<(), CodingError> {
+                step_column(iter, cursor, end, bits, output)
             }
+        }
+    };
+}
 
-            headers::TEN_BITS_THREE_SAMPLES => {
-                // 2 bit pad, 3 samples of 10 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 10;
-                let shift = 32 - padding - bit_width;
-                for i in 0..3 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111_1111) as i64;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i64 + delta) as i32;
-                    output.push(value);
-                }
+impl_step_column!(i8);
+impl_step_column!(i16);
+impl_step_column!(i32);
+impl_step_column!(i64);
+impl_step_column!(u8);
+impl_step_column!(u16);
+impl_step_column!(u32);
+impl_step_column!(u64);
+
+impl StepColumn for i128 {
+    type Wide = i128;
+
+    fn step(
+        iter: &mut HalfIter<'_>,
+        cursor: &mut ColumnCursor<i128>,
+        end: usize,
+        _bits: Option<u32>,
+        output: &mut Vec<Self>,
+    ) -> Result<(), CodingError> {
+        let tag = iter.next().ok_or(CodingError::NotEnoughBits)?;
+        match cursor.phase {
+            ColumnPhase::FirstRow => {
+                let value = match tag {
+                    headers::FIRST_ROW_DELTA_DELTA => read_full_i128(&read_bytes::<16>(iter)?),
+                    headers::FIRST_ROW_DELTA_DELTA_VARINT => read_varint_row(iter)?,
+                    headers::CONTINUE_DELTA_DELTA => return cursor.resume(true),
+                    _ => return Err(CodingError::InvalidTag(tag)),
+                };
+                reserve_room(output, end, 1)?;
+                output.push(value);
+                cursor.value = value;
+                cursor.phase = ColumnPhase::SecondRow { delta_delta: true };
             }
-            headers::SIXTEEN_BITS_TWO_SAMPLES => {
-                // 2 samples of 16 bit
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 16;
-                let shift = 32 - padding - bit_width;
-                for i in 0..2 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0xffff) as i64;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i64 + delta) as i32;
-                    output.push(value);
-                }
+            ColumnPhase::SecondRow { .. } => {
+                let delta = match tag {
+                    headers::SECOND_ROW => read_full_i128(&read_bytes::<16>(iter)?),
+                    headers::SECOND_ROW_VARINT => read_varint_row(iter)?,
+                    _ => return Err(CodingError::InvalidTag(tag)),
+                };
+                reserve_room(output, end, 1)?;
+                cursor.value = cursor.value.wrapping_add(delta);
+                cursor.delta = delta;
+                cursor.continuable = true;
+                output.push(cursor.value);
+                cursor.phase = ColumnPhase::DeltaDelta;
             }
-            headers::THIRTY_TWO_BITS_ONE_SAMPLE => {
-                // 1 sample of 32 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 32;
-                let shift = 32 - padding - bit_width;
-                for i in 0..1 {
-                    let delta = (word >> (shift - bit_width * i)) as i64;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i64 + delta) as i32;
-                    output.push(value);
-                }
+            ColumnPhase::Delta | ColumnPhase::DeltaDelta => {
+                let (value, delta) = step_i128_delta_delta(iter, tag, cursor.value, cursor.delta)?;
+                reserve_room(output, end, 1)?;
+                cursor.value = value;
+                cursor.delta = delta;
+                output.push(cursor.value);
             }
-            headers::SIXTY_FOUR_BITS_ONE_SAMPLE => {
-                // 1 sample of 64 bits
-                let mut word: u64 = 0;
-                for _ in 0..15 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u64;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u64;
-
-                let padding = 0;
-                let bit_width = 64;
-                let shift = 64 - padding - bit_width;
-                for i in 0..1 {
-                    let delta = (word >> (shift - bit_width * i)) as i64;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i64 + delta) as i32;
-                    output.push(value);
+        }
+
+        Ok(())
+    }
+}
+
+macro_rules! impl_step_xor_column {
+    ($ty:ty) => {
+        impl StepColumn for $ty {
+            type Wide = XorState;
+
+            fn step(
+                iter: &mut HalfIter<'_>,
+                cursor: &mut ColumnCursor<XorState>,
+                end: usize,
+                _bits: Option<u32>,
+                output: &mut Vec<Self>,
+            ) -> Result<(), CodingError> {
+                let tag = iter.next().ok_or(CodingError::NotEnoughBits)?;
+                let mut state = cursor.value;
+                match cursor.phase {
+                    ColumnPhase::FirstRow => {
+                        match tag {
+                            headers::FIRST_ROW => {}
+                            headers::CONTINUE_DELTA => return cursor.resume(false),
+                            _ => return Err(CodingError::InvalidTag(tag)),
+                        }
+                        state = XorState {
+                            bits: read_nibbles(iter, <$ty as XorColumn>::NIBBLES)?,
+                            ..Default::default()
+                        };
+                    }
+                    _ => decode_xor_word::<$ty>(iter, tag, &mut state)?,
                 }
-            }
-            _ => {
-                // Delta-Delta encoding, or not implemented
-                todo!("Implement i32 delta-delta");
+                reserve_room(output, end, 1)?;
+                cursor.value = state;
+                cursor.continuable = true;
+                cursor.phase = ColumnPhase::Delta;
+                output.push(<$ty as XorColumn>::from_xor_bits(state.bits));
+                Ok(())
             }
         }
+    };
+}
+
+impl_step_xor_column!(f32);
+impl_step_xor_column!(f64);
+
+///
+/// Decodes a column of `rows` values word by word, continuing from the cursor of the previous packet
+/// if the column starts with a continuation tag.
+///
+/// Exactly `rows` values are pushed, so a corrupted word cannot grow the column
+/// past the row count of the packet, and a column that ends short of it
+/// returns `CodingError::InvalidRowCount`. The start of the next column is consumed.
+///
+pub fn decode_column<T: StepColumn>(
+    iter: &mut HalfIter<'_>,
+    rows: usize,
+    cursor: &mut ColumnCursor<T::Wide>,
+    bits: Option<u32>,
+    output: &mut Vec<T>,
+) -> Result<(), CodingError> {
+    let end = output.len().saturating_add(rows);
+    cursor.next_packet();
+    while output.len() < end {
+        T::step(iter, cursor, end, bits, output).map_err(|error| match error {
+            // No word starts with the start of a column, so the column ended short of the row count
+            CodingError::InvalidTag(headers::START_OF_COLUMN) => CodingError::InvalidRowCount(rows),
+            error => error,
+        })?;
     }
 
-    Ok(())
+    match iter.next() {
+        Some(headers::START_OF_COLUMN) | None => Ok(()),
+        Some(_) => Err(CodingError::InvalidColumnTag),
+    }
 }
 
 ///
-/// Decodes 64-bit integers according to the delta encoding scheme.
+/// Checks the sequence field of a packet against the packet expected next,
+/// returning true if the packet continues the previous packet.
 ///
-/// This function reads the HalfIter in chunks, decodes the chunks according to the delta encoding scheme,
-/// and writes the decoded values to the Vec<i64>.
+/// A continuation packet that does not follow the last decoded packet returns
+/// `CodingError::MissingKeyframe`, and every packet until the next keyframe is rejected.
 ///
-pub fn decode_i64(iter: &mut HalfIter<'_>, output: &mut Vec<i64>) -> Result<(), CodingError> {
-    // Check for 0 rows
-    match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::FIRST_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
-    }
-
-    // Full 64 bit value
-    let buf = [
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-    ];
-    let value = read_full_i64(&buf);
-    output.push(value);
-
-    // One row
-    match iter.next() {
-        None => return Ok(()),
-        Some(headers::START_OF_COLUMN) => return Ok(()),
-        Some(headers::SECOND_ROW) => {}
-        _ => return Err(CodingError::InvalidBits),
-    }
-
-    // Delta encoded 128 bit value
-    let buf = [
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-        (iter.next().ok_or(CodingError::NotEnoughBits)? << 4)
-            | iter.next().ok_or(CodingError::NotEnoughBits)?,
-    ];
-    let delta = read_full_i128(&buf);
-    let mut value = (value as i128 + delta) as i64;
-    output.push(value);
-
-    while let Some(tag) = iter.next() {
-        match tag {
-            headers::START_OF_COLUMN => {
-                // Start of column of next column
-                break;
-            }
-            headers::THREE_BITS_TEN_SAMPLES => {
-                // 2 bit pad, 10 samples of 3 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 3;
-                let shift = 32 - padding - bit_width;
-                for i in 0..10 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b111) as i128;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i128 + delta) as i64;
-                    output.push(value);
-                }
-            }
-            headers::SIX_BITS_FIVE_SAMPLES => {
-                // 5 samples of 6 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 6;
-                let shift = 32 - padding - bit_width;
-                for i in 0..5 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111) as i128;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i128 + delta) as i64;
-                    output.push(value);
-                }
-            }
-            headers::EIGHT_BITS_FOUR_SAMPLES => {
-                // 4 samples of 8 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 8;
-                let shift = 32 - padding - bit_width;
-                for i in 0..4 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b1111_1111) as i128;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i128 + delta) as i64;
-                    output.push(value);
-                }
-            }
+pub fn check_sequence(expected: &mut Option<u16>, field: u16) -> Result<bool, CodingError> {
+    let sequence_number = field & sequence::MASK;
+    let continues = field & sequence::CONTINUATION != 0;
+    if continues && *expected != Some(sequence_number) {
+        *expected = None;
+        return Err(CodingError::MissingKeyframe);
+    }
+    *expected = Some(sequence_number.wrapping_add(1) & sequence::MASK);
+    Ok(continues)
+}
 
-            headers::TEN_BITS_THREE_SAMPLES => {
-                // 2 bit pad, 3 samples of 10 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 2;
-                let bit_width = 10;
-                let shift = 32 - padding - bit_width;
-                for i in 0..3 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0b11_1111_1111) as i128;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i128 + delta) as i64;
-                    output.push(value);
-                }
-            }
-            headers::SIXTEEN_BITS_TWO_SAMPLES => {
-                // 2 samples of 16 bit
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 16;
-                let shift = 32 - padding - bit_width;
-                for i in 0..2 {
-                    let delta = ((word >> (shift - bit_width * i)) & 0xffff) as i128;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i128 + delta) as i64;
-                    output.push(value);
-                }
-            }
-            headers::THIRTY_TWO_BITS_ONE_SAMPLE => {
-                // 1 sample of 32 bits
-                let mut word: u32 = 0;
-                for _ in 0..7 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u32;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u32;
-
-                let padding = 0;
-                let bit_width = 32;
-                let shift = 32 - padding - bit_width;
-                for i in 0..1 {
-                    let delta = (word >> (shift - bit_width * i)) as i128;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i128 + delta) as i64;
-                    output.push(value);
-                }
-            }
-            headers::SIXTY_FOUR_BITS_ONE_SAMPLE => {
-                // 1 sample of 64 bits
-                let mut word: u64 = 0;
-                for _ in 0..15 {
-                    let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                    word |= half as u64;
-                    word <<= 4;
-                }
-                let half = iter.next().ok_or(CodingError::NotEnoughBits)?;
-                word |= half as u64;
-
-                let padding = 0;
-                let bit_width = 64;
-                let shift = 64 - padding - bit_width;
-                for i in 0..1 {
-                    let delta = (word >> (shift - bit_width * i)) as i128;
-                    let delta = (delta >> 1) ^ -(delta & 1);
-                    value = (value as i128 + delta) as i64;
-                    output.push(value);
-                }
+///
+/// The progress of decoding a presence column run by run,
+/// and of expanding the present values with the runs.
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PresenceCursor {
+    /// The number of rows covered by the decoded runs.
+    total: usize,
+    /// The number of present rows in the decoded runs.
+    present: usize,
+    /// The index of the run being expanded.
+    run: usize,
+    /// The number of rows of the run already expanded.
+    offset: usize,
+    /// The number of present values already expanded.
+    value: usize,
+}
+
+impl PresenceCursor {
+    ///
+    /// The number of present rows in the decoded runs.
+    ///
+    pub fn present(&self) -> usize {
+        self.present
+    }
+}
+
+///
+/// Decodes the next run of a presence column, returning true once the runs cover all rows.
+///
+/// No bits are read once the runs cover all rows. Returns `CodingError::InvalidBits`
+/// if a run would cover more than `rows` or `MAX_PACKET_ROWS` rows.
+///
+pub fn step_presence(
+    iter: &mut HalfIter<'_>,
+    rows: usize,
+    cursor: &mut PresenceCursor,
+    runs: &mut Vec<usize>,
+) -> Result<bool, CodingError> {
+    if cursor.total >= rows {
+        return Ok(true);
+    }
+
+    let run = read_nibble_varint(iter)? as usize;
+    cursor.total = cursor
+        .total
+        .checked_add(run)
+        .filter(|total| *total <= rows.min(MAX_PACKET_ROWS))
+        .ok_or(CodingError::InvalidBits)?;
+    if runs.len().is_multiple_of(2) {
+        cursor.present += run;
+    }
+    runs.push(run);
+    Ok(cursor.total >= rows)
+}
+
+///
+/// Expands as many rows of an `Option<T>` column as the present values decoded so far allow.
+///
+/// The cursor remembers where the expansion stopped, so it can be called again as values arrive.
+/// Absent rows are pushed a step at a time, returning `CodingError::AllocationFailed`
+/// if the column cannot grow.
+///
+pub fn merge_presence_partial<T: Copy>(
+    runs: &[usize],
+    values: &[T],
+    cursor: &mut PresenceCursor,
+    output: &mut Vec<Option<T>>,
+) -> Result<(), CodingError> {
+    while let Some(run) = runs.get(cursor.run) {
+        let remaining = run - cursor.offset;
+        if cursor.run.is_multiple_of(2) {
+            let available = values.len().saturating_sub(cursor.value).min(remaining);
+            output
+                .try_reserve(available)
+                .map_err(|_| CodingError::AllocationFailed)?;
+            output.extend(
+                values[cursor.value..cursor.value + available]
+                    .iter()
+                    .map(|value| Some(*value)),
+            );
+            cursor.value += available;
+            cursor.offset += available;
+            if available < remaining {
+                return Ok(());
             }
-            _ => {
-                // Delta-Delta encoding, or not implemented
-                todo!("Implement i64 delta-delta");
+        } else {
+            while cursor.offset < *run {
+                let step = try_reserve_step(output, run - cursor.offset)?;
+                output.extend(core::iter::repeat_n(None, step));
+                cursor.offset += step;
             }
         }
-    }
 
+        cursor.run += 1;
+        cursor.offset = 0;
+    }
     Ok(())
 }
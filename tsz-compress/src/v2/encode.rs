@@ -2,9 +2,9 @@ use core::fmt::Binary;
 use num_traits::PrimInt;
 
 use crate::prelude::*;
-use crate::v2::consts::headers;
+use crate::v2::consts::{headers, schema};
 
-use super::halfvec::{HalfVec, HalfWord};
+use super::halfvec::{HalfBytes, HalfVec, HalfWord};
 
 ///
 /// A trait for types that can be represented as bits.
@@ -18,13 +18,13 @@ pub trait Bits: PrimInt + Binary {
 
     /// Language limitations prevent us from writing simple math expressions
     /// ((self << 1) ^ self >> (Self::BITS - 1)) as u32
-    fn zigzag(self) -> usize;
+    fn zigzag(self) -> u64;
 
     /// Return the zigzag encoding and number of bits required to represent the value
     #[inline(always)]
-    fn zigzag_bits(self) -> (usize, usize) {
+    fn zigzag_bits(self) -> (u64, usize) {
         let zbits = self.zigzag();
-        (zbits, (usize::BITS - zbits.leading_zeros()) as usize)
+        (zbits, (u64::BITS - zbits.leading_zeros()) as usize)
     }
 }
 
@@ -32,8 +32,8 @@ impl Bits for i8 {
     const BITS: usize = 8;
 
     #[inline(always)]
-    fn zigzag(self) -> usize {
-        ((self << 1) ^ self >> (Self::BITS - 1)) as u8 as usize
+    fn zigzag(self) -> u64 {
+        ((self << 1) ^ self >> (Self::BITS - 1)) as u8 as u64
     }
 }
 
@@ -41,8 +41,8 @@ impl Bits for i16 {
     const BITS: usize = 16;
 
     #[inline(always)]
-    fn zigzag(self) -> usize {
-        ((self << 1) ^ self >> (Self::BITS - 1)) as u16 as usize
+    fn zigzag(self) -> u64 {
+        ((self << 1) ^ self >> (Self::BITS - 1)) as u16 as u64
     }
 }
 
@@ -50,23 +50,25 @@ impl Bits for i32 {
     const BITS: usize = 32;
 
     #[inline(always)]
-    fn zigzag(self) -> usize {
-        ((self << 1) ^ self >> (Self::BITS - 1)) as u32 as usize
+    fn zigzag(self) -> u64 {
+        ((self << 1) ^ self >> (Self::BITS - 1)) as u32 as u64
     }
 }
 
-#[cfg(target_pointer_width = "64")]
 impl Bits for i64 {
     const BITS: usize = 64;
 
     #[inline(always)]
-    fn zigzag(self) -> usize {
-        ((self << 1) ^ self >> (Self::BITS - 1)) as u64 as usize
+    fn zigzag(self) -> u64 {
+        ((self << 1) ^ self >> (Self::BITS - 1)) as u64
     }
 }
 
 #[inline(always)]
-fn push_three_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
+fn push_three_bits<const Q: usize, S: HalfBytes>(
+    q: &mut CompressionQueue<Q>,
+    buf: &mut HalfVec<S>,
+) {
     const N: usize = 10;
     const N1: usize = N - 1;
     buf.push(HalfWord::Half(headers::THREE_BITS_TEN_SAMPLES));
@@ -81,7 +83,7 @@ fn push_three_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
 }
 
 #[inline(always)]
-fn push_six_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
+fn push_six_bits<const Q: usize, S: HalfBytes>(q: &mut CompressionQueue<Q>, buf: &mut HalfVec<S>) {
     const N: usize = 5;
     const N1: usize = N - 1;
     buf.push(HalfWord::Half(headers::SIX_BITS_FIVE_SAMPLES));
@@ -96,7 +98,10 @@ fn push_six_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
 }
 
 #[inline(always)]
-fn push_eight_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
+fn push_eight_bits<const Q: usize, S: HalfBytes>(
+    q: &mut CompressionQueue<Q>,
+    buf: &mut HalfVec<S>,
+) {
     const N: usize = 4;
     const N1: usize = N - 1;
     buf.push(HalfWord::Half(headers::EIGHT_BITS_FOUR_SAMPLES));
@@ -111,7 +116,7 @@ fn push_eight_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
 }
 
 #[inline(always)]
-fn push_ten_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
+fn push_ten_bits<const Q: usize, S: HalfBytes>(q: &mut CompressionQueue<Q>, buf: &mut HalfVec<S>) {
     const N: usize = 3;
     const N1: usize = N - 1;
     buf.push(HalfWord::Half(headers::TEN_BITS_THREE_SAMPLES));
@@ -126,7 +131,10 @@ fn push_ten_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
 }
 
 #[inline(always)]
-fn push_sixteen_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
+fn push_sixteen_bits<const Q: usize, S: HalfBytes>(
+    q: &mut CompressionQueue<Q>,
+    buf: &mut HalfVec<S>,
+) {
     const N: usize = 2;
     const N1: usize = N - 1;
     buf.push(HalfWord::Half(headers::SIXTEEN_BITS_TWO_SAMPLES));
@@ -141,9 +149,12 @@ fn push_sixteen_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
 }
 
 #[inline(always)]
-unsafe fn push_32_or_64_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
+unsafe fn push_32_or_64_bits<const N: usize, S: HalfBytes>(
+    q: &mut CompressionQueue<N>,
+    buf: &mut HalfVec<S>,
+) {
     let value = q.pop().unwrap_unchecked();
-    if value <= u32::MAX as usize {
+    if value <= u32::MAX as u64 {
         buf.push(HalfWord::Half(headers::THIRTY_TWO_BITS_ONE_SAMPLE));
     } else {
         buf.push(HalfWord::Half(headers::SIXTY_FOUR_BITS_ONE_SAMPLE));
@@ -161,136 +172,326 @@ unsafe fn push_32_or_64_bits(q: &mut CompressionQueue<10>, buf: &mut HalfVec) {
 pub trait EmitDeltaBits {
     /// Emits bits according to the most efficient case of Delta Compression.
     /// Returns the number of elements popped from the queue.
-    fn emit_delta_bits(&mut self, out: &mut HalfVec) -> usize;
-    fn flush_delta_bits(&mut self, out: &mut HalfVec) -> usize;
+    fn emit_delta_bits<S: HalfBytes>(&mut self, out: &mut HalfVec<S>) -> usize;
+    /// Emits a word or the pending run of identical deltas, until the queue is flushed.
+    fn flush_delta_bits<S: HalfBytes>(&mut self, out: &mut HalfVec<S>) -> usize;
+    /// The number of nibbles that flushing the whole queue would emit.
+    fn flushed_delta_len(&self) -> usize;
 }
 
-impl EmitDeltaBits for CompressionQueue<10> {
-    #[inline(always)]
-    fn emit_delta_bits(&mut self, out: &mut HalfVec) -> usize {
-        let mut fits = [true; 5];
-
-        // Check if the values will fit in the cases
-        let values = self.peak_bitcounts::<10>();
-        for (index, bits_required) in values.into_iter().enumerate() {
-            if (index < 2) & (bits_required > 16) {
-                fits[4] = false;
-            }
-            if (index < 3) & (bits_required > 10) {
-                fits[3] = false;
-            }
-            if (index < 4) & (bits_required > 8) {
-                fits[2] = false;
-            }
-            if (index < 5) & (bits_required > 6) {
-                fits[1] = false;
-            }
-            if (index < 10) & (bits_required > 3) {
-                fits[0] = false;
-            }
+///
+/// Emits a single word of packed deltas from a full queue.
+///
+#[inline(always)]
+fn emit_delta_word<const N: usize, S: HalfBytes>(
+    q: &mut CompressionQueue<N>,
+    out: &mut HalfVec<S>,
+) -> usize {
+    let mut fits = [true; 5];
+
+    // Check if the values will fit in the cases
+    let values = q.peak_bitcounts::<10>();
+    for (index, bits_required) in values.into_iter().enumerate() {
+        if (index < 2) & (bits_required > 16) {
+            fits[4] = false;
         }
+        if (index < 3) & (bits_required > 10) {
+            fits[3] = false;
+        }
+        if (index < 4) & (bits_required > 8) {
+            fits[2] = false;
+        }
+        if (index < 5) & (bits_required > 6) {
+            fits[1] = false;
+        }
+        if (index < 10) & (bits_required > 3) {
+            fits[0] = false;
+        }
+    }
 
-        // Emit according to priority of cases
-        if fits[0] {
-            push_three_bits(self, out);
+    // Emit according to priority of cases
+    if fits[0] {
+        push_three_bits(q, out);
+        10
+    } else if fits[1] {
+        push_six_bits(q, out);
+        5
+    } else if fits[2] {
+        push_eight_bits(q, out);
+        4
+    } else if fits[3] {
+        push_ten_bits(q, out);
+        3
+    } else if fits[4] {
+        push_sixteen_bits(q, out);
+        2
+    } else {
+        unsafe {
+            push_32_or_64_bits(q, out);
+        }
+        1
+    }
+}
+
+///
+/// Emits the pending run of identical deltas.
+///
+#[inline(always)]
+fn push_repeated_deltas<const N: usize, S: HalfBytes>(
+    q: &mut CompressionQueue<N>,
+    buf: &mut HalfVec<S>,
+) {
+    if let Some((count, zigzag)) = q.take_run() {
+        buf.push(HalfWord::Half(headers::REPEATED_DELTAS));
+        write_nibble_varint(buf, count as u64);
+        write_nibble_varint(buf, zigzag);
+    }
+}
+
+///
+/// The number of nibbles `push_repeated_deltas` emits for a run.
+///
+#[inline(always)]
+fn repeated_deltas_len(count: usize, zigzag: u64) -> usize {
+    1 + nibble_varint_len(count as u64) + nibble_varint_len(zigzag)
+}
+
+///
+/// Emits a word holding the next `samples` values of the queue, returning `samples`.
+///
+#[inline(always)]
+fn push_delta_samples<const N: usize, S: HalfBytes>(
+    q: &mut CompressionQueue<N>,
+    out: &mut HalfVec<S>,
+    samples: usize,
+) -> usize {
+    match samples {
+        0 => 0,
+        10 => {
+            push_three_bits(q, out);
             10
-        } else if fits[1] {
-            push_six_bits(self, out);
+        }
+        5 => {
+            push_six_bits(q, out);
             5
-        } else if fits[2] {
-            push_eight_bits(self, out);
+        }
+        4 => {
+            push_eight_bits(q, out);
             4
-        } else if fits[3] {
-            push_ten_bits(self, out);
+        }
+        3 => {
+            push_ten_bits(q, out);
             3
-        } else if fits[4] {
-            push_sixteen_bits(self, out);
+        }
+        2 => {
+            push_sixteen_bits(q, out);
             2
-        } else {
+        }
+        _ => {
             unsafe {
-                push_32_or_64_bits(self, out);
+                push_32_or_64_bits(q, out);
             }
             1
         }
     }
+}
 
+///
+/// The number of samples the next word of a partly emptied queue holds.
+///
+/// A queue longer than the 10 samples of the largest word looks ahead for the fewest nibbles,
+/// otherwise the largest word that fits is taken.
+///
+#[inline(always)]
+fn next_delta_samples<const N: usize>(q: &CompressionQueue<N>) -> usize {
+    if N > 10 {
+        lookahead_delta_samples(q)
+    } else {
+        flush_delta_samples(q)
+    }
+}
+
+impl<const N: usize> EmitDeltaBits for CompressionQueue<N> {
     #[inline(always)]
-    fn flush_delta_bits(&mut self, out: &mut HalfVec) -> usize {
-        let mut fits = [true; 5];
+    fn emit_delta_bits<S: HalfBytes>(&mut self, out: &mut HalfVec<S>) -> usize {
+        // Continue a pending run until a different value arrives
+        let popped = self.extend_run();
+        if self.run_len() > 0 {
+            if self.is_empty() {
+                return popped;
+            }
+            push_repeated_deltas(self, out);
 
-        // Can not emit with any case of delta compression if queue is empty
-        if self.is_empty() {
-            return 0;
+            // Wait for the queue to fill again
+            if !self.is_full() {
+                return popped;
+            }
         }
 
-        // Can not emit with case v of delta compression if number of samples < 10
-        if self.len() < 10 {
-            fits[0] = false;
+        // Start a run from a queue full of identical values
+        let started = self.start_run();
+        if started > 0 {
+            return popped + started;
         }
 
-        // Can not emit with case iv of delta compression if number of samples < 5.
-        if self.len() < 5 {
-            fits[1] = false;
+        if N > 10 {
+            popped + push_delta_samples(self, out, lookahead_delta_samples(self))
+        } else {
+            popped + emit_delta_word(self, out)
         }
+    }
 
-        // Can not emit with case iii of delta compression if number of samples < 4
-        if self.len() < 4 {
-            fits[2] = false;
+    #[inline(always)]
+    fn flush_delta_bits<S: HalfBytes>(&mut self, out: &mut HalfVec<S>) -> usize {
+        // The pending run precedes the values in the queue
+        if self.run_len() > 0 {
+            let popped = self.extend_run();
+            push_repeated_deltas(self, out);
+            return popped;
         }
 
-        // Can not emit with case ii of delta compression if number of samples < 3
-        if self.len() < 3 {
-            fits[3] = false;
+        push_delta_samples(self, out, next_delta_samples(self))
+    }
+
+    fn flushed_delta_len(&self) -> usize {
+        let mut queue = *self;
+        let mut nibbles = 0;
+        queue.extend_run();
+        if let Some((count, zigzag)) = queue.take_run() {
+            nibbles += repeated_deltas_len(count, zigzag);
         }
+        loop {
+            match next_delta_samples(&queue) {
+                0 => return nibbles,
+                1 => {
+                    // A 32-bit or 64-bit word
+                    let value = unsafe { queue.pop().unwrap_unchecked() };
+                    nibbles += if value <= u32::MAX as u64 { 9 } else { 17 };
+                }
+                samples => {
+                    // A header and a 32-bit word
+                    for _ in 0..samples {
+                        queue.pop();
+                    }
+                    nibbles += 9;
+                }
+            }
+        }
+    }
+}
+
+///
+/// The number of samples the next flushed word would hold, or 0 if the queue is empty.
+///
+#[inline(always)]
+fn flush_delta_samples<const N: usize>(q: &CompressionQueue<N>) -> usize {
+    let mut fits = [true; 5];
+
+    // Can not emit with any case of delta compression if queue is empty
+    if q.is_empty() {
+        return 0;
+    }
 
-        // Can not emit with case ii of delta compression if number of samples < 2
-        if self.len() < 2 {
+    // Can not emit with case v of delta compression if number of samples < 10
+    if q.len() < 10 {
+        fits[0] = false;
+    }
+
+    // Can not emit with case iv of delta compression if number of samples < 5.
+    if q.len() < 5 {
+        fits[1] = false;
+    }
+
+    // Can not emit with case iii of delta compression if number of samples < 4
+    if q.len() < 4 {
+        fits[2] = false;
+    }
+
+    // Can not emit with case ii of delta compression if number of samples < 3
+    if q.len() < 3 {
+        fits[3] = false;
+    }
+
+    // Can not emit with case ii of delta compression if number of samples < 2
+    if q.len() < 2 {
+        fits[4] = false;
+    }
+
+    // Check if the values will fit in the cases
+    let values = q.peak_bitcounts::<10>();
+    for (index, bits_required) in values.into_iter().enumerate() {
+        if (index < 2) & (bits_required > 16) {
             fits[4] = false;
         }
-
-        // Check if the values will fit in the cases
-        let values = self.peak_bitcounts::<10>();
-        for (index, bits_required) in values.into_iter().enumerate() {
-            if (index < 2) & (bits_required > 16) {
-                fits[4] = false;
-            }
-            if (index < 3) & (bits_required > 10) {
-                fits[3] = false;
-            }
-            if (index < 4) & (bits_required > 8) {
-                fits[2] = false;
-            }
-            if (index < 5) & (bits_required > 6) {
-                fits[1] = false;
-            }
-            if (index < 10) & (bits_required > 3) {
-                fits[0] = false;
-            }
+        if (index < 3) & (bits_required > 10) {
+            fits[3] = false;
         }
+        if (index < 4) & (bits_required > 8) {
+            fits[2] = false;
+        }
+        if (index < 5) & (bits_required > 6) {
+            fits[1] = false;
+        }
+        if (index < 10) & (bits_required > 3) {
+            fits[0] = false;
+        }
+    }
 
-        // Emit according to priority of cases
-        if fits[0] {
-            push_three_bits(self, out);
-            10
-        } else if fits[1] {
-            push_six_bits(self, out);
-            5
-        } else if fits[2] {
-            push_eight_bits(self, out);
-            4
-        } else if fits[3] {
-            push_ten_bits(self, out);
-            3
-        } else if fits[4] {
-            push_sixteen_bits(self, out);
-            2
-        } else {
-            unsafe {
-                push_32_or_64_bits(self, out);
+    if fits[0] {
+        10
+    } else if fits[1] {
+        5
+    } else if fits[2] {
+        4
+    } else if fits[3] {
+        3
+    } else if fits[4] {
+        2
+    } else {
+        1
+    }
+}
+
+///
+/// The samples, the widest zigzag value in bits, and the nibbles of each word of packed deltas.
+///
+const DELTA_WORDS: [(usize, usize, usize); 7] = [
+    (10, 3, 9),
+    (5, 6, 9),
+    (4, 8, 9),
+    (3, 10, 9),
+    (2, 16, 9),
+    (1, 32, 9),
+    (1, 64, 17),
+];
+
+///
+/// The number of samples in the first word of the partition of the whole queue
+/// into words with the fewest nibbles, or 0 if the queue is empty.
+///
+/// Ties go to the larger first word, as the greedy choice would take.
+///
+fn lookahead_delta_samples<const N: usize>(q: &CompressionQueue<N>) -> usize {
+    let len = q.len();
+    let bits = q.peak_bitcounts::<16>();
+
+    // The fewest nibbles for the values from each index to the end, and the samples of the word at the index
+    let mut nibbles = [0usize; 17];
+    let mut samples = [0usize; 17];
+    for start in (0..len).rev() {
+        nibbles[start] = usize::MAX;
+        for (word_samples, word_bits, word_nibbles) in DELTA_WORDS {
+            let end = start + word_samples;
+            if end > len || bits[start..end].iter().any(|bits| *bits > word_bits) {
+                continue;
+            }
+            if word_nibbles + nibbles[end] < nibbles[start] {
+                nibbles[start] = word_nibbles + nibbles[end];
+                samples[start] = word_samples;
             }
-            1
         }
     }
+    samples[0]
 }
 
 // Delta-Delta Encoding
@@ -300,59 +501,99 @@ impl EmitDeltaBits for CompressionQueue<10> {
 pub trait EmitDeltaDeltaBits {
     /// Emits bits according to the most efficient case of Delta-Delta Compression.
     /// Returns the number of elements popped from the queue.
-    fn emit_delta_delta_bits(&mut self, out: &mut HalfVec) -> usize;
+    fn emit_delta_delta_bits<S: HalfBytes>(&mut self, out: &mut HalfVec<S>) -> usize;
+    /// The number of nibbles that emitting the whole queue would emit.
+    fn flushed_delta_delta_len(&self) -> usize;
 }
 
 ///
-/// A helper function that emits bits according to the most efficient case of Delta-Delta Compression.
-fn emit_popped_values<const N: usize>(
-    bitcounts: &[usize; N],
-    values: &[usize; N],
-    out: &mut HalfVec,
-) {
-    for (bits, value) in bitcounts.iter().zip(values.iter()) {
-        match bits {
-            0 => out.push(HalfWord::Half(0b0000)),
-            1..=5 => {
-                let zigzag = (value & 0b1_1111) as u8;
-                out.push(HalfWord::Byte(0b0010_0000 | zigzag));
-            }
-            6..=9 => {
-                let zigzag = (value & 0b1_1111_1111) as u16;
-                out.push(HalfWord::Half(0b0100 | (zigzag >> 8) as u8));
-                out.push(HalfWord::Byte(zigzag as u8));
-            }
-            10..=16 => {
-                let zigzag = (value & 0b1111_1111_1111_1111) as u16;
-                out.push(HalfWord::Half(0b0110));
-                out.push(HalfWord::Byte((zigzag >> 8) as u8));
-                out.push(HalfWord::Byte(zigzag as u8));
-            }
-            _ => {
-                out.push(HalfWord::Half(0b0111));
-                out.push(HalfWord::Full(*value as u32));
-            }
+/// Push a single zigzag encoded delta-delta with the smallest prefix that fits `bits`.
+///
+#[inline(always)]
+fn push_delta_delta_bits<S: HalfBytes>(bits: usize, zigzag: u64, out: &mut HalfVec<S>) {
+    match bits {
+        0 => out.push(HalfWord::Half(headers::DELTA_DELTA_ZERO)),
+        1..=5 => {
+            let zigzag = (zigzag & 0b1_1111) as u8;
+            out.push(HalfWord::Byte(
+                (headers::DELTA_DELTA_FIVE_BITS << 4) | zigzag,
+            ));
+        }
+        6..=9 => {
+            let zigzag = (zigzag & 0b1_1111_1111) as u16;
+            out.push(HalfWord::Half(
+                headers::DELTA_DELTA_NINE_BITS | (zigzag >> 8) as u8,
+            ));
+            out.push(HalfWord::Byte(zigzag as u8));
+        }
+        10..=16 => {
+            let zigzag = (zigzag & 0b1111_1111_1111_1111) as u16;
+            out.push(HalfWord::Half(headers::DELTA_DELTA_SIXTEEN_BITS));
+            out.push(HalfWord::Byte((zigzag >> 8) as u8));
+            out.push(HalfWord::Byte(zigzag as u8));
+        }
+        17..=32 => {
+            out.push(HalfWord::Half(headers::DELTA_DELTA_THIRTY_TWO_BITS));
+            out.push(HalfWord::Full(zigzag as u32));
+        }
+        _ => {
+            out.push(HalfWord::Half(headers::DELTA_DELTA_SIXTY_FOUR_BITS));
+            out.push(HalfWord::Full((zigzag >> 32) as u32));
+            out.push(HalfWord::Full(zigzag as u32));
         }
     }
 }
 
+///
+/// Write a 128-bit delta-delta directly to the buffer.
+///
+/// The compression queue holds zigzag values of up to 64 bits,
+/// so i128 columns skip the queue and emit each delta-delta as it arrives.
+/// Values that do not fit in 64 bits are escaped with a 128-bit prefix.
+///
+pub fn write_i128_delta_delta_bits<S: HalfBytes>(buf: &mut HalfVec<S>, delta_delta: i128) {
+    let zigzag = ((delta_delta << 1) ^ (delta_delta >> 127)) as u128;
+    let bits = (128 - zigzag.leading_zeros()) as usize;
+    if bits <= 64 {
+        push_delta_delta_bits(bits, zigzag as u64, buf);
+        return;
+    }
+
+    buf.push(HalfWord::Half(
+        headers::DELTA_DELTA_ONE_HUNDRED_TWENTY_EIGHT_BITS,
+    ));
+    buf.push(HalfWord::Full((zigzag >> 96) as u32));
+    buf.push(HalfWord::Full((zigzag >> 64) as u32));
+    buf.push(HalfWord::Full((zigzag >> 32) as u32));
+    buf.push(HalfWord::Full(zigzag as u32));
+}
+
 impl EmitDeltaDeltaBits for CompressionQueue<2> {
-    fn emit_delta_delta_bits(&mut self, out: &mut HalfVec) -> usize {
-        match self.len() {
-            2 => {
-                let bitcounts = self.peak_bitcounts::<2>();
-                let values = self.pop_n::<2>();
-                emit_popped_values(&bitcounts, &values, out);
-                2
-            }
-            1 => {
-                let bitcounts = self.peak_bitcounts::<1>();
-                let values = self.pop_n::<1>();
-                emit_popped_values(&bitcounts, &values, out);
-                1
-            }
-            _ => 0,
+    fn emit_delta_delta_bits<S: HalfBytes>(&mut self, out: &mut HalfVec<S>) -> usize {
+        // Each delta-delta is emitted with the smallest prefix that fits it
+        let popped = self.len().min(2);
+        let bitcounts = self.peak_bitcounts::<2>();
+        for bits in bitcounts.iter().take(popped) {
+            let zigzag = unsafe { self.pop().unwrap_unchecked() };
+            push_delta_delta_bits(*bits, zigzag, out);
         }
+        popped
+    }
+
+    fn flushed_delta_delta_len(&self) -> usize {
+        let bitcounts = self.peak_bitcounts::<2>();
+        bitcounts
+            .iter()
+            .take(self.len())
+            .map(|bits| match bits {
+                0 => 1,
+                1..=5 => 2,
+                6..=9 => 3,
+                10..=16 => 5,
+                17..=32 => 9,
+                _ => 17,
+            })
+            .sum()
     }
 }
 
@@ -362,7 +603,7 @@ impl EmitDeltaDeltaBits for CompressionQueue<2> {
 /// This function takes a mutable reference to a HalfVec and a 128-bit integer.
 /// It converts the integer to a 128-bit unsigned integer and pushes it to the HalfVec in 32-bit chunks.
 ///
-pub fn write_i128_bits(buf: &mut HalfVec, i: i128) {
+pub fn write_i128_bits<S: HalfBytes>(buf: &mut HalfVec<S>, i: i128) {
     let i = i as u128;
     buf.push(HalfWord::Full((i >> 96) as u32));
     buf.push(HalfWord::Full((i >> 64) as u32));
@@ -376,7 +617,7 @@ pub fn write_i128_bits(buf: &mut HalfVec, i: i128) {
 /// This function takes a mutable reference to a HalfVec and a 64-bit integer.
 /// It converts the integer to a 64-bit unsigned integer and pushes it to the HalfVec in 32-bit chunks.
 ///
-pub fn write_i64_bits(buf: &mut HalfVec, i: i64) {
+pub fn write_i64_bits<S: HalfBytes>(buf: &mut HalfVec<S>, i: i64) {
     let i = i as u64;
     buf.push(HalfWord::Full((i >> 32) as u32));
     buf.push(HalfWord::Full(i as u32));
@@ -388,7 +629,7 @@ pub fn write_i64_bits(buf: &mut HalfVec, i: i64) {
 /// This function takes a mutable reference to a HalfVec and a 32-bit integer.
 /// It pushes the integer to the HalfVec as a 32-bit unsigned integer.
 ///
-pub fn write_i32_bits(buf: &mut HalfVec, i: i32) {
+pub fn write_i32_bits<S: HalfBytes>(buf: &mut HalfVec<S>, i: i32) {
     buf.push(HalfWord::Full(i as u32));
 }
 
@@ -398,7 +639,7 @@ pub fn write_i32_bits(buf: &mut HalfVec, i: i32) {
 /// This function takes a mutable reference to a HalfVec and a 16-bit integer.
 /// It converts the integer to a 16-bit unsigned integer and pushes it to the HalfVec in 8-bit chunks.
 ///
-pub fn write_i16_bits(buf: &mut HalfVec, i: i16) {
+pub fn write_i16_bits<S: HalfBytes>(buf: &mut HalfVec<S>, i: i16) {
     let i = i as u16;
     buf.push(HalfWord::Byte((i >> 8) as u8));
     buf.push(HalfWord::Byte(i as u8));
@@ -410,6 +651,172 @@ pub fn write_i16_bits(buf: &mut HalfVec, i: i16) {
 /// This function takes a mutable reference to a HalfVec and an 8-bit integer.
 /// It pushes the integer to the HalfVec as an 8-bit unsigned integer.
 ///
-pub fn write_i8_bits(buf: &mut HalfVec, i: i8) {
+pub fn write_i8_bits<S: HalfBytes>(buf: &mut HalfVec<S>, i: i8) {
     buf.push(HalfWord::Byte(i as u8));
 }
+
+///
+/// Writes an unsigned 64-bit integer to a HalfVec.
+///
+/// The bits are written exactly as `write_i64_bits` would write the same bits reinterpreted as signed.
+///
+pub fn write_u64_bits<S: HalfBytes>(buf: &mut HalfVec<S>, u: u64) {
+    write_i64_bits(buf, u as i64);
+}
+
+///
+/// Writes an unsigned 32-bit integer to a HalfVec.
+///
+/// The bits are written exactly as `write_i32_bits` would write the same bits reinterpreted as signed.
+///
+pub fn write_u32_bits<S: HalfBytes>(buf: &mut HalfVec<S>, u: u32) {
+    write_i32_bits(buf, u as i32);
+}
+
+///
+/// Writes an unsigned 16-bit integer to a HalfVec.
+///
+/// The bits are written exactly as `write_i16_bits` would write the same bits reinterpreted as signed.
+///
+pub fn write_u16_bits<S: HalfBytes>(buf: &mut HalfVec<S>, u: u16) {
+    write_i16_bits(buf, u as i16);
+}
+
+///
+/// Writes an unsigned 8-bit integer to a HalfVec.
+///
+/// The bits are written exactly as `write_i8_bits` would write the same bits reinterpreted as signed.
+///
+pub fn write_u8_bits<S: HalfBytes>(buf: &mut HalfVec<S>, u: u8) {
+    write_i8_bits(buf, u as i8);
+}
+
+///
+/// Writes the low `bits` of a value to a HalfVec, in as few nibbles as hold them.
+///
+/// The last nibble is filled with the bits above, so a sign extended value reads back sign extended.
+///
+pub fn write_bits<S: HalfBytes>(buf: &mut HalfVec<S>, value: u64, bits: u32) {
+    for i in (0..bits.div_ceil(4)).rev() {
+        buf.push(HalfWord::Half(((value >> (4 * i)) & 0xF) as u8));
+    }
+}
+
+///
+/// Writes the XOR of a float with the previous float of a column of `nibbles` nibble-wide floats.
+///
+/// Only the meaningful nibbles between the leading and trailing zero nibbles of the XOR are written,
+/// in the window of the last XOR if they fit and that is no longer than opening a new window.
+/// The window is packed as `leading << 8 | meaningful`, and is 0 before the first XOR.
+///
+/// Returns the window of the next XOR.
+///
+pub fn write_xor_bits<S: HalfBytes>(
+    buf: &mut HalfVec<S>,
+    xor: u64,
+    window: u16,
+    nibbles: u32,
+) -> u16 {
+    if xor == 0 {
+        buf.push(HalfWord::Half(headers::XOR_REPEATED));
+        return window;
+    }
+
+    let leading = (xor.leading_zeros() - (64 - 4 * nibbles)) / 4;
+    let trailing = xor.trailing_zeros() / 4;
+    let meaningful = nibbles - leading - trailing;
+
+    // The window costs 1 + window_meaningful nibbles, a new window 3 + meaningful
+    let window_leading = (window >> 8) as u32;
+    let window_meaningful = (window & 0xFF) as u32;
+    if window_meaningful > 0
+        && leading >= window_leading
+        && leading + meaningful <= window_leading + window_meaningful
+        && window_meaningful <= meaningful + 2
+    {
+        buf.push(HalfWord::Half(headers::XOR_REUSE_WINDOW));
+        let shift = 4 * (nibbles - window_leading - window_meaningful);
+        write_bits(buf, xor >> shift, 4 * window_meaningful);
+        return window;
+    }
+
+    buf.push(HalfWord::Half(headers::XOR_NEW_WINDOW));
+    buf.push(HalfWord::Half(leading as u8));
+    buf.push(HalfWord::Half((meaningful - 1) as u8));
+    write_bits(buf, xor >> (4 * trailing), 4 * meaningful);
+    ((leading << 8) | meaningful) as u16
+}
+
+///
+/// Writes the optional stream header to the start of a byte slice, returning the number of bytes written.
+///
+/// `columns` holds one schema byte per column, see `consts::schema`.
+/// The header is always byte aligned at the start of a packet, so it is written as bytes.
+///
+pub fn write_header(out: &mut [u8], columns: &[u8]) -> usize {
+    debug_assert!(columns.len() <= u8::MAX as usize);
+    out[0] = (schema::MAGIC << 4) | schema::VERSION;
+    out[1] = columns.len() as u8;
+    out[2..2 + columns.len()].copy_from_slice(columns);
+    2 + columns.len()
+}
+
+///
+/// The number of nibbles `write_nibble_varint` writes for a value.
+///
+pub fn nibble_varint_len(value: u64) -> usize {
+    let bits = (u64::BITS - value.leading_zeros()) as usize;
+    bits.div_ceil(3).max(1)
+}
+
+///
+/// The number of nibbles `write_nibble_varint_u128` writes for a value.
+///
+pub fn nibble_varint_len_u128(value: u128) -> usize {
+    let bits = (u128::BITS - value.leading_zeros()) as usize;
+    bits.div_ceil(3).max(1)
+}
+
+///
+/// Writes an unsigned integer to a HalfVec as a variable number of nibbles.
+///
+/// Each nibble holds 3 bits of the value, most significant first.
+/// The top bit of the nibble is set if more nibbles follow.
+///
+pub fn write_nibble_varint<S: HalfBytes>(buf: &mut HalfVec<S>, value: u64) {
+    for i in (1..nibble_varint_len(value)).rev() {
+        buf.push(HalfWord::Half(0b1000 | ((value >> (3 * i)) & 0b111) as u8));
+    }
+    buf.push(HalfWord::Half((value & 0b111) as u8));
+}
+
+///
+/// Writes an unsigned integer of up to 128 bits to a HalfVec, in the same form as `write_nibble_varint`.
+///
+pub fn write_nibble_varint_u128<S: HalfBytes>(buf: &mut HalfVec<S>, value: u128) {
+    for i in (1..nibble_varint_len_u128(value)).rev() {
+        buf.push(HalfWord::Half(0b1000 | ((value >> (3 * i)) & 0b111) as u8));
+    }
+    buf.push(HalfWord::Half((value & 0b111) as u8));
+}
+
+///
+/// Writes the tag and the first row or first delta of a column as a zigzag nibble varint,
+/// if that is shorter than the `full_nibbles` of the fixed-width form.
+///
+/// Returns false without writing anything if the fixed-width form is no longer.
+///
+pub fn write_varint_row<S: HalfBytes>(
+    buf: &mut HalfVec<S>,
+    tag: u8,
+    value: i128,
+    full_nibbles: usize,
+) -> bool {
+    let zigzag = ((value << 1) ^ (value >> 127)) as u128;
+    if nibble_varint_len_u128(zigzag) >= full_nibbles {
+        return false;
+    }
+    buf.push(HalfWord::Half(tag));
+    write_nibble_varint_u128(buf, zigzag);
+    true
+}
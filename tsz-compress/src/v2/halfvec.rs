@@ -1,28 +1,201 @@
 #![allow(dead_code)]
 
-use core::mem::MaybeUninit;
-
 use crate::prelude::*;
 use crate::v2::consts::headers;
+use crate::v2::snapshot::{SnapshotReader, SnapshotWriter};
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 pub use queue::*;
 
 ///
-/// A dynamic vector of nibbles.
+/// The bytes behind a HalfVec, each holding two nibbles.
+///
+/// A `Vec` grows as bytes are pushed, while a `HalfArray` has a fixed capacity
+/// and refuses bytes once it is full.
+///
+pub trait HalfBytes: core::fmt::Debug {
+    ///
+    /// Creates empty storage, reserving room for `capacity` bytes if the storage can grow.
+    ///
+    fn with_capacity(capacity: usize) -> Self;
+
+    ///
+    /// The most bytes the storage can ever hold.
+    ///
+    fn max_len(&self) -> usize;
+
+    ///
+    /// Appends a byte. The caller checks that there is room first.
+    ///
+    fn push(&mut self, byte: u8);
+
+    ///
+    /// Shortens the storage to `len` bytes.
+    ///
+    fn truncate(&mut self, len: usize);
+
+    ///
+    /// The bytes in the order they were pushed.
+    ///
+    fn as_slice(&self) -> &[u8];
+
+    ///
+    /// The bytes in the order they were pushed, to fill the lower nibble of the last byte.
+    ///
+    fn as_mut_slice(&mut self) -> &mut [u8];
+}
+
+#[cfg(feature = "alloc")]
+impl HalfBytes for Vec<u8> {
+    fn with_capacity(capacity: usize) -> Self {
+        Vec::with_capacity(capacity)
+    }
+
+    fn max_len(&self) -> usize {
+        usize::MAX
+    }
+
+    #[inline(always)]
+    fn push(&mut self, byte: u8) {
+        Vec::push(self, byte);
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        self
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+///
+/// The growable storage of a HalfVec by default.
+///
+#[cfg(feature = "alloc")]
+pub type HalfByteVec = Vec<u8>;
+
+///
+/// A fixed-capacity array of bytes that never allocates.
+///
+#[derive(Debug)]
+pub struct HalfArray<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> HalfArray<N> {
+    ///
+    /// Creates an empty array with room for `N` bytes.
+    ///
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for HalfArray<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> HalfBytes for HalfArray<N> {
+    fn with_capacity(_capacity: usize) -> Self {
+        Self::new()
+    }
+
+    fn max_len(&self) -> usize {
+        N
+    }
+
+    #[inline(always)]
+    fn push(&mut self, byte: u8) {
+        self.bytes[self.len] = byte;
+        self.len += 1;
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes[..self.len]
+    }
+}
+
+///
+/// Storage that counts the bytes pushed without keeping them,
+/// to measure the words of a row before writing them.
+///
+#[derive(Debug, Default)]
+pub struct HalfCount {
+    len: usize,
+    max_len: usize,
+}
+
+impl HalfBytes for HalfCount {
+    fn with_capacity(_capacity: usize) -> Self {
+        Self {
+            len: 0,
+            max_len: usize::MAX,
+        }
+    }
+
+    fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    #[inline(always)]
+    fn push(&mut self, _byte: u8) {
+        self.len += 1;
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        &[]
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut []
+    }
+}
+
+///
+/// A vector of nibbles, packed two to a byte as they are pushed.
 ///
 /// Pushing is fast. There is no pop.
-/// All bits are pushed together during finish.
+/// All bytes are concatenated together during finish.
+///
+/// The storage defaults to a growable `Vec` when allocation is available.
 ///
 #[derive(Debug)]
-pub struct HalfVec {
-    words: Vec<HalfWord>,
+pub struct HalfVec<
+    #[cfg(feature = "alloc")] S: HalfBytes = Vec<u8>,
+    #[cfg(not(feature = "alloc"))] S: HalfBytes,
+> {
+    bytes: S,
     len: usize,
+    overflowed: bool,
 }
 
 ///
 /// Bits collected into a single word as one byte or two bytes.
 ///
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum HalfWord {
     /// The bottom bits of the word are used.
     /// 0b0000_1111
@@ -47,14 +220,26 @@ impl HalfWord {
     }
 }
 
+#[cfg(feature = "alloc")]
 impl HalfVec {
     ///
-    /// Creates an empty vector.
+    /// Creates an empty vector with room for `capacity` nibbles.
     ///
     pub fn new(capacity: usize) -> Self {
+        Self::with_storage(Vec::with_capacity(capacity.div_ceil(2)))
+    }
+}
+
+impl<S: HalfBytes> HalfVec<S> {
+    ///
+    /// Creates an empty vector that packs nibbles into `bytes`.
+    ///
+    pub fn with_storage(mut bytes: S) -> Self {
+        bytes.truncate(0);
         Self {
-            words: Vec::with_capacity(capacity),
+            bytes,
             len: 0,
+            overflowed: false,
         }
     }
 
@@ -73,225 +258,250 @@ impl HalfVec {
         self.len == 0
     }
 
+    ///
+    /// The number of nibbles that can still be pushed before the storage is full.
+    ///
+    pub fn remaining_len(&self) -> usize {
+        self.bytes.max_len().saturating_mul(2) - self.len
+    }
+
+    ///
+    /// Returns true if a word was refused because the storage was full.
+    ///
+    pub const fn is_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    ///
+    /// The packed nibbles, with the lower nibble of the last byte zeroed if the length is odd.
+    ///
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_slice()
+    }
+
+    ///
+    /// A vector of the same length and capacity that counts the nibbles pushed after them, without keeping any.
+    ///
+    pub fn to_counting(&self) -> HalfVec<HalfCount> {
+        HalfVec {
+            bytes: HalfCount {
+                len: self.len.div_ceil(2),
+                max_len: self.bytes.max_len(),
+            },
+            len: self.len,
+            overflowed: self.overflowed,
+        }
+    }
+
     ///
     /// Clears the queue, removing all values.
     /// The queue will be empty after this call completes, but it may not be zero-capacity.
     ///
     pub fn clear(&mut self) {
         self.len = 0;
-        self.words.clear();
+        self.overflowed = false;
+        self.bytes.truncate(0);
+    }
+
+    ///
+    /// Shortens the vector to `len` nibbles.
+    ///
+    /// Words refused on overflow were pushed after every stored word,
+    /// so truncating also clears the overflow.
+    ///
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len {
+            self.len = len;
+            self.bytes.truncate(len.div_ceil(2));
+            if len % 2 == 1 {
+                if let Some(last) = self.bytes.as_mut_slice().last_mut() {
+                    *last &= 0xF0;
+                }
+            }
+        }
+        self.overflowed = false;
     }
 
     ///
     /// Pushes a value into the queue,
-    /// overwriting the oldest value if the queue is full.
+    /// marking the vector as overflowed if the storage is full.
     ///
     #[inline(always)]
     pub fn push(&mut self, value: HalfWord) {
+        // Words are never split, so a word that does not fit is refused
+        if value.len() > self.remaining_len() {
+            self.overflowed = true;
+            return;
+        }
+
+        if self.len.is_multiple_of(2) {
+            match value {
+                HalfWord::Half(value) => self.bytes.push(value << 4),
+                HalfWord::Byte(value) => self.bytes.push(value),
+                HalfWord::Full(value) => {
+                    for byte in value.to_be_bytes() {
+                        self.bytes.push(byte);
+                    }
+                }
+            }
+        } else {
+            // Fill the lower nibble of the last byte, then push the rest of the word shifted by a nibble
+            let (top, rest) = match value {
+                HalfWord::Half(value) => (value & 0x0F, None),
+                HalfWord::Byte(value) => (value >> 4, Some(((value as u32) << 28, 1))),
+                HalfWord::Full(value) => ((value >> 28) as u8, Some((value << 4, 4))),
+            };
+            if let Some(last) = self.bytes.as_mut_slice().last_mut() {
+                *last |= top;
+            }
+            if let Some((rest, count)) = rest {
+                for byte in &rest.to_be_bytes()[..count] {
+                    self.bytes.push(*byte);
+                }
+            }
+        }
         self.len += value.len();
-        self.words.push(value);
     }
 
     ///
-    /// Flattens the queue into a single vector of bytes.
+    /// Writes the length in nibbles, the packed bytes and whether a word was refused.
     ///
-    pub fn finish<'a, I>(out: &mut Vec<u8>, word_lists: I)
-    where
-        I: Iterator<Item = &'a HalfVec> + Clone,
-    {
-        // We will be writing directly into the buffer since we know the capacity
-        unsafe {
-            // 2 nibbles per byte and len is in nibbles
-            // Reserve enough space for the output
-            let len = word_lists.clone().map(|w| w.len).sum::<usize>() / 2;
-            let reserve_len = len + 1;
-            let avail = out.capacity() - out.len();
-            if avail < reserve_len {
-                out.reserve_exact(reserve_len - avail);
-            }
-            let bytes = out.spare_capacity_mut();
-            let mut idx = 0;
-
-            // Keep track of whether we are on the upper or lower nibble across word lists
-            let mut upper = true;
-            let mut byte = 0u8;
-
-            // Iterate over all the words in all the word lists
-            for word in word_lists.flat_map(|w| w.words.iter()) {
-                if upper {
-                    match word {
-                        HalfWord::Half(value) => {
-                            // Shift the value into the upper nibble
-                            byte = value << 4;
-                            // We are now on the lower nibble
-                            upper = false;
-                        }
-                        HalfWord::Byte(value) => {
-                            // Use both nibbles from the byte
-                            known_append(bytes, &mut idx, *value);
-                        }
-                        HalfWord::Full(value) => {
-                            // Use both nibbles from the top of the full
-                            known_append(bytes, &mut idx, (value >> 24) as u8);
-                            // Use both nibbles from the top middle of the full
-                            known_append(bytes, &mut idx, (value >> 16) as u8);
-                            // Use both nibbles from the bottom middle of the full
-                            known_append(bytes, &mut idx, (value >> 8) as u8);
-                            // Use both nibbles from the bottom of the full
-                            known_append(bytes, &mut idx, *value as u8);
-                        }
-                    }
-                } else {
-                    match word {
-                        HalfWord::Half(value) => {
-                            // Fill the lower nibble, the upper nibble is already filled
-                            byte |= value & 0x0F;
-                            known_append(bytes, &mut idx, byte);
-                            // We are now on the upper nibble
-                            upper = true;
-                        }
-                        HalfWord::Byte(value) => {
-                            // Fill the lower nibble with the upper nibble of the value
-                            byte |= value >> 4;
-                            known_append(bytes, &mut idx, byte);
-                            // Use the lower nibble from the value as the upper nibble
-                            byte = value << 4;
-                            // We are still on the lower nibble
-                        }
-                        HalfWord::Full(value) => {
-                            // Fill the lower nibble with the upper nibble of the value
-                            byte |= (value >> 28) as u8;
-                            known_append(bytes, &mut idx, byte);
-                            // Bits 28-20
-                            byte = (value >> 20) as u8;
-                            known_append(bytes, &mut idx, byte);
-                            // Bits 20-12
-                            byte = (value >> 12) as u8;
-                            known_append(bytes, &mut idx, byte);
-                            // Bits 12-4
-                            byte = (value >> 4) as u8;
-                            known_append(bytes, &mut idx, byte);
-
-                            // Use the lower nibble from the full as the upper nibble
-                            byte = (value << 4) as u8;
-                            // We are still on the lower nibble
-                        }
-                    }
-                }
-            }
+    pub fn write_snapshot(&self, writer: &mut SnapshotWriter<'_>) {
+        writer.write_usize(self.len);
+        writer.write_bytes(self.bytes.as_slice());
+        writer.write_bool(self.overflowed);
+    }
 
-            if !upper {
-                // We are on the lower nibble, so fill the upper nibble with headers::START_OF_COLUMN
-                byte |= headers::START_OF_COLUMN;
-                known_append(bytes, &mut idx, byte);
-            }
+    ///
+    /// Replaces the nibbles with those written by `write_snapshot`,
+    /// returning `CodingError::BufferFull` if the storage cannot hold them.
+    ///
+    pub fn read_snapshot(&mut self, reader: &mut SnapshotReader<'_>) -> Result<(), CodingError> {
+        let len = reader.read_usize()?;
+        let bytes = reader.read_bytes(len.div_ceil(2))?;
+        self.clear();
+        if len > self.remaining_len() {
+            return Err(CodingError::BufferFull);
+        }
+        for byte in bytes {
+            self.bytes.push(*byte);
+        }
+        self.len = len;
+        self.overflowed = reader.read_bool()?;
 
-            out.set_len(out.len() + idx);
+        // The lower nibble after an odd length is always zero
+        if len % 2 == 1 {
+            if let Some(last) = self.bytes.as_mut_slice().last_mut() {
+                *last &= 0xF0;
+            }
         }
+        Ok(())
     }
 
     ///
-    /// Flattens the queue into a single vector of bytes.
+    /// Concatenates the queues into a single vector of bytes.
+    ///
+    #[cfg(feature = "alloc")]
+    pub fn finish<'a, I>(out: &mut Vec<u8>, word_lists: I)
+    where
+        S: 'a,
+        I: Iterator<Item = &'a HalfVec<S>> + Clone,
+    {
+        // 2 nibbles per byte and len is in nibbles
+        // Reserve enough space for the output
+        let len = word_lists.clone().map(|w| w.len).sum::<usize>().div_ceil(2);
+        let avail = out.capacity() - out.len();
+        if avail < len {
+            out.reserve_exact(len);
+        }
+
+        concat_nibbles(word_lists, |bytes| out.extend_from_slice(bytes));
+    }
+
     ///
-    /// TODO: Change the ThinVec interface with a push unchecked that skips
-    /// the wasted capacity checks done by the ThinVec repo
+    /// Concatenates the queues into a single vector of bytes.
     ///
     #[cfg(feature = "thin-vec")]
     pub fn finish_thin<'a, I>(out: &mut ::thin_vec::ThinVec<u8>, word_lists: I)
     where
-        I: Iterator<Item = &'a HalfVec> + Clone,
+        S: 'a,
+        I: Iterator<Item = &'a HalfVec<S>> + Clone,
     {
         // 2 nibbles per byte and len is in nibbles
         // Reserve enough space for the output
-        let len = word_lists.clone().map(|w| w.len).sum::<usize>();
-        let len = if len % 2 == 0 { len / 2 } else { len / 2 + 1 };
+        let len = word_lists.clone().map(|w| w.len).sum::<usize>().div_ceil(2);
         let avail = out.capacity() - out.len();
         if avail < len {
-            out.reserve_exact(len - avail);
+            out.reserve_exact(len);
         }
 
-        // Keep track of whether we are on the upper or lower nibble across word lists
-        let mut upper = true;
-        let mut byte = 0u8;
+        concat_nibbles(word_lists, |bytes| out.extend_from_slice(bytes));
+    }
 
-        // Iterate over all the words in all the word lists
-        for word in word_lists.flat_map(|w| w.words.iter()) {
-            if upper {
-                match word {
-                    HalfWord::Half(value) => {
-                        // Shift the value into the upper nibble
-                        byte = value << 4;
-                        // We are now on the lower nibble
-                        upper = false;
-                    }
-                    HalfWord::Byte(value) => {
-                        // Use both nibbles from the byte
-                        out.push(*value);
-                    }
-                    HalfWord::Full(value) => {
-                        // Use both nibbles from the top of the full
-                        out.push((value >> 24) as u8);
-                        // Use both nibbles from the top middle of the full
-                        out.push((value >> 16) as u8);
-                        // Use both nibbles from the bottom middle of the full
-                        out.push((value >> 8) as u8);
-                        // Use both nibbles from the bottom of the full
-                        out.push(*value as u8);
-                    }
+    ///
+    /// Concatenates the queues into the start of a byte slice, returning the number of bytes written.
+    ///
+    /// Panics if the slice is shorter than the packed nibbles.
+    ///
+    pub fn finish_into_slice<'a, I>(out: &mut [u8], word_lists: I) -> usize
+    where
+        S: 'a,
+        I: Iterator<Item = &'a HalfVec<S>>,
+    {
+        let mut idx = 0;
+        concat_nibbles(word_lists, |bytes| {
+            out[idx..idx + bytes.len()].copy_from_slice(bytes);
+            idx += bytes.len();
+        });
+        idx
+    }
+}
+
+///
+/// Concatenates the nibbles of all of the word lists, passing runs of bytes to `append`.
+///
+/// Lists are copied as they are while the output is on a byte boundary.
+/// After a list with an odd number of nibbles, the following lists are shifted by a nibble.
+///
+#[inline(always)]
+fn concat_nibbles<'a, S, I>(word_lists: I, mut append: impl FnMut(&[u8]))
+where
+    S: HalfBytes + 'a,
+    I: Iterator<Item = &'a HalfVec<S>>,
+{
+    // The upper nibble waiting for a lower nibble, if the output is between bytes
+    let mut carry: Option<u8> = None;
+
+    for list in word_lists {
+        let bytes = list.bytes.as_slice();
+        let full = list.len / 2;
+        let odd = list.len % 2 == 1;
+        match carry {
+            None => {
+                append(&bytes[..full]);
+                if odd {
+                    carry = Some(bytes[full]);
                 }
-            } else {
-                match word {
-                    HalfWord::Half(value) => {
-                        // Fill the lower nibble, the upper nibble is already filled
-                        byte |= value & 0x0F;
-                        out.push(byte);
-                        // We are now on the upper nibble
-                        upper = true;
-                    }
-                    HalfWord::Byte(value) => {
-                        // Fill the lower nibble with the upper nibble of the value
-                        byte |= value >> 4;
-                        out.push(byte);
-                        // Use the lower nibble from the value as the upper nibble
-                        byte = value << 4;
-                        // We are still on the lower nibble
-                    }
-                    HalfWord::Full(value) => {
-                        // Fill the lower nibble with the upper nibble of the value
-                        byte |= (value >> 28) as u8;
-                        out.push(byte);
-                        // Bits 28-20
-                        byte = (value >> 20) as u8;
-                        out.push(byte);
-                        // Bits 20-12
-                        byte = (value >> 12) as u8;
-                        out.push(byte);
-                        // Bits 12-4
-                        byte = (value >> 4) as u8;
-                        out.push(byte);
-
-                        // Use the lower nibble from the full as the upper nibble
-                        byte = (value << 4) as u8;
-                        // We are still on the lower nibble
-                    }
+            }
+            Some(mut byte) => {
+                for value in &bytes[..full] {
+                    append(&[byte | (value >> 4)]);
+                    byte = value << 4;
+                }
+                if odd {
+                    append(&[byte | (bytes[full] >> 4)]);
+                    carry = None;
+                } else {
+                    carry = Some(byte);
                 }
             }
         }
-
-        if !upper {
-            // We are on the lower nibble, so fill the upper nibble with headers::START_OF_COLUMN
-            byte |= headers::START_OF_COLUMN;
-            out.push(byte);
-        }
     }
-}
 
-/// Appends a value to a vector without checking the capacity.
-#[inline(always)]
-unsafe fn known_append(buf: &mut [MaybeUninit<u8>], idx: &mut usize, value: u8) {
-    // SAFETY: We allocate at least one vector in the constructor and never remove it.
-    (*buf.as_mut_ptr().add(*idx)).write(value);
-    *idx += 1;
+    if let Some(byte) = carry {
+        // We are on the lower nibble, so fill the upper nibble with headers::START_OF_COLUMN
+        append(&[byte | headers::START_OF_COLUMN]);
+    }
 }
 
 #[cfg(test)]
@@ -302,25 +512,25 @@ mod tests {
     fn can_init() {
         let queue = HalfVec::new(128);
         assert_eq!(queue.len(), 0);
-        assert_eq!(queue.is_empty(), true);
+        assert!(queue.is_empty());
     }
 
     #[test]
     fn can_push() {
         let mut queue = HalfVec::new(128);
         assert_eq!(queue.len(), 0);
-        assert_eq!(queue.is_empty(), true);
+        assert!(queue.is_empty());
 
         for i in 0..128 {
             queue.push(HalfWord::Half(i as u8));
             assert_eq!(queue.len(), i + 1);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
         }
 
         for i in 0..128 {
             queue.push(HalfWord::Full(i as u32));
             assert_eq!(queue.len(), 128 + (i + 1) * 8);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
         }
 
         queue.push(HalfWord::Half(15));
@@ -331,25 +541,25 @@ mod tests {
         // Now every nibble is pushed together
         let mut bytes = Vec::new();
         HalfVec::finish(&mut bytes, [&queue].into_iter());
-        assert_eq!(bytes.len(), (128 + 8 * 128 + 1 + 1) / 2);
+        assert_eq!(bytes.len(), (128 + 8 * 128 + 1usize).div_ceil(2));
     }
 
     #[test]
     fn can_push_with_header() {
         let mut queue = HalfVec::new(128);
         assert_eq!(queue.len(), 0);
-        assert_eq!(queue.is_empty(), true);
+        assert!(queue.is_empty());
 
         for i in 0..128 {
             queue.push(HalfWord::Half(i as u8));
             assert_eq!(queue.len(), i + 1);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
         }
 
         for i in 0..128 {
             queue.push(HalfWord::Full(i as u32));
             assert_eq!(queue.len(), 128 + (i + 1) * 8);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
         }
 
         queue.push(HalfWord::Half(15));
@@ -358,36 +568,140 @@ mod tests {
         queue.push(HalfWord::Half(0));
 
         // Now every nibble is pushed together
-        let mut bytes = Vec::new();
-        bytes.push(0xDE);
-        bytes.push(0xAD);
-        bytes.push(0xBE);
-        bytes.push(0xEF);
+        let mut bytes = vec![0xDE, 0xAD, 0xBE, 0xEF];
         HalfVec::finish(&mut bytes, [&queue].into_iter());
-        assert_eq!(bytes.len(), 4 + ((128 + 8 * 128 + 1 + 1) / 2));
+        assert_eq!(bytes.len(), 4 + (128 + 8 * 128 + 1usize).div_ceil(2));
         assert_eq!(bytes[0], 0xDE);
         assert_eq!(bytes[1], 0xAD);
         assert_eq!(bytes[2], 0xBE);
         assert_eq!(bytes[3], 0xEF);
     }
 
+    #[test]
+    fn can_push_into_array() {
+        let mut queue = HalfVec::with_storage(HalfArray::<6>::new());
+        queue.push(HalfWord::Half(0xA));
+        queue.push(HalfWord::Byte(0xBC));
+        queue.push(HalfWord::Full(0xDEAD_BEEF));
+        assert_eq!(queue.as_bytes(), &[0xAB, 0xCD, 0xEA, 0xDB, 0xEE, 0xF0]);
+        assert_eq!(queue.remaining_len(), 1);
+        assert!(!queue.is_overflowed());
+
+        // A full array refuses the word without counting it
+        queue.push(HalfWord::Byte(0x12));
+        assert_eq!(queue.len(), 11);
+        assert!(queue.is_overflowed());
+
+        // Truncating clears the lower nibble of the last byte and the overflow
+        queue.truncate(9);
+        assert!(!queue.is_overflowed());
+        assert_eq!(queue.as_bytes(), &[0xAB, 0xCD, 0xEA, 0xDB, 0xE0]);
+        assert_eq!(queue.remaining_len(), 3);
+
+        // An odd number of nibbles is padded with the start of a column
+        let mut bytes = [0u8; 8];
+        let len = HalfVec::finish_into_slice(&mut bytes, [&queue].into_iter());
+        assert_eq!(&bytes[..len], &[0xAB, 0xCD, 0xEA, 0xDB, 0xE9]);
+    }
+
+    #[test]
+    fn can_count_like_array() {
+        let mut queue = HalfVec::with_storage(HalfArray::<6>::new());
+        queue.push(HalfWord::Half(0xA));
+        queue.push(HalfWord::Byte(0xBC));
+
+        // The counting vector starts from the same length and capacity
+        let mut counting = queue.to_counting();
+        assert_eq!(counting.len(), 3);
+        assert_eq!(counting.remaining_len(), queue.remaining_len());
+        assert!(counting.as_bytes().is_empty());
+
+        // And refuses the same words once full
+        for word in [HalfWord::Full(0xDEAD_BEEF), HalfWord::Byte(0x12)] {
+            queue.push(word);
+            counting.push(word);
+            assert_eq!(counting.len(), queue.len());
+            assert_eq!(counting.is_overflowed(), queue.is_overflowed());
+        }
+        assert!(counting.is_overflowed());
+    }
+
+    #[test]
+    fn can_concat_odd_lengths() {
+        let mut first = HalfVec::new(8);
+        first.push(HalfWord::Half(0x1));
+        let mut second = HalfVec::new(8);
+        second.push(HalfWord::Byte(0x23));
+        second.push(HalfWord::Half(0x4));
+        let mut third = HalfVec::new(8);
+        third.push(HalfWord::Full(0x5678_9ABC));
+        third.push(HalfWord::Half(0xD));
+
+        // The second list lines up on a byte again, then the third is shifted by a nibble
+        let mut bytes = Vec::new();
+        HalfVec::finish(&mut bytes, [&first, &second, &third].into_iter());
+        assert_eq!(bytes, [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xD9]);
+    }
+
+    #[test]
+    fn can_push_and_truncate_random() {
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..1000 {
+            // Track every nibble along with the lengths a truncate may return to
+            let mut queue = HalfVec::new(0);
+            let mut nibbles: Vec<u8> = Vec::new();
+            for _ in 0..rng.gen_range(0..64) {
+                let word = match rng.gen_range(0..3) {
+                    0 => HalfWord::Half(rng.gen_range(0..16)),
+                    1 => HalfWord::Byte(rng.gen()),
+                    _ => HalfWord::Full(rng.gen()),
+                };
+                match word {
+                    HalfWord::Half(value) => nibbles.push(value),
+                    HalfWord::Byte(value) => nibbles.extend([value >> 4, value & 0x0F]),
+                    HalfWord::Full(value) => {
+                        nibbles.extend((0..8).rev().map(|i| (value >> (4 * i)) as u8 & 0x0F))
+                    }
+                }
+                queue.push(word);
+                if rng.gen_ratio(1, 8) {
+                    let len = rng.gen_range(0..=nibbles.len());
+                    queue.truncate(len);
+                    nibbles.truncate(len);
+                }
+                assert_eq!(queue.len(), nibbles.len());
+            }
+
+            // Pad with the start of a column and pack the reference nibbles
+            if nibbles.len() % 2 == 1 {
+                nibbles.push(headers::START_OF_COLUMN);
+            }
+            let expected: Vec<u8> = nibbles.chunks(2).map(|n| (n[0] << 4) | n[1]).collect();
+            let mut bytes = Vec::new();
+            HalfVec::finish(&mut bytes, [&queue].into_iter());
+            assert_eq!(bytes, expected);
+        }
+    }
+
     #[cfg(feature = "thin-vec")]
     #[test]
     fn can_push_with_header_thin() {
         let mut queue = HalfVec::new(128);
         assert_eq!(queue.len(), 0);
-        assert_eq!(queue.is_empty(), true);
+        assert!(queue.is_empty());
 
         for i in 0..128 {
             queue.push(HalfWord::Half(i as u8));
             assert_eq!(queue.len(), i + 1);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
         }
 
         for i in 0..128 {
             queue.push(HalfWord::Full(i as u32));
             assert_eq!(queue.len(), 128 + (i + 1) * 8);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
         }
 
         queue.push(HalfWord::Half(15));
@@ -402,7 +716,7 @@ mod tests {
         bytes.push(0xBE);
         bytes.push(0xEF);
         HalfVec::finish_thin(&mut bytes, [&queue].into_iter());
-        assert_eq!(bytes.len(), 4 + ((128 + 8 * 128 + 1 + 1) / 2));
+        assert_eq!(bytes.len(), 4 + (128 + 8 * 128 + 1usize).div_ceil(2));
         assert_eq!(bytes[0], 0xDE);
         assert_eq!(bytes[1], 0xAD);
         assert_eq!(bytes[2], 0xBE);
@@ -423,7 +737,7 @@ mod tests_emit_delta {
     /// Compares two HalfVec for equality.
     impl PartialEq for HalfVec {
         fn eq(&self, other: &Self) -> bool {
-            self.words == other.words && self.len() == other.len()
+            self.as_bytes() == other.as_bytes() && self.len() == other.len()
         }
     }
 
@@ -595,4 +909,126 @@ mod tests_emit_delta {
         // Expected length
         assert_eq!(encoded_halfvec, expected_halfvec);
     }
+
+    #[test]
+    fn test_flushed_len_matches_flush() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..1000 {
+            // Random bit-widths in a partially filled queue
+            let mut delta_queue: CompressionQueue<10> = CompressionQueue::new();
+            let mut delta_delta_queue: CompressionQueue<2> = CompressionQueue::new();
+            for _ in 0..rng.gen_range(0..10) {
+                delta_queue.push(rng.gen::<i64>() >> rng.gen_range(0..64));
+            }
+            for _ in 0..rng.gen_range(0..2) {
+                delta_delta_queue.push(rng.gen::<i64>() >> rng.gen_range(0..64));
+            }
+            let expected_delta_len = delta_queue.flushed_delta_len();
+            let expected_delta_delta_len = delta_delta_queue.flushed_delta_delta_len();
+
+            // Flush the queues for real
+            let mut bits = HalfVec::new(8);
+            while !delta_queue.is_empty() {
+                delta_queue.flush_delta_bits(&mut bits);
+            }
+            assert_eq!(bits.len(), expected_delta_len);
+            let mut bits = HalfVec::new(8);
+            while !delta_delta_queue.is_empty() {
+                delta_delta_queue.emit_delta_delta_bits(&mut bits);
+            }
+            assert_eq!(bits.len(), expected_delta_delta_len);
+
+            // Truncating is the reverse of pushing
+            let len = bits.len();
+            bits.push(HalfWord::Full(0));
+            bits.push(HalfWord::Half(0));
+            bits.truncate(len);
+            assert_eq!(bits.len(), len);
+        }
+    }
+
+    // Helper function, returning the samples of each word and the emitted nibbles
+    fn _emit_delta_words<const N: usize>(values: &[i32]) -> (Vec<usize>, usize) {
+        let mut queue: CompressionQueue<N> = CompressionQueue::new();
+        let mut bits = HalfVec::new(8);
+        let mut words = Vec::new();
+        for value in values {
+            queue.push(*value);
+            if queue.is_full() {
+                words.push(queue.emit_delta_bits(&mut bits));
+            }
+        }
+        let expected_len = bits.len() + queue.flushed_delta_len();
+        while !queue.is_flushed() {
+            words.push(queue.flush_delta_bits(&mut bits));
+        }
+        assert_eq!(bits.len(), expected_len);
+        assert_eq!(words.iter().sum::<usize>(), values.len());
+        (words, bits.len())
+    }
+
+    #[test]
+    fn test_emit_delta_lookahead_outliers() {
+        // 3-bit deltas around two deltas that each need a 16-bit word
+        let mut values = (0..60).map(|i| (i % 7) - 3).collect::<Vec<i32>>();
+        values[8] = 1000;
+        values[19] = -1000;
+
+        // Taking the largest word that fits breaks up the 3-bit deltas between the outliers
+        let (greedy, greedy_nibbles) = _emit_delta_words::<10>(&values);
+        assert_eq!(greedy, [5, 3, 2, 5, 4, 2, 10, 10, 10, 5, 4]);
+
+        // Looking ahead packs them into a single word
+        let (lookahead, lookahead_nibbles) = _emit_delta_words::<16>(&values);
+        assert_eq!(lookahead, [5, 3, 1, 10, 2, 10, 10, 10, 5, 4]);
+        assert_eq!(lookahead_nibbles + 9, greedy_nibbles);
+    }
+
+    #[test]
+    fn test_emit_delta_lookahead_random() {
+        let mut rng = rand::thread_rng();
+        let mut greedy_nibbles = 0;
+        let mut lookahead_nibbles = 0;
+        for _ in 0..1000 {
+            // Mostly narrow deltas with outliers of random widths
+            let values = (0..rng.gen_range(0..100))
+                .map(|_| match rng.gen_ratio(1, 10) {
+                    true => rng.gen::<i32>() >> rng.gen_range(0..32),
+                    false => rng.gen_range(-4..4),
+                })
+                .collect::<Vec<i32>>();
+            greedy_nibbles += _emit_delta_words::<10>(&values).1;
+            lookahead_nibbles += _emit_delta_words::<16>(&values).1;
+        }
+        assert!(lookahead_nibbles < greedy_nibbles);
+    }
+
+    #[test]
+    fn test_emit_delta_repeated_deltas() {
+        let mut queue: CompressionQueue<10> = CompressionQueue::new();
+        let mut bits = HalfVec::new(8);
+
+        // 25 identical deltas then a different one
+        for value in core::iter::repeat_n(-2i32, 25).chain([7]) {
+            queue.push(value);
+            if queue.is_full() {
+                queue.emit_delta_bits(&mut bits);
+            }
+        }
+        let expected_len = queue.flushed_delta_len();
+        while !queue.is_flushed() {
+            queue.flush_delta_bits(&mut bits);
+        }
+        assert_eq!(bits.len(), expected_len);
+
+        // Expecting a run of 25 zigzag 3 values, then a single 32-bit delta
+        let mut expected_halfvec = HalfVec::new(8);
+        expected_halfvec.push(HalfWord::Half(headers::REPEATED_DELTAS));
+        expected_halfvec.push(HalfWord::Half(0b1011));
+        expected_halfvec.push(HalfWord::Half(0b0001));
+        expected_halfvec.push(HalfWord::Half(0b0011));
+        expected_halfvec.push(HalfWord::Half(headers::THIRTY_TWO_BITS_ONE_SAMPLE));
+        expected_halfvec.push(HalfWord::Full(14));
+        assert_eq!(bits, expected_halfvec);
+    }
 }
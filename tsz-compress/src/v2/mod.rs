@@ -1,11 +1,23 @@
 pub mod consts;
+pub mod crc;
+#[cfg(feature = "alloc")]
 pub mod decode;
 pub mod encode;
 pub mod halfvec;
+pub mod quantize;
 pub mod queue;
+pub mod snapshot;
+#[cfg(feature = "alloc")]
+pub mod stream;
+pub use crc::*;
+#[cfg(feature = "alloc")]
 pub use decode::*;
 pub use encode::*;
+pub use quantize::*;
 pub use queue::*;
+pub use snapshot::*;
+#[cfg(feature = "alloc")]
+pub use stream::*;
 
 ///
 /// An enumeration representing the possible errors that can occur during the decoding process.
@@ -18,25 +30,141 @@ pub enum CodingError {
     NotEnoughBits,
     /// There were bits that indicated an invalid value.
     InvalidBits,
+    /// A word started with a tag that is not valid at its position.
+    InvalidTag(u8),
     /// The first column tag was invalid.
     InvalidInitialColumnTag,
     /// A non-first column tag was invalid.
     InvalidColumnTag,
     /// The number of rows decoded did not match the expected number of rows.
+    #[cfg(feature = "alloc")]
     ColumnLengthMismatch(ColumnLengths),
     /// The number of rows to decode cannot be valid
     InvalidRowCount(usize),
+    /// The stream header is missing or does not describe the columns of the decompressor.
+    HeaderMismatch,
+    /// A column failed to decode.
+    #[cfg(feature = "alloc")]
+    Column(ColumnError),
+    /// A fixed-capacity buffer cannot hold the compressed bytes.
+    BufferFull,
+    /// A continuation packet does not follow the last decoded packet, so a keyframe is needed.
+    MissingKeyframe,
+    /// A value of the named field is outside of its declared `#[tsz(bits = N)]` bit-width.
+    ValueOutOfRange(&'static str),
+    /// The CRC-32 trailer of a `#[tsz(crc)]` packet does not match the bytes of the packet.
+    ChecksumMismatch,
+    /// A column could not grow to hold the decoded rows.
+    AllocationFailed,
+}
+
+#[cfg(feature = "alloc")]
+impl CodingError {
+    ///
+    /// Records the column that was being decoded when the error occurred.
+    ///
+    pub fn in_column(self, index: usize, name: &'static str, nibble_offset: usize) -> Self {
+        CodingError::Column(ColumnError {
+            index,
+            name,
+            nibble_offset,
+            error: ::alloc::boxed::Box::new(self),
+        })
+    }
+}
+
+impl core::fmt::Display for CodingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CodingError::Empty => write!(f, "not enough bytes to decode a packet"),
+            CodingError::NotEnoughBits => write!(f, "ran out of bits while decoding a value"),
+            CodingError::InvalidBits => write!(f, "invalid bits for a value"),
+            CodingError::InvalidTag(tag) => write!(f, "invalid tag {:#06b}", tag),
+            CodingError::InvalidInitialColumnTag => {
+                write!(f, "invalid tag at the start of the first column")
+            }
+            CodingError::InvalidColumnTag => write!(f, "invalid tag at the start of a column"),
+            #[cfg(feature = "alloc")]
+            CodingError::ColumnLengthMismatch(lengths) => write!(
+                f,
+                "decoded column lengths {:?} for {} rows",
+                lengths.column_lengths, lengths.expected_rows
+            ),
+            CodingError::InvalidRowCount(rows) => {
+                write!(f, "decoded rows do not match the row count of {}", rows)
+            }
+            CodingError::HeaderMismatch => {
+                write!(f, "stream header does not match the decompressor")
+            }
+            #[cfg(feature = "alloc")]
+            CodingError::Column(error) => write!(f, "{}", error),
+            CodingError::BufferFull => write!(f, "buffer is too small for the compressed bytes"),
+            CodingError::MissingKeyframe => {
+                write!(
+                    f,
+                    "continuation packet does not follow the last decoded packet"
+                )
+            }
+            CodingError::ValueOutOfRange(name) => {
+                write!(f, "value of {} is outside of its declared bit-width", name)
+            }
+            CodingError::ChecksumMismatch => {
+                write!(f, "packet does not match its checksum")
+            }
+            CodingError::AllocationFailed => {
+                write!(f, "failed to allocate room for the decoded rows")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for CodingError {
+    fn source(&self) -> Option<&(dyn ::std::error::Error + 'static)> {
+        match self {
+            CodingError::Column(error) => Some(error.error.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 ///
 /// A struct representing the expected and actual lengths of columns in a data set.
 ///
+#[cfg(feature = "alloc")]
 #[derive(Debug)]
 pub struct ColumnLengths {
     pub expected_rows: usize,
     pub column_lengths: ::alloc::vec::Vec<usize>,
 }
 
+///
+/// A struct representing where in a packet a column failed to decode.
+///
+#[cfg(feature = "alloc")]
+#[derive(Debug)]
+pub struct ColumnError {
+    /// The index of the field in the row struct.
+    pub index: usize,
+    /// The name of the field in the row struct.
+    pub name: &'static str,
+    /// The number of nibbles consumed from the packet, including any header, when the error occurred.
+    pub nibble_offset: usize,
+    /// The error decoding the column.
+    pub error: ::alloc::boxed::Box<CodingError>,
+}
+
+#[cfg(feature = "alloc")]
+impl core::fmt::Display for ColumnError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "column {} ({}) at nibble {}: {}",
+            self.index, self.name, self.nibble_offset, self.error
+        )
+    }
+}
+
 ///
 /// High-level interface for compression.
 ///
@@ -55,11 +183,27 @@ pub trait TszCompressV2 {
     ///
     /// Lazily compress a row.
     ///
+    /// A value outside of its declared `#[tsz(bits = N)]` bit-width panics in debug builds,
+    /// and is truncated to the bit-width otherwise. See `try_compress` to check the row instead.
+    ///
     fn compress(&mut self, row: Self::T);
 
+    ///
+    /// Compress a row only if the compressor has room to finish it.
+    ///
+    /// Compressors backed by fixed-capacity buffers return `CodingError::BufferFull`
+    /// and leave the state as it was before the row, so the packet can be finished.
+    /// Compressors backed by growing buffers always accept the row.
+    ///
+    /// A row with a value outside of its declared `#[tsz(bits = N)]` bit-width
+    /// returns `CodingError::ValueOutOfRange` and is not compressed.
+    ///
+    fn try_compress(&mut self, row: Self::T) -> Result<(), CodingError>;
+
     ///
     /// The number of bits that have been compressed.
     /// This is an estimate, as the last few samples may have been emitted are estimated.
+    /// See `len_bytes_if_finished` for the exact size of the packet.
     ///
     fn len(&self) -> usize;
 
@@ -88,11 +232,13 @@ pub trait TszCompressV2 {
     ///
     /// Leaving the intermediate buffers in a reserved, cleared state.
     ///
+    #[cfg(feature = "alloc")]
     fn finish_into(&mut self, output_bytes: &mut ::alloc::vec::Vec<u8>);
 
     ///
     /// Convienence method to call `finish_into` compression and return the compressed bytes.
     ///
+    #[cfg(feature = "alloc")]
     fn finish(&mut self) -> ::alloc::vec::Vec<u8> {
         let mut bytes = ::alloc::vec::Vec::new();
         self.finish_into(&mut bytes);
@@ -117,9 +263,142 @@ pub trait TszCompressV2 {
     }
 }
 
+///
+/// Sizing, checkpoints and snapshots of the packet being compressed.
+///
+/// Implemented by `#[derive(CompressV2)]` along with `TszCompressV2`.
+///
+pub trait TszCompressV2Ext: TszCompressV2 {
+    ///
+    /// The exact number of bytes that finishing the compressor would write,
+    /// including flushing the values still pending in the queues.
+    ///
+    fn len_bytes_if_finished(&self) -> usize;
+
+    ///
+    /// Returns true if the packet would still finish within `budget_bytes` after compressing `row`.
+    ///
+    /// Only the length of the words of the row is counted, so the compressor is left as it was.
+    /// A compressor backed by fixed-capacity buffers also checks that the buffers have room for the row.
+    ///
+    fn would_fit(&self, row: &Self::T, budget_bytes: usize) -> bool;
+
+    ///
+    /// The state of the compressor before a row, see `checkpoint`.
+    ///
+    type Checkpoint: Copy;
+
+    ///
+    /// Captures the state needed to roll back any rows compressed after this point.
+    ///
+    /// A checkpoint is small and cheap to take, as the buffers are kept as lengths.
+    ///
+    fn checkpoint(&self) -> Self::Checkpoint;
+
+    ///
+    /// Discards the rows compressed since `checkpoint` was taken.
+    ///
+    /// The checkpoint must have been taken from this compressor since the packet was last finished.
+    ///
+    fn rollback(&mut self, checkpoint: Self::Checkpoint);
+
+    ///
+    /// The number of bytes written by `snapshot_into`.
+    ///
+    fn snapshot_len(&self) -> usize;
+
+    ///
+    /// Writes the whole state of the compressor, including the unfinished packet,
+    /// to the start of the provided slice and returns the number of bytes written.
+    ///
+    /// Returns `CodingError::BufferFull` if the slice is shorter than `snapshot_len`.
+    ///
+    fn snapshot_into(&self, output_bytes: &mut [u8]) -> Result<usize, CodingError>;
+
+    ///
+    /// Convenience method to call `snapshot_into` and return the snapshot bytes.
+    ///
+    /// Panics if `snapshot_into` writes more than `snapshot_len` bytes.
+    ///
+    #[cfg(feature = "alloc")]
+    fn snapshot(&self) -> ::alloc::vec::Vec<u8> {
+        let mut bytes = ::alloc::vec![0; self.snapshot_len()];
+        let len = self
+            .snapshot_into(&mut bytes)
+            .expect("snapshot_len bytes should hold the snapshot");
+        bytes.truncate(len);
+        bytes
+    }
+
+    ///
+    /// Replaces the state of the compressor with a snapshot, so compression continues
+    /// as if the rows before the snapshot were compressed by this compressor.
+    ///
+    /// Returns `CodingError::HeaderMismatch` if the snapshot was taken from a compressor with
+    /// other columns, and `CodingError::BufferFull` if a fixed-capacity buffer cannot hold it.
+    /// On error, the compressor is left empty.
+    ///
+    fn restore(&mut self, snapshot: &[u8]) -> Result<(), CodingError>;
+
+    ///
+    /// The maximum number of bytes in a packet emitted by `compress_into`, if any.
+    ///
+    fn mtu(&self) -> Option<usize>;
+
+    ///
+    /// Sets the maximum number of bytes in a packet emitted by `compress_into`.
+    /// `None` removes the limit, so packets are only emitted by `finish_into`.
+    ///
+    fn set_mtu(&mut self, mtu: Option<usize>);
+
+    ///
+    /// Compress a row, keeping the finished packet within the MTU.
+    ///
+    /// If the row would push the finished packet past the MTU, the rows before it are
+    /// finished into `output_bytes` as an independently decodable packet,
+    /// and the row is carried into the next packet. Returns true if a packet was emitted.
+    ///
+    /// A single row is always accepted, so a packet with one row may exceed a tiny MTU.
+    ///
+    #[cfg(feature = "alloc")]
+    fn compress_into(&mut self, row: Self::T, output_bytes: &mut ::alloc::vec::Vec<u8>) -> bool {
+        // Always accept the first row of a packet
+        let mtu = match self.mtu() {
+            Some(mtu) if self.row_count() > 0 => mtu,
+            _ => {
+                self.compress(row);
+                return false;
+            }
+        };
+
+        // Try the row, then carry it to the next packet if it does not fit
+        let checkpoint = self.checkpoint();
+        self.compress(row);
+        if self.len_bytes_if_finished() <= mtu {
+            return false;
+        }
+        self.rollback(checkpoint);
+        self.finish_into(output_bytes);
+        self.compress(row);
+        true
+    }
+
+    ///
+    /// Consumes the compressor state, writing the compressed bytes to the start
+    /// of the provided slice and returning the number of bytes written.
+    ///
+    /// Never allocates. If the slice is too small, `CodingError::BufferFull` is returned
+    /// and the state is kept, so the packet can be finished into a larger slice.
+    /// If rows compressed with `compress` overflowed a fixed-capacity buffer, the packet
+    /// cannot be finished, so its rows are discarded and `CodingError::BufferFull` is returned.
+    ///
+    fn finish_into_slice(&mut self, output_bytes: &mut [u8]) -> Result<usize, CodingError>;
+}
+
 ///
 /// High-level interface for decompression.
 ///
+#[cfg(feature = "alloc")]
 pub trait TszDecompressV2 {
     type T: Copy;
 
@@ -133,11 +412,27 @@ pub trait TszDecompressV2 {
     ///
     /// This operation will not overwrite existing data in the buffers.
     ///
+    /// Malformed bytes return a `CodingError` rather than panicking, leaving the buffers as they were.
+    /// No column grows past the row count of the packet.
+    ///
     /// # Arguments
     /// * `bits` - The compressed data from a TszCmopressV2 instance.
     ///
     fn decompress(&mut self, bits: &[u8]) -> Result<(), CodingError>;
 
+    ///
+    /// Decompress the next chunk of a stream of packets, as received.
+    ///
+    /// Packets may be split across chunks anywhere, even in the middle of a word,
+    /// and a chunk may hold the end of one packet and the start of the next.
+    /// Rows are available from `rows` and the column accessors as soon as every column of the row is decoded,
+    /// which for a packet is once its last column starts to decode.
+    ///
+    /// Returns `DecodeProgress::NeedMoreData` while a packet is unfinished. On error, the rows of the
+    /// unfinished packet are discarded and the next chunk is expected to start a new packet.
+    ///
+    fn decompress_chunk(&mut self, chunk: &[u8]) -> Result<DecodeProgress, CodingError>;
+
     ///
     /// Rotate the decompressed values into a vector of rows.
     ///
@@ -158,7 +453,7 @@ pub trait TszDecompressV2 {
     fn rows(&self) -> ::alloc::vec::Vec<Self::T>;
 
     ///
-    /// Clears the internal state of the decompressor.
+    /// Clears the internal state of the decompressor, including any unfinished stream.
     ///
     /// This is useful for reusing the decompressor instance for multiple decompression operations.
     ///
@@ -0,0 +1,48 @@
+use num_traits::float::FloatCore;
+
+///
+/// Quantizes a float of a `#[tsz(scale = ..., offset = ...)]` column to the integer it is compressed as,
+/// the nearest multiple of `1 / scale` after subtracting `offset`.
+///
+/// The result is rounded half away from zero, but is returned as a float, as it may be NaN
+/// or outside of the integer column. Casting it to the integer column saturates it to the bounds of the column,
+/// and NaN to 0.
+///
+pub fn quantize(value: f64, scale: f64, offset: f64) -> f64 {
+    FloatCore::round((value - offset) * scale)
+}
+
+///
+/// Reconstructs the float of a quantized integer, to within half a quantization step of the float it was quantized from.
+///
+/// The arithmetic is done in f64, so the same integer always decodes to the same float, on every target.
+///
+pub fn dequantize(quantized: f64, scale: f64, offset: f64) -> f64 {
+    quantized / scale + offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_quantize_within_half_a_step() {
+        for i in -1000..1000 {
+            let value = i as f64 * 0.0137;
+            let quantized = quantize(value, 100.0, -40.0);
+            assert_eq!(quantized, quantized.trunc());
+            assert!((dequantize(quantized, 100.0, -40.0) - value).abs() <= 0.005 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(quantize(0.5, 1.0, 0.0), 1.0);
+        assert_eq!(quantize(-0.5, 1.0, 0.0), -1.0);
+        assert_eq!(quantize(2.5, 1.0, 0.0), 3.0);
+        assert_eq!(quantize(20.0, 0.5, 1.0), 10.0);
+        assert!(quantize(f64::NAN, 1.0, 0.0).is_nan());
+        assert_eq!(quantize(f64::NAN, 1.0, 0.0) as i32, 0);
+        assert_eq!(quantize(1e12, 1.0, 0.0) as i32, i32::MAX);
+    }
+}
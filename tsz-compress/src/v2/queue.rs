@@ -1,4 +1,6 @@
 use super::encode::Bits;
+use super::snapshot::{SnapshotReader, SnapshotWriter};
+use super::CodingError;
 
 ///
 /// A statically sized ring-buffer queue used
@@ -6,12 +8,24 @@ use super::encode::Bits;
 ///
 /// The absolute max size of this buffer is 16 elements.
 ///
-#[derive(Debug)]
+/// Zigzag values are split into their low and high 32 bits, so 64-bit values
+/// are queued the same way on 32-bit targets as on 64-bit hosts.
+///
+#[derive(Debug, Clone, Copy)]
 pub struct CompressionQueue<const N: usize> {
-    zigzag: [usize; 16],
+    zigzag_low: [u32; 16],
+    zigzag_high: [u32; 16],
     bitcount: [usize; 16],
     front: usize,
     len: usize,
+    run_zigzag: u64,
+    run_len: usize,
+}
+
+impl<const N: usize> Default for CompressionQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<const N: usize> CompressionQueue<N> {
@@ -21,10 +35,13 @@ impl<const N: usize> CompressionQueue<N> {
     pub const fn new() -> Self {
         assert!(N <= 16);
         CompressionQueue {
-            zigzag: [0; 16],
+            zigzag_low: [0; 16],
+            zigzag_high: [0; 16],
             bitcount: [0; 16],
             front: 0,
             len: 0,
+            run_zigzag: 0,
+            run_len: 0,
         }
     }
 
@@ -51,6 +68,77 @@ impl<const N: usize> CompressionQueue<N> {
         self.len == 0
     }
 
+    ///
+    /// Returns true if there are no values in the queue and no pending run.
+    ///
+    pub fn is_flushed(&self) -> bool {
+        self.len == 0 && self.run_len == 0
+    }
+
+    ///
+    /// Returns the number of identical values in the pending run.
+    ///
+    pub const fn run_len(&self) -> usize {
+        self.run_len
+    }
+
+    ///
+    /// Starts a pending run from the whole queue if it is full of identical values,
+    /// returning the number of values moved into the run.
+    ///
+    pub fn start_run(&mut self) -> usize {
+        if !self.is_full() || self.run_len > 0 {
+            return 0;
+        }
+
+        let zigzag = unsafe { self.value_at(self.front) };
+        for i in 1..self.len {
+            if unsafe { self.value_at((self.front + i) % 16) } != zigzag {
+                return 0;
+            }
+        }
+
+        let len = self.len;
+        self.run_zigzag = zigzag;
+        self.run_len = len;
+        self.front = (self.front + len) % 16;
+        self.len = 0;
+        len
+    }
+
+    ///
+    /// Moves the leading values that are identical to the pending run into the run,
+    /// returning the number of values moved.
+    ///
+    pub fn extend_run(&mut self) -> usize {
+        if self.run_len == 0 {
+            return 0;
+        }
+
+        let mut moved = 0;
+        while self.len > 0 && unsafe { self.value_at(self.front) } == self.run_zigzag {
+            self.front = (self.front + 1) % 16;
+            self.len -= 1;
+            moved += 1;
+        }
+        self.run_len += moved;
+        moved
+    }
+
+    ///
+    /// Takes the pending run as the number of values and the zigzag value,
+    /// leaving no run pending.
+    ///
+    pub fn take_run(&mut self) -> Option<(usize, u64)> {
+        if self.run_len == 0 {
+            return None;
+        }
+
+        let run = (self.run_len, self.run_zigzag);
+        self.run_len = 0;
+        Some(run)
+    }
+
     ///
     /// Pushes a value into the queue,
     /// overwriting the oldest value if the queue is full.
@@ -69,7 +157,7 @@ impl<const N: usize> CompressionQueue<N> {
     /// Pops the oldest value from the queue,
     /// returning None if the queue is empty.
     ///
-    pub fn pop(&mut self) -> Option<usize> {
+    pub fn pop(&mut self) -> Option<u64> {
         if self.is_empty() {
             return None;
         }
@@ -85,13 +173,16 @@ impl<const N: usize> CompressionQueue<N> {
     /// values may not be meaningful if the queue is
     /// not of length N.
     ///
+    /// Only the low 32 bits of each value are returned,
+    /// which is all of the bits of the values packed several to a word.
+    ///
     #[inline(always)]
     pub fn pop_n<const M: usize>(&mut self) -> [usize; M] {
         let mut values: [usize; M] = [0; M];
         for i in 0..M {
             let index = (self.front + i) % 16;
             unsafe {
-                *values.get_unchecked_mut(i) = self.value_at(index);
+                *values.get_unchecked_mut(i) = *self.zigzag_low.get_unchecked(index) as usize;
             }
         }
         self.front = (self.front + M) % 16;
@@ -105,7 +196,7 @@ impl<const N: usize> CompressionQueue<N> {
     /// not of length N.
     ///
     #[inline(always)]
-    pub fn peak_bitcounts<const M: usize>(&mut self) -> [usize; M] {
+    pub fn peak_bitcounts<const M: usize>(&self) -> [usize; M] {
         let mut values: [usize; M] = [0; M];
         for i in 0..M {
             let index = (self.front + i) % 16;
@@ -116,6 +207,39 @@ impl<const N: usize> CompressionQueue<N> {
         values
     }
 
+    ///
+    /// Writes the values in the queue and the pending run.
+    ///
+    pub fn write_snapshot(&self, writer: &mut SnapshotWriter<'_>) {
+        writer.write_usize(self.len);
+        for i in 0..self.len {
+            writer.write_u128(unsafe { self.value_at((self.front + i) % 16) } as u128);
+        }
+        writer.write_usize(self.run_len);
+        writer.write_u128(self.run_zigzag as u128);
+    }
+
+    ///
+    /// Reads a queue written by `write_snapshot`.
+    ///
+    pub fn read_snapshot(reader: &mut SnapshotReader<'_>) -> Result<Self, CodingError> {
+        let len = reader.read_usize()?;
+        if len > 16 {
+            return Err(CodingError::InvalidBits);
+        }
+        let mut queue = Self::new();
+        for index in 0..len {
+            let zigzag = read_zigzag(reader)?;
+            queue.zigzag_low[index] = zigzag as u32;
+            queue.zigzag_high[index] = (zigzag >> 32) as u32;
+            queue.bitcount[index] = (u64::BITS - zigzag.leading_zeros()) as usize;
+        }
+        queue.len = len;
+        queue.run_len = reader.read_usize()?;
+        queue.run_zigzag = read_zigzag(reader)?;
+        Ok(queue)
+    }
+
     ///
     /// Internal use accessor to an initialized value.
     ///
@@ -123,8 +247,9 @@ impl<const N: usize> CompressionQueue<N> {
     /// This function is unsafe because it assumes that
     /// the index is inbounds and initialized.
     ///
-    unsafe fn value_at(&self, index: usize) -> usize {
-        *self.zigzag.get_unchecked(index)
+    unsafe fn value_at(&self, index: usize) -> u64 {
+        ((*self.zigzag_high.get_unchecked(index) as u64) << 32)
+            | *self.zigzag_low.get_unchecked(index) as u64
     }
 
     ///
@@ -149,11 +274,19 @@ impl<const N: usize> CompressionQueue<N> {
     ///
     unsafe fn write<T: Bits + Sized>(&mut self, index: usize, t: T) {
         let (zbits, zcount) = t.zigzag_bits();
-        *self.zigzag.get_unchecked_mut(index) = zbits;
+        *self.zigzag_low.get_unchecked_mut(index) = zbits as u32;
+        *self.zigzag_high.get_unchecked_mut(index) = (zbits >> 32) as u32;
         *self.bitcount.get_unchecked_mut(index) = zcount;
     }
 }
 
+///
+/// Reads a zigzag value of a snapshot, rejecting values wider than 64 bits.
+///
+fn read_zigzag(reader: &mut SnapshotReader<'_>) -> Result<u64, CodingError> {
+    u64::try_from(reader.read_u128()?).map_err(|_| CodingError::InvalidBits)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,22 +295,22 @@ mod tests {
     fn can_init() {
         let queue: CompressionQueue<10> = CompressionQueue::new();
         assert_eq!(queue.len(), 0);
-        assert_eq!(queue.is_full(), false);
-        assert_eq!(queue.is_empty(), true);
+        assert!(!queue.is_full());
+        assert!(queue.is_empty());
     }
 
     #[test]
     fn is_empty_or_full() {
         let mut queue: CompressionQueue<4> = CompressionQueue::new();
         assert_eq!(queue.len(), 0);
-        assert_eq!(queue.is_empty(), true);
-        assert_eq!(queue.is_full(), false);
+        assert!(queue.is_empty());
+        assert!(!queue.is_full());
 
         // push 4 values, queue should be full
         for i in 0..4 {
             queue.push(i as i8);
             assert_eq!(queue.len(), i + 1);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
             assert_eq!(queue.is_full(), i == 3);
         }
 
@@ -186,7 +319,7 @@ mod tests {
             assert_eq!(queue.pop(), Some((i as i8).zigzag()));
             assert_eq!(queue.len(), 3 - i);
             assert_eq!(queue.is_empty(), (i == 3));
-            assert_eq!(queue.is_full(), false);
+            assert!(!queue.is_full());
         }
     }
 
@@ -198,7 +331,7 @@ mod tests {
         for i in 0..4 {
             queue.push(i as i8);
             assert_eq!(queue.len(), i + 1);
-            assert_eq!(queue.is_empty(), false);
+            assert!(!queue.is_empty());
             assert_eq!(queue.is_full(), i == 3);
         }
 
@@ -206,39 +339,77 @@ mod tests {
         for i in 4..8 {
             queue.push(i as i8);
             assert_eq!(queue.len(), i + 1);
-            assert_eq!(queue.is_empty(), false);
-            assert_eq!(queue.is_full(), true);
+            assert!(!queue.is_empty());
+            assert!(queue.is_full());
         }
 
         // keep pushing, queue should still be full and start overwriting
         for i in 8..20 {
             queue.push(i as i8);
             assert_eq!(queue.len(), (i + 1).min(16));
-            assert_eq!(queue.is_empty(), false);
-            assert_eq!(queue.is_full(), true);
+            assert!(!queue.is_empty());
+            assert!(queue.is_full());
         }
 
         // pop the values, they should be 16..20
         for j in 0..4 {
             assert_eq!(queue.pop(), Some(((j + 4) as i8).zigzag()));
             assert_eq!(queue.len(), 15 - j);
-            assert_eq!(queue.is_empty(), false);
-            assert_eq!(queue.is_full(), true);
+            assert!(!queue.is_empty());
+            assert!(queue.is_full());
         }
 
         // pop another 8 values, then the queue will start to empty
         queue.pop_n::<8>();
         assert_eq!(queue.len(), 4);
-        assert_eq!(queue.is_empty(), false);
-        assert_eq!(queue.is_full(), true);
+        assert!(!queue.is_empty());
+        assert!(queue.is_full());
 
         // pop the remaining 4 values, then the queue will be empty
         for j in 0..4 {
             assert_eq!(queue.pop(), Some(((j + 16) as i8).zigzag()));
             assert_eq!(queue.len(), 3 - j);
             assert_eq!(queue.is_empty(), (j == 3));
-            assert_eq!(queue.is_full(), false);
+            assert!(!queue.is_full());
+        }
+    }
+
+    #[test]
+    fn can_queue_64_bit_values() {
+        let mut queue: CompressionQueue<10> = CompressionQueue::new();
+        let values = [i64::MIN, i64::MAX, 1 << 40, -(1 << 33), 5];
+        for value in values {
+            queue.push(value);
+        }
+        assert_eq!(queue.peak_bitcounts::<5>(), [64, 64, 42, 34, 4]);
+
+        // The snapshot holds the whole of each value
+        let mut out = [0u8; 64];
+        let mut writer = SnapshotWriter::new(&mut out);
+        queue.write_snapshot(&mut writer);
+        let len = writer.finish().unwrap();
+        let mut restored =
+            CompressionQueue::<10>::read_snapshot(&mut SnapshotReader::new(&out[..len])).unwrap();
+        assert_eq!(restored.peak_bitcounts::<5>(), [64, 64, 42, 34, 4]);
+
+        for value in values {
+            assert_eq!(queue.pop(), Some(value.zigzag()));
+            assert_eq!(restored.pop(), Some(value.zigzag()));
+        }
+    }
+
+    #[test]
+    fn can_run_64_bit_values() {
+        let mut queue: CompressionQueue<10> = CompressionQueue::new();
+        for _ in 0..10 {
+            queue.push(1i64 << 40);
         }
+        assert_eq!(queue.start_run(), 10);
+
+        // A value with the same low 32 bits does not extend the run
+        queue.push((1i64 << 40) + (1 << 60));
+        assert_eq!(queue.extend_run(), 0);
+        assert_eq!(queue.take_run(), Some((10, 1 << 41)));
     }
 
     #[test]
@@ -247,13 +418,13 @@ mod tests {
         use rand::Rng;
 
         let mut rng = rand::thread_rng();
-        let mut std_queue: VecDeque<usize> = VecDeque::new();
+        let mut std_queue: VecDeque<u64> = VecDeque::new();
         let mut queue: CompressionQueue<10> = CompressionQueue::new();
         for _ in 0..10000 {
             let value = rng.gen::<i32>();
             let zig_zag_value = value.zigzag();
             if rng.gen::<bool>() {
-                std_queue.push_back(zig_zag_value as usize);
+                std_queue.push_back(zig_zag_value);
                 if queue.len() == 16 {
                     assert_eq!(std_queue.pop_front(), queue.pop());
                 }
@@ -0,0 +1,271 @@
+use crate::prelude::*;
+use crate::v2::consts::schema;
+
+///
+/// Writes the state of a compressor as compact bytes.
+///
+/// Integers are written as LEB128 varints, signed integers zigzag encoded first.
+/// Writing past the end of the output is counted but not stored, so the same
+/// writer measures the length of a snapshot.
+///
+#[derive(Debug)]
+pub struct SnapshotWriter<'a> {
+    out: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> SnapshotWriter<'a> {
+    ///
+    /// Creates a writer that fills `out` from the start.
+    ///
+    pub fn new(out: &'a mut [u8]) -> Self {
+        Self { out, len: 0 }
+    }
+
+    ///
+    /// The number of bytes written so far, including any that did not fit.
+    ///
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    ///
+    /// Returns true if nothing has been written.
+    ///
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    ///
+    /// Returns the length of the snapshot, or `CodingError::BufferFull` if it did not fit.
+    ///
+    pub fn finish(self) -> Result<usize, CodingError> {
+        match self.len <= self.out.len() {
+            true => Ok(self.len),
+            false => Err(CodingError::BufferFull),
+        }
+    }
+
+    ///
+    /// Writes a single byte.
+    ///
+    pub fn write_u8(&mut self, value: u8) {
+        if let Some(byte) = self.out.get_mut(self.len) {
+            *byte = value;
+        }
+        self.len += 1;
+    }
+
+    ///
+    /// Writes an unsigned integer as a varint.
+    ///
+    pub fn write_u128(&mut self, mut value: u128) {
+        while value >= 0x80 {
+            self.write_u8(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.write_u8(value as u8);
+    }
+
+    ///
+    /// Writes a signed integer as a zigzag encoded varint.
+    ///
+    pub fn write_i128(&mut self, value: i128) {
+        self.write_u128(((value << 1) ^ (value >> 127)) as u128);
+    }
+
+    ///
+    /// Writes a length or count as a varint.
+    ///
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u128(value as u128);
+    }
+
+    ///
+    /// Writes a flag as a byte.
+    ///
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(value as u8);
+    }
+
+    ///
+    /// Writes the bytes as they are.
+    ///
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_u8(*byte);
+        }
+    }
+
+    ///
+    /// Writes the start of a snapshot, identifying the compressor by the schema codes of its columns.
+    ///
+    pub fn write_magic(&mut self, columns: &[u8]) {
+        self.write_u8((schema::MAGIC << 4) | schema::VERSION);
+        self.write_usize(columns.len());
+        self.write_bytes(columns);
+    }
+}
+
+///
+/// Reads the state of a compressor written by a `SnapshotWriter`.
+///
+#[derive(Debug)]
+pub struct SnapshotReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> SnapshotReader<'a> {
+    ///
+    /// Creates a reader over the bytes of a snapshot.
+    ///
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    ///
+    /// Checks that every byte of the snapshot was read.
+    ///
+    pub fn finish(self) -> Result<(), CodingError> {
+        match self.bytes.is_empty() {
+            true => Ok(()),
+            false => Err(CodingError::InvalidBits),
+        }
+    }
+
+    ///
+    /// Reads a single byte.
+    ///
+    pub fn read_u8(&mut self) -> Result<u8, CodingError> {
+        let (byte, rest) = self.bytes.split_first().ok_or(CodingError::NotEnoughBits)?;
+        self.bytes = rest;
+        Ok(*byte)
+    }
+
+    ///
+    /// Reads a varint, rejecting values wider than 128 bits.
+    ///
+    pub fn read_u128(&mut self) -> Result<u128, CodingError> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.read_u8()?;
+            let bits = (byte & 0x7F) as u128;
+            if shift > 0 && bits >> (128 - shift) != 0 {
+                return Err(CodingError::InvalidBits);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(CodingError::InvalidBits)
+    }
+
+    ///
+    /// Reads a zigzag encoded varint.
+    ///
+    pub fn read_i128(&mut self) -> Result<i128, CodingError> {
+        let value = self.read_u128()?;
+        Ok((value >> 1) as i128 ^ -((value & 1) as i128))
+    }
+
+    ///
+    /// Reads a length or count, rejecting values that do not fit in a usize.
+    ///
+    pub fn read_usize(&mut self) -> Result<usize, CodingError> {
+        usize::try_from(self.read_u128()?).map_err(|_| CodingError::InvalidBits)
+    }
+
+    ///
+    /// Reads a flag, rejecting bytes other than 0 and 1.
+    ///
+    pub fn read_bool(&mut self) -> Result<bool, CodingError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(CodingError::InvalidBits),
+        }
+    }
+
+    ///
+    /// Reads the next `len` bytes as they are.
+    ///
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], CodingError> {
+        if self.bytes.len() < len {
+            return Err(CodingError::NotEnoughBits);
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    ///
+    /// Reads the start of a snapshot, checking that it is for a compressor with the same columns.
+    ///
+    pub fn read_magic(&mut self, columns: &[u8]) -> Result<(), CodingError> {
+        if self.read_u8()? != (schema::MAGIC << 4) | schema::VERSION
+            || self.read_usize()? != columns.len()
+            || self.read_bytes(columns.len())? != columns
+        {
+            return Err(CodingError::HeaderMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_round_trip_varints() {
+        let values = [
+            0,
+            1,
+            -1,
+            63,
+            -64,
+            64,
+            i64::MAX as i128,
+            i128::MIN,
+            i128::MAX,
+        ];
+        let mut out = [0u8; 256];
+        let mut writer = SnapshotWriter::new(&mut out);
+        for value in values {
+            writer.write_i128(value);
+            writer.write_u128(value as u128);
+        }
+        let len = writer.finish().unwrap();
+
+        let mut reader = SnapshotReader::new(&out[..len]);
+        for value in values {
+            assert_eq!(reader.read_i128().unwrap(), value);
+            assert_eq!(reader.read_u128().unwrap(), value as u128);
+        }
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn counts_bytes_past_the_end() {
+        let mut out = [0u8; 2];
+        let mut writer = SnapshotWriter::new(&mut out);
+        writer.write_u128(u128::MAX);
+        assert_eq!(writer.len(), 19);
+        assert!(matches!(writer.finish(), Err(CodingError::BufferFull)));
+    }
+
+    #[test]
+    fn rejects_overlong_varints() {
+        let bytes = [0xFF; 19];
+        let mut reader = SnapshotReader::new(&bytes);
+        assert!(matches!(reader.read_u128(), Err(CodingError::InvalidBits)));
+
+        let bytes = [0x80, 0x80];
+        let mut reader = SnapshotReader::new(&bytes);
+        assert!(matches!(
+            reader.read_u128(),
+            Err(CodingError::NotEnoughBits)
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+use crate::prelude::*;
+use crate::v2::consts::headers;
+use alloc::vec::Vec;
+
+///
+/// Whether a stream of chunks ended on a packet boundary.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeProgress {
+    /// Every packet in the chunks so far has been decompressed.
+    Complete,
+    /// A packet has only been partially received, so more data is needed to finish it.
+    NeedMoreData,
+}
+
+///
+/// Where in a packet the stream is.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+enum StreamPhase {
+    #[default]
+    Header,
+    Sequence,
+    RowCount,
+    ColumnStart(usize),
+    Column(usize),
+    Padding,
+    Trailer,
+}
+
+///
+/// The state of a stream between chunks: the bytes of an unfinished word and the place in the packet.
+///
+/// Only the bytes of a word that has not been fully received are kept,
+/// so the state stays small regardless of the packet size.
+///
+#[derive(Debug, Default)]
+pub struct StreamState {
+    pending: Vec<u8>,
+    nibble: usize,
+    packet_nibbles: usize,
+    phase: StreamPhase,
+    rows: usize,
+    sequence: Option<u16>,
+    crc: Crc32,
+    crc_bytes: usize,
+}
+
+impl StreamState {
+    ///
+    /// Returns true if the stream is between packets.
+    ///
+    pub fn is_complete(&self) -> bool {
+        self.phase == StreamPhase::Header && self.nibble == self.pending.len() * 2
+    }
+}
+
+///
+/// Resumable decoding of the columns of a packet, implemented by the derived decompressors.
+///
+/// Presence columns of nullable fields count as columns of their own.
+///
+pub trait StreamColumns {
+    /// The length of the stream header in bytes, or 0 if packets have no header.
+    const HEADER_LEN: usize;
+
+    /// The field index and name of each column, in packet order.
+    const COLUMNS: &'static [(usize, &'static str)];
+
+    /// True if a sequence field precedes the row count, see `consts::sequence`.
+    const CONTINUATION: bool;
+
+    /// True if a CRC-32 trailer follows the columns of each packet.
+    const CRC: bool;
+
+    /// Checks the stream header at the start of a packet.
+    fn check_header(header: &[u8]) -> Result<(), CodingError>;
+
+    /// The state of the stream between chunks.
+    fn stream_state(&mut self) -> &mut StreamState;
+
+    ///
+    /// Prepares the columns for a packet of `rows` rows,
+    /// checking the sequence field of the packet if there is one.
+    ///
+    fn begin_packet(&mut self, rows: usize, sequence: Option<u16>) -> Result<(), CodingError>;
+
+    ///
+    /// Decodes the next word of a column of the packet.
+    ///
+    /// Returns true, without reading, once the column holds all of its values.
+    ///
+    fn step_column(
+        &mut self,
+        column: usize,
+        rows: usize,
+        iter: &mut HalfIter<'_>,
+    ) -> Result<bool, CodingError>;
+
+    /// Discards the rows of a partially decoded packet.
+    fn abort_packet(&mut self);
+}
+
+///
+/// Decompresses the next chunk of a stream of packets.
+///
+/// Words split across chunks are kept until the rest of the word arrives.
+/// Rows are available from the decompressor as soon as every column of the row is decoded.
+///
+/// On error, the rows of the unfinished packet are discarded and the stream restarts
+/// with a new packet at the next chunk. Packets with a CRC-32 trailer are only checked once
+/// the trailer arrives, so the rows of a packet that fails its checksum are discarded then.
+///
+pub fn decompress_stream_chunk<D: StreamColumns>(
+    decompressor: &mut D,
+    chunk: &[u8],
+) -> Result<DecodeProgress, CodingError> {
+    let mut state = core::mem::take(decompressor.stream_state());
+    state.pending.extend_from_slice(chunk);
+
+    let result = decode_pending(decompressor, &mut state);
+    if result.is_err() {
+        // A packet that fails before its row count has not begun, so there are no rows to discard
+        if !matches!(
+            state.phase,
+            StreamPhase::Header | StreamPhase::Sequence | StreamPhase::RowCount
+        ) {
+            decompressor.abort_packet();
+        }
+        state = StreamState::default();
+    }
+
+    // Keep only the bytes that have not been consumed
+    let consumed = state.nibble / 2;
+    state.pending.drain(..consumed);
+    state.nibble -= consumed * 2;
+    state.crc_bytes = state.crc_bytes.saturating_sub(consumed);
+
+    let progress = match state.is_complete() {
+        true => DecodeProgress::Complete,
+        false => DecodeProgress::NeedMoreData,
+    };
+    *decompressor.stream_state() = state;
+    result.map(|_| progress)
+}
+
+///
+/// Decodes as many words from the pending bytes as possible.
+///
+fn decode_pending<D: StreamColumns>(
+    decompressor: &mut D,
+    state: &mut StreamState,
+) -> Result<(), CodingError> {
+    loop {
+        // Check every byte of the packet as it is consumed
+        if D::CRC && state.crc_bytes < state.nibble / 2 {
+            state
+                .crc
+                .update(&state.pending[state.crc_bytes..state.nibble / 2]);
+            state.crc_bytes = state.nibble / 2;
+        }
+
+        let mut iter = HalfIter::new(&state.pending[state.nibble / 2..]);
+        if state.nibble % 2 == 1 {
+            iter.next();
+        }
+        let start = iter.nibble_offset();
+        let remaining = state.pending.len() * 2 - state.nibble;
+
+        let result = match state.phase {
+            StreamPhase::Header => {
+                // Stay between packets until the next packet starts
+                if remaining == 0 || remaining < D::HEADER_LEN * 2 {
+                    return Ok(());
+                }
+                let bytes = &state.pending[state.nibble / 2..];
+                D::check_header(&bytes[..D::HEADER_LEN])?;
+                state.nibble += D::HEADER_LEN * 2;
+                state.packet_nibbles = D::HEADER_LEN * 2;
+                state.phase = StreamPhase::Sequence;
+                continue;
+            }
+            StreamPhase::Sequence => {
+                // The sequence field of continuation packets
+                state.sequence = None;
+                if D::CONTINUATION {
+                    if remaining < 4 {
+                        return Ok(());
+                    }
+                    let bytes = &state.pending[state.nibble / 2..];
+                    state.sequence = Some(u16::from_be_bytes([bytes[0], bytes[1]]));
+                    state.nibble += 4;
+                    state.packet_nibbles += 4;
+                }
+                state.phase = StreamPhase::RowCount;
+                continue;
+            }
+            StreamPhase::RowCount => match read_row_count(&mut iter) {
+                Ok(rows) => {
+                    decompressor.begin_packet(rows, state.sequence)?;
+                    state.rows = rows;
+                    state.phase = StreamPhase::ColumnStart(0);
+                    Ok(())
+                }
+                // Wait for the rest of the row count
+                Err(CodingError::NotEnoughBits) => return Ok(()),
+                Err(error) => Err(error),
+            },
+            StreamPhase::ColumnStart(column) => match iter.next() {
+                None => return Ok(()),
+                Some(headers::START_OF_COLUMN) => {
+                    state.phase = StreamPhase::Column(column);
+                    Ok(())
+                }
+                Some(_) if column == 0 => Err(CodingError::InvalidInitialColumnTag),
+                Some(_) => Err(CodingError::InvalidColumnTag),
+            },
+            StreamPhase::Column(column) => {
+                match decompressor.step_column(column, state.rows, &mut iter) {
+                    Ok(true) if column + 1 < D::COLUMNS.len() => {
+                        state.phase = StreamPhase::ColumnStart(column + 1);
+                        Ok(())
+                    }
+                    Ok(true) => {
+                        state.phase = StreamPhase::Padding;
+                        Ok(())
+                    }
+                    Ok(false) => Ok(()),
+                    // Wait for the rest of the word
+                    Err(CodingError::NotEnoughBits) => return Ok(()),
+                    Err(error) => {
+                        let (index, name) = D::COLUMNS[column];
+                        let offset = state.packet_nibbles + iter.nibble_offset() - start;
+                        Err(error.in_column(index, name, offset))
+                    }
+                }
+            }
+            StreamPhase::Padding if state.packet_nibbles.is_multiple_of(2) => {
+                state.phase = match D::CRC {
+                    true => StreamPhase::Trailer,
+                    false => StreamPhase::Header,
+                };
+                continue;
+            }
+            StreamPhase::Padding => match iter.next() {
+                None => return Ok(()),
+                Some(headers::START_OF_COLUMN) => {
+                    state.phase = match D::CRC {
+                        true => StreamPhase::Trailer,
+                        false => StreamPhase::Header,
+                    };
+                    Ok(())
+                }
+                Some(_) => Err(CodingError::InvalidColumnTag),
+            },
+            StreamPhase::Trailer => {
+                // The checksum of every byte of the packet before it
+                if remaining < 8 {
+                    return Ok(());
+                }
+                let bytes = &state.pending[state.nibble / 2..];
+                let crc = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                if crc != state.crc.finish() {
+                    return Err(CodingError::ChecksumMismatch);
+                }
+                state.nibble += 8;
+                state.crc = Crc32::new();
+                state.crc_bytes = state.nibble / 2;
+                state.phase = StreamPhase::Header;
+                continue;
+            }
+        };
+        result?;
+
+        let consumed = iter.nibble_offset() - start;
+        state.nibble += consumed;
+        state.packet_nibbles += consumed;
+    }
+}
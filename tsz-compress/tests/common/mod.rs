@@ -0,0 +1,82 @@
+//!
+//! The row and random rows shared by the integration tests.
+//!
+//! Each test derives its own `TestRow` with `test_row!`, passing the struct attributes it tests,
+//! so only the attributes differ between the tests.
+//!
+use rand::Rng;
+
+///
+/// The columns of every row declared by `test_row!`: a periodic timestamp,
+/// a small signed value, a nullable unsigned value and a wide value.
+///
+pub trait SharedRow: Copy {
+    fn from_columns(ts: i64, a: i16, b: Option<u32>, c: i128) -> Self;
+    fn columns(&self) -> (i64, i16, Option<u32>, i128);
+}
+
+///
+/// Declares `TestRow` with the shared columns and the given struct attributes,
+/// along with its compressors and decompressor.
+///
+macro_rules! test_row {
+    ($(#[$attr:meta])*) => {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        $(#[$attr])*
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i16,
+            pub b: Option<u32>,
+            pub c: i128,
+        }
+
+        impl $crate::common::SharedRow for TestRow {
+            fn from_columns(ts: i64, a: i16, b: Option<u32>, c: i128) -> Self {
+                TestRow { ts, a, b, c }
+            }
+
+            fn columns(&self) -> (i64, i16, Option<u32>, i128) {
+                (self.ts, self.a, self.b, self.c)
+            }
+        }
+
+        pub use compress::*;
+        pub use decompress::*;
+    };
+}
+pub(crate) use test_row;
+
+///
+/// Timestamps about a second apart, small values, a column that is absent a quarter of the time,
+/// and a wide value that drifts.
+///
+pub fn random_rows<T: SharedRow>(n: usize) -> Vec<T> {
+    let mut rng = rand::thread_rng();
+    let mut ts = 1_700_000_000_000;
+    let mut c = rng.gen::<i32>() as i128;
+    (0..n)
+        .map(|_| {
+            ts += rng.gen_range(990..1010);
+            c += rng.gen_range(-1000..1000);
+            T::from_columns(
+                ts,
+                rng.gen_range(-100..100),
+                rng.gen_ratio(3, 4).then(|| rng.gen_range(0..1000)),
+                c,
+            )
+        })
+        .collect()
+}
+
+///
+/// The same rows as the `TestRow` of another test.
+///
+pub fn as_rows<T: SharedRow, U: SharedRow>(rows: &[T]) -> Vec<U> {
+    rows.iter()
+        .map(|row| {
+            let (ts, a, b, c) = row.columns();
+            U::from_columns(ts, a, b, c)
+        })
+        .collect()
+}
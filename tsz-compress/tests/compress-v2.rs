@@ -1,10 +1,12 @@
-#![allow(unused)]
+#![allow(unused, clippy::reversed_empty_ranges)]
 use tsz_compress::prelude::*;
 
 extern crate alloc;
 
 use rand::Rng;
 
+mod common;
+
 #[cfg(test)]
 mod tests {
 
@@ -2284,3 +2286,3823 @@ mod test_field_attributes {
         }
     }
 }
+
+#[cfg(test)]
+mod test_delta_delta {
+
+    use super::*;
+    use rand::Rng;
+
+    /// The upper nibble of the first column is the start of column tag
+    /// and the lower nibble is the first row tag that selects the encoding.
+    const DELTA_DELTA_COLUMN: u8 =
+        (consts::headers::START_OF_COLUMN << 4) | consts::headers::FIRST_ROW_DELTA_DELTA;
+
+    /// The same, with a first row small enough to be written as a varint.
+    const DELTA_DELTA_VARINT_COLUMN: u8 =
+        (consts::headers::START_OF_COLUMN << 4) | consts::headers::FIRST_ROW_DELTA_DELTA_VARINT;
+
+    #[test]
+    fn test_macro_compress_i64_periodic_timestamps() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub ts: i64,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+
+        // 50 Hz epoch microseconds with a little jitter
+        let values: Vec<i64> = (0..1000)
+            .map(|i| 1_675_465_460_000_000 + i * 20_000 + rng.gen_range(-1..=1))
+            .collect();
+
+        // Initialize the compressor
+        let mut compressor = TestRowCompressorImpl::new(128);
+
+        // Compress rows
+        for value in &values {
+            compressor.compress(TestRow { ts: *value });
+        }
+
+        // Finalize the compression
+        let bytes = compressor.finish();
+
+        // Delta-delta is chosen for the column and costs about a byte per row, after 4 nibbles of row count
+        assert_eq!(bytes[2], DELTA_DELTA_COLUMN);
+        assert!(bytes.len() < 2 + 8 + 16 + 2 * values.len());
+
+        // Decompress the bit buffer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+
+        // Assert that the decompressed data matches the original
+        assert_eq!(values, decompressor.col_ts());
+    }
+
+    #[test]
+    fn test_macro_compress_i64_delta_delta_all_widths() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub ts: i64,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        // An accelerating ramp with jumps that need 5, 9, 16, 32, and 64 bits of delta-delta
+        let mut values: Vec<i64> = Vec::new();
+        let mut value = 0i64;
+        for jump in [0, 10, 200, 30_000, 1 << 30, 1 << 62, -(1 << 62)] {
+            for i in 0..100 {
+                value += 1000 + i;
+                values.push(value);
+            }
+            value += jump;
+        }
+
+        // Initialize the compressor
+        let mut compressor = TestRowCompressorImpl::new(128);
+
+        // Compress rows
+        for value in &values {
+            compressor.compress(TestRow { ts: *value });
+        }
+
+        // Finalize the compression
+        let bytes = compressor.finish();
+        assert_eq!(bytes[2], DELTA_DELTA_VARINT_COLUMN);
+
+        // Decompress the bit buffer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+
+        // Assert that the decompressed data matches the original
+        assert_eq!(values, decompressor.col_ts());
+    }
+
+    #[test]
+    fn test_macro_compress_mixed_delta_delta_ramps() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub a: i8,
+                pub b: i16,
+                pub c: i32,
+                pub d: i64,
+                #[tsz(delta = "i16")]
+                pub e: i32,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            // Initialize the compressor
+            let mut compressor = TestRowCompressorImpl::new(128);
+
+            // Ramps that wrap around and occasionally jump
+            let rows: Vec<TestRow> = (0..rng.gen_range(0..2000))
+                .map(|i: i64| {
+                    let jump = if rng.gen_ratio(1, 50) {
+                        rng.gen_range(-100..100)
+                    } else {
+                        0
+                    };
+                    TestRow {
+                        a: (i as i8).wrapping_mul(3),
+                        b: (i * 300) as i16,
+                        c: (i * 70_000 + jump) as i32,
+                        d: i * 1_000_000_007 + jump,
+                        e: (i * 5 + jump) as i32,
+                    }
+                })
+                .collect();
+
+            // Compress rows
+            for row in &rows {
+                compressor.compress(*row);
+            }
+
+            // Finalize the compression
+            let bytes = compressor.finish();
+
+            // Decompress the bit buffer
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+
+            // Assert that the decompressed data matches the original
+            let a: Vec<i8> = rows.iter().map(|row| row.a).collect();
+            let b: Vec<i16> = rows.iter().map(|row| row.b).collect();
+            let c: Vec<i32> = rows.iter().map(|row| row.c).collect();
+            let d: Vec<i64> = rows.iter().map(|row| row.d).collect();
+            let e: Vec<i32> = rows.iter().map(|row| row.e).collect();
+            assert_eq!(a, decompressor.col_a());
+            assert_eq!(b, decompressor.col_b());
+            assert_eq!(c, decompressor.col_c());
+            assert_eq!(d, decompressor.col_d());
+            assert_eq!(e, decompressor.col_e());
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_delta_delta_reuse_after_finish() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub a: i32,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+
+        // Alternate between packets that prefer delta and delta-delta
+        let mut compressor = TestRowCompressorImpl::new(128);
+        for packet in 0..10 {
+            let values: Vec<i32> = if packet % 2 == 0 {
+                (0..500).map(|i| i * 1000).collect()
+            } else {
+                (0..500).map(|_| rng.gen_range(-3..=3)).collect()
+            };
+            for value in &values {
+                compressor.compress(TestRow { a: *value });
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(values, decompressor.col_a());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_unsigned {
+
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_macro_compress_unsigned_zero_and_one_rows() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub a: u8,
+                pub b: u16,
+                pub c: u32,
+                pub d: u64,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        let row = TestRow {
+            a: u8::MAX,
+            b: u16::MAX,
+            c: u32::MAX,
+            d: u64::MAX,
+        };
+
+        for n in 0..=1 {
+            // Initialize the compressor
+            let mut compressor = TestRowCompressorImpl::new(128);
+
+            // Compress rows
+            for _ in 0..n {
+                compressor.compress(row);
+            }
+
+            // Finalize the compression
+            let bytes = compressor.finish();
+
+            // Decompress the bit buffer
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+
+            // Assert that the decompressed data matches the original
+            assert_eq!(decompressor.col_a(), vec![row.a; n]);
+            assert_eq!(decompressor.col_b(), vec![row.b; n]);
+            assert_eq!(decompressor.col_c(), vec![row.c; n]);
+            assert_eq!(decompressor.col_d(), vec![row.d; n]);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_u8_all_deltas() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub a: u8,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        // Every value followed by both extremes
+        let mut values: Vec<u8> = Vec::new();
+        for i in u8::MIN..=u8::MAX {
+            values.extend([i, u8::MIN, i, u8::MAX]);
+        }
+
+        // Initialize the compressor
+        let mut compressor = TestRowCompressorImpl::new(128);
+
+        // Compress rows
+        for value in &values {
+            compressor.compress(TestRow { a: *value });
+        }
+
+        // Finalize the compression
+        let bytes = compressor.finish();
+
+        // Decompress the bit buffer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+
+        // Assert that the decompressed data matches the original
+        assert_eq!(values, decompressor.col_a());
+    }
+
+    #[test]
+    fn test_macro_compress_unsigned_values_random() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub uptime: u32,
+                pub adc: u16,
+                pub a: u8,
+                pub d: u64,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            // Initialize the compressor
+            let mut compressor = TestRowCompressorImpl::new(128);
+
+            // SoC uptime near the top of the range, noisy ADC counts, and full range values
+            let start = u32::MAX - rng.gen_range(0..1_000_000);
+            let rows: Vec<TestRow> = (0..rng.gen_range(0..1000))
+                .map(|i: u32| TestRow {
+                    uptime: start.wrapping_add(i * 1000),
+                    adc: rng.gen_range(u16::MAX - 16..=u16::MAX),
+                    a: rng.gen(),
+                    d: rng.gen(),
+                })
+                .collect();
+
+            // Compress rows
+            for row in &rows {
+                compressor.compress(*row);
+            }
+
+            // Finalize the compression
+            let bytes = compressor.finish();
+
+            // Decompress the bit buffer
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+
+            // Assert that the decompressed data matches the original
+            let uptime: Vec<u32> = rows.iter().map(|row| row.uptime).collect();
+            let adc: Vec<u16> = rows.iter().map(|row| row.adc).collect();
+            let a: Vec<u8> = rows.iter().map(|row| row.a).collect();
+            let d: Vec<u64> = rows.iter().map(|row| row.d).collect();
+            assert_eq!(uptime, decompressor.col_uptime());
+            assert_eq!(adc, decompressor.col_adc());
+            assert_eq!(a, decompressor.col_a());
+            assert_eq!(d, decompressor.col_d());
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_u16_value_i16_delta() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                #[tsz(delta = "i16")]
+                pub a: u16,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        // Small steps across the signed boundary of the delta bit-width
+        let values: Vec<u16> = (32000..34000).chain((0..2000).rev()).collect();
+
+        // Initialize the compressor
+        let mut compressor = TestRowCompressorImpl::new(128);
+
+        // Compress rows
+        for value in &values {
+            compressor.compress(TestRow { a: *value });
+        }
+
+        // Finalize the compression
+        let bytes = compressor.finish();
+
+        // Decompress the bit buffer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+
+        // Assert that the decompressed data matches the original
+        assert_eq!(values, decompressor.col_a());
+    }
+}
+
+#[cfg(test)]
+mod test_i128 {
+
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_macro_compress_i128_zero_one_two_rows() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub a: i128,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        // The first delta does not fit in 128 bits, so it must wrap
+        let values = [i128::MIN, i128::MAX];
+
+        for n in 0..=2 {
+            // Initialize the compressor
+            let mut compressor = TestRowCompressorImpl::new(128);
+
+            // Compress rows
+            for value in &values[..n] {
+                compressor.compress(TestRow { a: *value });
+            }
+
+            // Finalize the compression
+            let bytes = compressor.finish();
+
+            // Decompress the bit buffer
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+
+            // Assert that the decompressed data matches the original
+            assert_eq!(&values[..n], decompressor.col_a());
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_i128_nanosecond_counter() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub ns: i128,
+                pub val: i32,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+
+        // Nanoseconds since the epoch beyond the range of i64, with a little jitter
+        let start = i64::MAX as i128 * 1000;
+        let rows: Vec<TestRow> = (0..1000)
+            .map(|i: i128| TestRow {
+                ns: start + i * 1_000_000 + rng.gen_range(-100..100),
+                val: rng.gen_range(-1000..1000),
+            })
+            .collect();
+
+        // Initialize the compressor
+        let mut compressor = TestRowCompressorImpl::new(128);
+
+        // Compress rows
+        for row in &rows {
+            compressor.compress(*row);
+        }
+
+        // Finalize the compression
+        let bytes = compressor.finish();
+
+        // Jittered timestamps should need about a byte per row
+        assert!(bytes.len() < 1000 * 6);
+
+        // Decompress the bit buffer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+
+        // Assert that the decompressed data matches the original
+        let ns: Vec<i128> = rows.iter().map(|row| row.ns).collect();
+        let val: Vec<i32> = rows.iter().map(|row| row.val).collect();
+        assert_eq!(ns, decompressor.col_ns());
+        assert_eq!(val, decompressor.col_val());
+    }
+
+    #[test]
+    fn test_macro_compress_i128_all_delta_delta_widths() {
+        mod row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+            pub struct TestRow {
+                pub a: i128,
+            }
+
+            pub use compress::TestRowCompressorImpl;
+            pub use decompress::TestRowDecompressorImpl;
+        }
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+
+        // Random walks with steps of every bit-width, including the extremes
+        let mut values: Vec<i128> = Vec::new();
+        let mut value: i128 = 0;
+        for bits in 0..128 {
+            for _ in 0..10 {
+                let step = rng.gen::<i128>() >> bits;
+                value = value.wrapping_add(step);
+                values.push(value);
+            }
+        }
+        values.extend([i128::MAX, i128::MIN, i128::MAX, 0, i128::MIN, 0]);
+
+        // Initialize the compressor
+        let mut compressor = TestRowCompressorImpl::new(128);
+
+        // Compress rows
+        for value in &values {
+            compressor.compress(TestRow { a: *value });
+        }
+
+        // Finalize the compression
+        let bytes = compressor.finish();
+
+        // Decompress the bit buffer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+
+        // Assert that the decompressed data matches the original
+        assert_eq!(values, decompressor.col_a());
+    }
+}
+
+#[cfg(test)]
+mod test_header {
+
+    use super::*;
+    use tsz_compress::prelude::consts::schema;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header)]
+        pub struct TestRow {
+            pub ts: i64,
+            #[tsz(delta = "i16")]
+            pub a: u16,
+            pub b: i8,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod other_row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+        #[tsz(header)]
+        pub struct OtherRow {
+            pub ts: i64,
+            pub a: u16,
+            pub b: i8,
+        }
+
+        pub use compress::OtherRowCompressorImpl;
+        pub use decompress::OtherRowDecompressorImpl;
+    }
+
+    mod headerless_row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, CompressV2, DecompressV2)]
+        pub struct HeaderlessRow {
+            pub ts: i64,
+            #[tsz(delta = "i16")]
+            pub a: u16,
+            pub b: i8,
+        }
+
+        pub use compress::HeaderlessRowCompressorImpl;
+        pub use decompress::HeaderlessRowDecompressorImpl;
+    }
+
+    fn test_rows() -> Vec<row::TestRow> {
+        (0..100)
+            .map(|i| row::TestRow {
+                ts: 1_700_000_000_000 + i * 1000 + i * i,
+                a: 1000 + (i as u16 % 3),
+                b: (i % 5) as i8,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_header_round_trip() {
+        use row::*;
+
+        for n in [0, 1, 2, 100] {
+            // Compress rows
+            let rows = &test_rows()[..n];
+            let mut compressor = TestRowCompressorImpl::new(128);
+            for row in rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            // The header describes the 3 columns ahead of the row count
+            assert_eq!(bytes[0], (schema::MAGIC << 4) | schema::VERSION);
+            assert_eq!(bytes[1], 3);
+            assert_eq!(
+                bytes[2] & !schema::DELTA_DELTA,
+                (schema::I64 << 4) | schema::I64
+            );
+            assert_eq!(
+                bytes[3] & !schema::DELTA_DELTA,
+                (schema::U16 << 4) | schema::I16
+            );
+            assert_eq!(
+                bytes[4] & !schema::DELTA_DELTA,
+                (schema::I8 << 4) | schema::I16
+            );
+
+            // Decompress the bit buffer
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_header_encoding() {
+        use row::*;
+
+        // Accelerating timestamps are delta-delta encoded, the noisy columns are not
+        let mut compressor = TestRowCompressorImpl::new(128);
+        for row in test_rows() {
+            compressor.compress(row);
+        }
+        let bytes = compressor.finish();
+        assert_eq!(bytes[2] & schema::DELTA_DELTA, schema::DELTA_DELTA);
+        assert_eq!(bytes[3] & schema::DELTA_DELTA, 0);
+    }
+
+    #[test]
+    fn test_macro_compress_header_mismatch() {
+        use headerless_row::*;
+        use other_row::*;
+        use row::*;
+
+        let mut compressor = TestRowCompressorImpl::new(128);
+        for row in test_rows() {
+            compressor.compress(row);
+        }
+        let bytes = compressor.finish();
+
+        // Same column types with a different delta type
+        let mut decompressor = OtherRowDecompressorImpl::new();
+        assert!(matches!(
+            decompressor.decompress(&bytes),
+            Err(CodingError::HeaderMismatch)
+        ));
+
+        // Bytes without a header
+        let mut compressor = HeaderlessRowCompressorImpl::new(128);
+        for row in test_rows() {
+            compressor.compress(HeaderlessRow {
+                ts: row.ts,
+                a: row.a,
+                b: row.b,
+            });
+        }
+        let headerless_bytes = compressor.finish();
+        let mut decompressor = TestRowDecompressorImpl::new();
+        assert!(matches!(
+            decompressor.decompress(&headerless_bytes),
+            Err(CodingError::HeaderMismatch)
+        ));
+
+        // Truncated header
+        assert!(matches!(
+            decompressor.decompress(&bytes[..3]),
+            Err(CodingError::HeaderMismatch)
+        ));
+
+        // The unmodified bytes still decode
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(decompressor.rows(), test_rows());
+    }
+}
+
+#[cfg(test)]
+mod test_mtu {
+
+    use super::*;
+    use crate::common::random_rows;
+    use rand::Rng;
+
+    mod row {
+        crate::common::test_row!();
+    }
+
+    mod header_row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header)]
+        pub struct HeaderRow {
+            pub ts: i64,
+            pub a: i8,
+        }
+
+        pub use compress::HeaderRowCompressorImpl;
+        pub use decompress::HeaderRowDecompressorImpl;
+    }
+
+    #[test]
+    fn test_macro_compress_mtu_packets() {
+        use row::*;
+
+        for mtu in [32, 64, 251, 1024] {
+            let rows = random_rows(5000);
+
+            // Initialize the compressor with a byte budget
+            let mut compressor = TestRowCompressorImpl::new(128);
+            compressor.set_mtu(Some(mtu));
+            assert_eq!(compressor.mtu(), Some(mtu));
+
+            // Compress rows, collecting the emitted packets
+            let mut packets = Vec::new();
+            for row in &rows {
+                let mut packet = Vec::new();
+                if compressor.compress_into(*row, &mut packet) {
+                    packets.push(packet);
+                }
+            }
+            packets.push(compressor.finish());
+
+            // Every packet is within the budget and decodes on its own
+            let mut decompressed = Vec::new();
+            for packet in &packets {
+                assert!(packet.len() <= mtu, "{} > {}", packet.len(), mtu);
+                let mut decompressor = TestRowDecompressorImpl::new();
+                decompressor.decompress(packet).unwrap();
+                assert!(!decompressor.rows().is_empty());
+                decompressed.extend(decompressor.rows());
+            }
+            assert_eq!(decompressed, rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_mtu_packets_are_full() {
+        use header_row::*;
+
+        // Constant rows emit a nibble or two per row, so every packet should fill to within a few bytes
+        let mtu = 251;
+        let mut compressor = HeaderRowCompressorImpl::new(128);
+        compressor.set_mtu(Some(mtu));
+        let mut packets = Vec::new();
+        let mut rows = Vec::new();
+        for i in 0..10_000 {
+            let row = HeaderRow {
+                ts: i * 1000,
+                a: (i % 2) as i8,
+            };
+            rows.push(row);
+            let mut packet = Vec::new();
+            if compressor.compress_into(row, &mut packet) {
+                packets.push(packet);
+            }
+        }
+        assert!(packets.len() > 1);
+        for packet in &packets {
+            assert!(packet.len() <= mtu);
+            assert!(packet.len() >= mtu - 8, "{} < {}", packet.len(), mtu - 8);
+        }
+        packets.push(compressor.finish());
+
+        let mut decompressor = HeaderRowDecompressorImpl::new();
+        for packet in &packets {
+            decompressor.decompress(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_mtu_tiny() {
+        use row::*;
+
+        // A row that can never fit is emitted alone rather than stalling
+        let rows = random_rows(10);
+        let mut compressor = TestRowCompressorImpl::new(128);
+        compressor.set_mtu(Some(1));
+        let mut bytes = Vec::new();
+        let mut emitted = 0;
+        for row in &rows {
+            if compressor.compress_into(*row, &mut bytes) {
+                emitted += 1;
+            }
+        }
+        assert_eq!(emitted, rows.len() - 1);
+        assert_eq!(compressor.row_count(), 1);
+
+        // Without a budget, rows are only emitted on finish
+        compressor.set_mtu(None);
+        for row in &rows {
+            assert!(!compressor.compress_into(*row, &mut bytes));
+        }
+        assert_eq!(compressor.row_count(), rows.len() + 1);
+    }
+}
+
+#[cfg(test)]
+mod test_nullable {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub utc: Option<i64>,
+            pub a: Option<u16>,
+            pub b: i8,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod required_row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct RequiredRow {
+            pub ts: i64,
+            pub utc: i64,
+        }
+
+        pub use compress::RequiredRowCompressorImpl;
+        pub use decompress::RequiredRowDecompressorImpl;
+    }
+
+    mod nullable_row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct NullableRow {
+            pub ts: i64,
+            pub utc: Option<i64>,
+        }
+
+        pub use compress::NullableRowCompressorImpl;
+        pub use decompress::NullableRowDecompressorImpl;
+    }
+
+    mod all_nullable_row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct AllNullableRow {
+            pub a: Option<i32>,
+        }
+
+        pub use compress::AllNullableRowCompressorImpl;
+        pub use decompress::AllNullableRowDecompressorImpl;
+    }
+
+    fn random_rows(n: usize) -> Vec<row::TestRow> {
+        let mut rng = rand::thread_rng();
+        let mut connected = true;
+        (0..n as i64)
+            .map(|i| {
+                // The phone connects and disconnects in spans
+                if rng.gen_ratio(1, 100) {
+                    connected = !connected;
+                }
+                row::TestRow {
+                    ts: i * 20_000,
+                    utc: connected.then_some(1_700_000_000_000_000 + i * 20_000),
+                    a: rng.gen::<bool>().then(|| rng.gen()),
+                    b: rng.gen(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_nullable_random() {
+        use row::*;
+
+        for n in [0, 1, 2, 3, 4, 10, 100, 1000, 10_000] {
+            let rows = random_rows(n);
+
+            // Compress rows
+            let mut compressor = TestRowCompressorImpl::new(128);
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            // Decompress the bit buffer
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+            assert_eq!(
+                decompressor.col_utc(),
+                rows.iter().map(|row| row.utc).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_nullable_spans_are_cheap() {
+        use nullable_row::*;
+        use required_row::*;
+
+        let n = 10_000;
+
+        // Columns that are always present cost a few nibbles more than required columns
+        let mut required = RequiredRowCompressorImpl::new(128);
+        let mut nullable = NullableRowCompressorImpl::new(128);
+        for i in 0..n {
+            required.compress(RequiredRow { ts: i, utc: i * 3 });
+            nullable.compress(NullableRow {
+                ts: i,
+                utc: Some(i * 3),
+            });
+        }
+        let required_bytes = required.finish();
+        let nullable_bytes = nullable.finish();
+        assert!(nullable_bytes.len() <= required_bytes.len() + 4);
+
+        // Columns that are always absent cost a few nibbles
+        let mut nullable = NullableRowCompressorImpl::new(128);
+        let mut rows = Vec::new();
+        for i in 0..n {
+            let row = NullableRow { ts: i, utc: None };
+            rows.push(row);
+            nullable.compress(row);
+        }
+        let absent_bytes = nullable.finish();
+        assert!(absent_bytes.len() <= required_bytes.len() / 2 + 8);
+
+        let mut decompressor = NullableRowDecompressorImpl::new();
+        decompressor.decompress(&absent_bytes).unwrap();
+        assert_eq!(decompressor.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_all_nullable_absent() {
+        use all_nullable_row::*;
+
+        // Many more rows than bytes
+        let n = 1_000_000;
+        let mut compressor = AllNullableRowCompressorImpl::new(128);
+        for _ in 0..n {
+            compressor.compress(AllNullableRow { a: None });
+        }
+        compressor.compress(AllNullableRow { a: Some(-1) });
+        let bytes = compressor.finish();
+        assert!(bytes.len() < 16);
+
+        let mut decompressor = AllNullableRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(decompressor.col_a().len(), n + 1);
+        assert!(decompressor.col_a()[..n].iter().all(|a| a.is_none()));
+        assert_eq!(decompressor.col_a()[n], Some(-1));
+    }
+
+    #[test]
+    fn test_macro_compress_nullable_mtu() {
+        use row::*;
+
+        // Rolling back a row must also roll back the runs
+        let rows = random_rows(5000);
+        let mtu = 100;
+        let mut compressor = TestRowCompressorImpl::new(128);
+        compressor.set_mtu(Some(mtu));
+        let mut packets = Vec::new();
+        for row in &rows {
+            let mut packet = Vec::new();
+            if compressor.compress_into(*row, &mut packet) {
+                packets.push(packet);
+            }
+        }
+        packets.push(compressor.finish());
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        for packet in &packets {
+            assert!(packet.len() <= mtu);
+            decompressor.decompress(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), rows);
+    }
+}
+
+#[cfg(test)]
+mod test_repeated_deltas {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub disabled: i16,
+            pub saturated: u16,
+            pub a: i32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+    use row::*;
+
+    #[test]
+    fn test_macro_compress_constant_and_linear_columns() {
+        // Linear timestamps, a disabled channel, a saturated ADC, and a counter
+        let rows: Vec<TestRow> = (0..100_000)
+            .map(|i| TestRow {
+                ts: 1_700_000_000_000_000 + i * 20_000,
+                disabled: 0,
+                saturated: u16::MAX,
+                a: -(i as i32),
+            })
+            .collect();
+
+        // Compress rows
+        let mut compressor = TestRowCompressorImpl::new(128);
+        for row in &rows {
+            compressor.compress(*row);
+        }
+        let bytes = compressor.finish();
+
+        // Each column is the first two rows and a single run
+        assert!(bytes.len() < 80, "{}", bytes.len());
+
+        // Decompress the bit buffer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(decompressor.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_runs_random() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100 {
+            // Runs of every length around the queue size, interrupted by noise
+            let mut rows = Vec::new();
+            let mut row = TestRow {
+                ts: 0,
+                disabled: 0,
+                saturated: 0,
+                a: 0,
+            };
+            while rows.len() < 2000 {
+                let run = rng.gen_range(0..25);
+                let step = rng.gen_range(-1000..1000);
+                for _ in 0..run {
+                    row.ts += 1000;
+                    row.a = row.a.wrapping_add(step);
+                    rows.push(row);
+                }
+                row.ts += rng.gen_range(0..1 << 40);
+                row.disabled = rng.gen();
+                row.saturated = rng.gen();
+                row.a = rng.gen();
+                rows.push(row);
+            }
+
+            // Compress rows
+            let mut compressor = TestRowCompressorImpl::new(128);
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            // Decompress the bit buffer
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_runs_mtu() {
+        let mut rng = rand::thread_rng();
+
+        // Flat spans with the occasional burst of noise
+        let rows: Vec<TestRow> = (0..20_000)
+            .map(|i| TestRow {
+                ts: i * 1000,
+                disabled: if i % 500 < 20 { rng.gen() } else { 0 },
+                saturated: u16::MAX,
+                a: if i % 300 < 10 { rng.gen() } else { 1 },
+            })
+            .collect();
+
+        let mtu = 64;
+        let mut compressor = TestRowCompressorImpl::new(128);
+        compressor.set_mtu(Some(mtu));
+        let mut packets = Vec::new();
+        for row in &rows {
+            let mut packet = Vec::new();
+            if compressor.compress_into(*row, &mut packet) {
+                packets.push(packet);
+            }
+        }
+        packets.push(compressor.finish());
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        for packet in &packets {
+            assert!(packet.len() <= mtu);
+            decompressor.decompress(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), rows);
+    }
+}
+
+#[cfg(test)]
+mod test_coding_error {
+
+    use super::*;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i16,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+    use row::*;
+
+    fn one_row_bytes() -> Vec<u8> {
+        // The values are too wide for a varint, so they are written at full width
+        let mut compressor = TestRowCompressorImpl::new(1);
+        compressor.compress(TestRow {
+            ts: i64::MIN,
+            a: i16::MIN,
+        });
+        compressor.finish()
+    }
+
+    #[test]
+    fn test_macro_compress_error_invalid_tag() {
+        // [row count], then [SOC][first row][16 nibbles of ts], then [SOC][first row][4 nibbles of a], then [SOC] padding
+        let mut bytes = one_row_bytes();
+        assert_eq!(bytes.len(), 13);
+        assert_eq!(bytes[10], 0x68);
+        bytes[10] = 0x18;
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        let error = decompressor.decompress(&bytes).unwrap_err();
+        let CodingError::Column(column_error) = &error else {
+            panic!("Expected a column error, got {:?}", error);
+        };
+        assert_eq!(column_error.index, 1);
+        assert_eq!(column_error.name, "a");
+        assert_eq!(column_error.nibble_offset, 21);
+        assert!(matches!(
+            *column_error.error,
+            CodingError::InvalidTag(0b0001)
+        ));
+        assert_eq!(
+            error.to_string(),
+            "column 1 (a) at nibble 21: invalid tag 0b0001"
+        );
+    }
+
+    #[test]
+    fn test_macro_compress_error_truncated() {
+        // Cut the packet in the middle of the first value of ts
+        let bytes = one_row_bytes();
+        let mut decompressor = TestRowDecompressorImpl::new();
+        let error = decompressor.decompress(&bytes[..8]).unwrap_err();
+        let CodingError::Column(column_error) = &error else {
+            panic!("Expected a column error, got {:?}", error);
+        };
+        assert_eq!(column_error.index, 0);
+        assert_eq!(column_error.name, "ts");
+        assert_eq!(column_error.nibble_offset, 16);
+        assert!(matches!(*column_error.error, CodingError::NotEnoughBits));
+    }
+}
+
+#[cfg(test)]
+mod test_stream {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: Option<i16>,
+            pub b: u8,
+            pub c: i128,
+            pub d: Option<i32>,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+    use row::*;
+
+    mod header_row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header)]
+        pub struct HeaderRow {
+            pub ts: i64,
+            pub a: Option<i16>,
+        }
+
+        pub use compress::HeaderRowCompressorImpl;
+        pub use decompress::HeaderRowDecompressorImpl;
+    }
+    use header_row::*;
+
+    fn random_rows(rng: &mut impl Rng, n: usize) -> Vec<TestRow> {
+        let mut ts = rng.gen_range(0..1 << 40);
+        let mut b = 0u8;
+        (0..n)
+            .map(|i| {
+                ts += rng.gen_range(990..1010);
+                b = b.wrapping_add(1);
+                TestRow {
+                    ts,
+                    a: rng.gen_bool(0.7).then(|| rng.gen_range(-300..300)),
+                    b: if i % 40 < 20 { b } else { rng.gen() },
+                    c: ts as i128 * 1_000_000,
+                    d: (i % 30 < 25).then_some(7),
+                }
+            })
+            .collect()
+    }
+
+    fn compress_packets(rng: &mut impl Rng, rows: &[TestRow]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for packet in rows.chunks(rng.gen_range(1..100)) {
+            let mut compressor = TestRowCompressorImpl::new(packet.len());
+            for row in packet {
+                compressor.compress(*row);
+            }
+            compressor.finish_into(&mut bytes);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_macro_compress_stream_random_chunks() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..20 {
+            let n = rng.gen_range(0..500);
+            let rows = random_rows(&mut rng, n);
+            let bytes = compress_packets(&mut rng, &rows);
+
+            // Feed the packets back in chunks that ignore packet and word boundaries
+            let mut decompressor = TestRowDecompressorImpl::new();
+            let mut remaining = &bytes[..];
+            let mut decoded = 0;
+            while !remaining.is_empty() {
+                let len = rng.gen_range(1..=remaining.len().min(24));
+                let progress = decompressor.decompress_chunk(&remaining[..len]).unwrap();
+                remaining = &remaining[len..];
+
+                // Rows are only ever appended
+                let available = decompressor.rows();
+                assert!(available.len() >= decoded);
+                assert_eq!(available, rows[..available.len()]);
+                decoded = available.len();
+
+                if remaining.is_empty() {
+                    assert_eq!(progress, DecodeProgress::Complete);
+                }
+            }
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_stream_rows_before_packet_ends() {
+        // A single column packet makes rows available word by word
+        mod ts_row {
+            use tsz_compress::prelude::*;
+            #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+            pub struct TsRow {
+                pub ts: i64,
+            }
+
+            pub use compress::TsRowCompressorImpl;
+            pub use decompress::TsRowDecompressorImpl;
+        }
+        use ts_row::*;
+
+        let mut rng = rand::thread_rng();
+        let rows: Vec<TsRow> = (0..1000)
+            .map(|i| TsRow {
+                ts: i * 1000 + rng.gen_range(0..100),
+            })
+            .collect();
+        let mut compressor = TsRowCompressorImpl::new(rows.len());
+        for row in &rows {
+            compressor.compress(*row);
+        }
+        let bytes = compressor.finish();
+
+        let mut decompressor = TsRowDecompressorImpl::new();
+        let half = bytes.len() / 2;
+        assert_eq!(
+            decompressor.decompress_chunk(&bytes[..half]).unwrap(),
+            DecodeProgress::NeedMoreData
+        );
+        let available = decompressor.rows();
+        assert!(available.len() > 400 && available.len() < 600);
+        assert_eq!(available, rows[..available.len()]);
+
+        assert_eq!(
+            decompressor.decompress_chunk(&bytes[half..]).unwrap(),
+            DecodeProgress::Complete
+        );
+        assert_eq!(decompressor.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_stream_header_bytewise() {
+        let rows: Vec<HeaderRow> = (0..300)
+            .map(|i| HeaderRow {
+                ts: 1_700_000_000_000 + i * 1000,
+                a: (i % 7 != 0).then_some(i as i16),
+            })
+            .collect();
+
+        let mut bytes = Vec::new();
+        for packet in rows.chunks(64) {
+            let mut compressor = HeaderRowCompressorImpl::new(packet.len());
+            for row in packet {
+                compressor.compress(*row);
+            }
+            compressor.finish_into(&mut bytes);
+        }
+
+        let mut decompressor = HeaderRowDecompressorImpl::new();
+        for byte in &bytes {
+            decompressor.decompress_chunk(&[*byte]).unwrap();
+        }
+        assert_eq!(decompressor.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_stream_recovers_after_error() {
+        let mut rng = rand::thread_rng();
+        let rows = random_rows(&mut rng, 100);
+        let (first, second) = rows.split_at(50);
+
+        let compress = |rows: &[TestRow]| {
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in rows {
+                compressor.compress(*row);
+            }
+            compressor.finish()
+        };
+        let first_bytes = compress(first);
+        let second_bytes = compress(second);
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        assert_eq!(
+            decompressor.decompress_chunk(&first_bytes).unwrap(),
+            DecodeProgress::Complete
+        );
+
+        // A packet with a broken first column tag is discarded
+        let mut broken = second_bytes.clone();
+        broken[1] = 0x00;
+        assert!(matches!(
+            decompressor.decompress_chunk(&broken),
+            Err(CodingError::InvalidInitialColumnTag)
+        ));
+        assert_eq!(decompressor.rows(), first);
+
+        // The next chunk starts a new packet
+        assert_eq!(
+            decompressor.decompress_chunk(&second_bytes).unwrap(),
+            DecodeProgress::Complete
+        );
+        assert_eq!(decompressor.rows(), rows);
+    }
+}
+
+#[cfg(test)]
+mod test_array_compressor {
+
+    use super::*;
+    use crate::common::random_rows;
+    use rand::Rng;
+
+    mod row {
+        crate::common::test_row!(#[tsz(header)]);
+    }
+
+    #[test]
+    fn test_macro_compress_array_matches_vec() {
+        use row::*;
+
+        for n in [0, 1, 2, 3, 10, 100] {
+            let rows = random_rows(n);
+
+            // The same rows compress to the same bytes with either storage
+            let mut compressor = TestRowCompressorImpl::new(n);
+            let mut array_compressor = TestRowArrayCompressorImpl::<512>::new(n);
+            for row in &rows {
+                compressor.compress(*row);
+                array_compressor.try_compress(*row).unwrap();
+            }
+            let bytes = compressor.finish();
+
+            let mut slice = [0u8; 1024];
+            let len = array_compressor.finish_into_slice(&mut slice).unwrap();
+            assert_eq!(&slice[..len], &bytes[..]);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&slice[..len]).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_array_slice_too_small() {
+        use row::*;
+
+        let rows = random_rows(50);
+        let mut compressor = TestRowArrayCompressorImpl::<256>::new(0);
+        for row in &rows {
+            compressor.try_compress(*row).unwrap();
+        }
+
+        // Shrink the slice until the packet does not fit, keeping the rows
+        let mut slice = [0u8; 512];
+        let len = {
+            let mut probe = TestRowCompressorImpl::new(0);
+            rows.iter().for_each(|row| probe.compress(*row));
+            probe.finish().len()
+        };
+        assert!(matches!(
+            compressor.finish_into_slice(&mut slice[..len - 1]),
+            Err(CodingError::BufferFull)
+        ));
+        assert_eq!(compressor.row_count(), rows.len());
+
+        // The same rows finish once there is room
+        assert_eq!(compressor.finish_into_slice(&mut slice).unwrap(), len);
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&slice[..len]).unwrap();
+        assert_eq!(decompressor.rows(), rows);
+        assert_eq!(compressor.row_count(), 0);
+    }
+
+    #[test]
+    fn test_macro_compress_array_full() {
+        use row::*;
+
+        let rows = random_rows(10_000);
+        let mut compressor = TestRowArrayCompressorImpl::<32>::new(0);
+
+        // Fill packets until the buffers refuse a row, then carry the row to the next packet
+        let mut decompressor = TestRowDecompressorImpl::new();
+        let mut packets = 0;
+        let mut slice = [0u8; 512];
+        for row in &rows {
+            if compressor.try_compress(*row).is_err() {
+                let len = compressor.finish_into_slice(&mut slice).unwrap();
+                decompressor.decompress(&slice[..len]).unwrap();
+                compressor.try_compress(*row).unwrap();
+                packets += 1;
+            }
+        }
+        let len = compressor.finish_into_slice(&mut slice).unwrap();
+        decompressor.decompress(&slice[..len]).unwrap();
+
+        assert!(packets > 10);
+        assert_eq!(decompressor.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_array_overflow_drops_packet() {
+        use row::*;
+
+        // Rows compressed without checking overflow the buffers
+        let rows = random_rows(1000);
+        let mut compressor = TestRowArrayCompressorImpl::<16>::new(0);
+        for row in &rows {
+            compressor.compress(*row);
+        }
+
+        let mut slice = [0u8; 4096];
+        assert!(matches!(
+            compressor.finish_into_slice(&mut slice),
+            Err(CodingError::BufferFull)
+        ));
+
+        // The compressor starts a fresh packet
+        assert_eq!(compressor.row_count(), 0);
+        compressor.try_compress(rows[0]).unwrap();
+        let len = compressor.finish_into_slice(&mut slice).unwrap();
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&slice[..len]).unwrap();
+        assert_eq!(decompressor.rows(), &rows[..1]);
+    }
+}
+
+#[cfg(test)]
+mod test_len_bytes {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        crate::common::test_row!(#[tsz(header)]);
+    }
+
+    ///
+    /// Bursts of large changes between flat stretches, so the bit rate is a poor estimate.
+    ///
+    fn bursty_rows(n: usize) -> Vec<row::TestRow> {
+        let mut rng = rand::thread_rng();
+        let mut row = row::TestRow {
+            ts: 1_700_000_000_000,
+            a: 0,
+            b: Some(0),
+            c: 0,
+        };
+        let mut burst = 0;
+        (0..n)
+            .map(|_| {
+                row.ts += 1000;
+                if burst == 0 && rng.gen_ratio(1, 30) {
+                    burst = rng.gen_range(1..20);
+                }
+                if burst > 0 {
+                    burst -= 1;
+                    row.a = rng.gen();
+                    row.b = rng.gen::<bool>().then(|| rng.gen());
+                    row.c = rng.gen::<i64>() as i128;
+                }
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_len_bytes_if_finished_is_exact() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let rows = bursty_rows(rng.gen_range(0..300));
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let len = compressor.len_bytes_if_finished();
+            assert_eq!(compressor.finish().len(), len);
+            assert_eq!(
+                compressor.len_bytes_if_finished(),
+                TestRowCompressorImpl::new(0).finish().len()
+            );
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_would_fit_budget() {
+        use row::*;
+
+        for budget in [64, 100, 251] {
+            let rows = bursty_rows(3000);
+
+            // Finish before any row that would overshoot the budget
+            let mut compressor = TestRowCompressorImpl::new(64);
+            let mut packets = Vec::new();
+            for row in &rows {
+                if compressor.row_count() > 0 && !compressor.would_fit(row, budget) {
+                    packets.push(compressor.finish());
+                }
+                compressor.compress(*row);
+            }
+            packets.push(compressor.finish());
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for packet in &packets {
+                assert!(packet.len() <= budget, "{} > {}", packet.len(), budget);
+                decompressor.decompress(packet).unwrap();
+            }
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_would_fit_keeps_state() {
+        use row::*;
+
+        let rows = bursty_rows(500);
+        let mut compressor = TestRowCompressorImpl::new(0);
+        let mut probed = TestRowCompressorImpl::new(0);
+        for row in &rows {
+            compressor.compress(*row);
+
+            // Probing with every row changes nothing
+            let len = probed.len_bytes_if_finished();
+            probed.would_fit(row, 0);
+            probed.would_fit(&rows[0], usize::MAX);
+            assert_eq!(probed.len_bytes_if_finished(), len);
+            probed.compress(*row);
+        }
+        assert_eq!(compressor.finish(), probed.finish());
+    }
+
+    #[test]
+    fn test_macro_compress_would_fit_is_exact() {
+        use row::*;
+
+        let rows = bursty_rows(2000);
+        let mut compressor = TestRowCompressorImpl::new(0);
+        let mut bounded = compress::TestRowArrayCompressorImpl::<96>::new(0);
+        for row in &rows {
+            // The budget is met exactly by the length after the row
+            let checkpoint = compressor.checkpoint();
+            compressor.compress(*row);
+            let len = compressor.len_bytes_if_finished();
+            compressor.rollback(checkpoint);
+            assert!(compressor.would_fit(row, len));
+            assert!(!compressor.would_fit(row, len - 1));
+            compressor.compress(*row);
+
+            // And a fixed-capacity compressor fits the rows it accepts
+            let fits = bounded.would_fit(row, usize::MAX);
+            assert_eq!(bounded.try_compress(*row).is_ok(), fits);
+            if !fits {
+                bounded.finish_into_slice(&mut [0; 512]).unwrap();
+                bounded.compress(*row);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_snapshot {
+
+    use super::*;
+    use crate::common::random_rows;
+    use rand::Rng;
+
+    mod row {
+        crate::common::test_row!(#[tsz(header)]);
+    }
+
+    mod other {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct OtherRow {
+            pub ts: i64,
+            pub a: i16,
+            pub b: Option<u32>,
+            pub c: i64,
+        }
+
+        pub use compress::OtherRowCompressorImpl;
+    }
+
+    #[test]
+    fn test_macro_compress_restore_continues_packet() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..200));
+            let split = rng.gen_range(0..=rows.len());
+
+            // Compress without interruption
+            let mut compressor = TestRowCompressorImpl::new(0);
+            compressor.set_mtu(Some(64));
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let expected = compressor.finish();
+
+            // Snapshot part way through, then continue in a new compressor
+            let mut compressor = TestRowCompressorImpl::new(0);
+            compressor.set_mtu(Some(64));
+            for row in &rows[..split] {
+                compressor.compress(*row);
+            }
+            let snapshot = compressor.snapshot();
+            assert_eq!(snapshot.len(), compressor.snapshot_len());
+            drop(compressor);
+
+            let mut compressor = TestRowCompressorImpl::new(0);
+            compressor.restore(&snapshot).unwrap();
+            assert_eq!(compressor.mtu(), Some(64));
+            assert_eq!(compressor.row_count(), split);
+            for row in &rows[split..] {
+                compressor.compress(*row);
+            }
+            assert_eq!(compressor.finish(), expected);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&expected).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_restore_into_array_compressor() {
+        use row::*;
+
+        let rows = random_rows(100);
+        let mut compressor = TestRowArrayCompressorImpl::<1024>::new(0);
+        for row in &rows[..60] {
+            compressor.try_compress(*row).unwrap();
+        }
+        let mut snapshot = [0u8; 4096];
+        let len = compressor.snapshot_into(&mut snapshot).unwrap();
+        assert!(matches!(
+            compressor.snapshot_into(&mut snapshot[..len - 1]),
+            Err(CodingError::BufferFull)
+        ));
+
+        let mut restored = TestRowArrayCompressorImpl::<1024>::new(0);
+        restored.restore(&snapshot[..len]).unwrap();
+        for row in &rows[60..] {
+            compressor.try_compress(*row).unwrap();
+            restored.try_compress(*row).unwrap();
+        }
+        let mut expected = [0u8; 4096];
+        let mut actual = [0u8; 4096];
+        let expected_len = compressor.finish_into_slice(&mut expected).unwrap();
+        let actual_len = restored.finish_into_slice(&mut actual).unwrap();
+        assert_eq!(actual[..actual_len], expected[..expected_len]);
+
+        // A smaller array cannot hold the buffers of the snapshot
+        let mut small = TestRowArrayCompressorImpl::<8>::new(0);
+        assert!(matches!(
+            small.restore(&snapshot[..len]),
+            Err(CodingError::BufferFull)
+        ));
+        assert_eq!(small.row_count(), 0);
+    }
+
+    #[test]
+    fn test_macro_compress_restore_rejects_bad_snapshots() {
+        use row::*;
+
+        let rows = random_rows(50);
+        let mut compressor = TestRowCompressorImpl::new(0);
+        for row in &rows {
+            compressor.compress(*row);
+        }
+        let snapshot = compressor.snapshot();
+
+        // Every truncation and trailing byte is rejected, leaving the compressor empty
+        for len in 0..snapshot.len() {
+            let mut restored = TestRowCompressorImpl::new(0);
+            restored.compress(rows[0]);
+            assert!(restored.restore(&snapshot[..len]).is_err());
+            assert_eq!(restored.row_count(), 0);
+            assert_eq!(restored.finish(), TestRowCompressorImpl::new(0).finish());
+        }
+        let mut trailing = snapshot.clone();
+        trailing.push(0);
+        assert!(TestRowCompressorImpl::new(0).restore(&trailing).is_err());
+
+        // A snapshot of a compressor with other columns is rejected
+        let mut other = other::OtherRowCompressorImpl::new(0);
+        assert!(matches!(
+            other.restore(&snapshot),
+            Err(CodingError::HeaderMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_macro_compress_rollback_last_row() {
+        use row::*;
+
+        let rows: Vec<TestRow> = random_rows(300);
+        let mut compressor = TestRowCompressorImpl::new(0);
+        let mut expected = TestRowCompressorImpl::new(0);
+        for row in &rows {
+            // Compress a row that is then discarded
+            let checkpoint = compressor.checkpoint();
+            compressor.compress(TestRow {
+                ts: row.ts * 3,
+                a: i16::MIN,
+                b: None,
+                c: i128::MAX,
+            });
+            compressor.rollback(checkpoint);
+
+            compressor.compress(*row);
+            expected.compress(*row);
+        }
+        assert_eq!(compressor.row_count(), rows.len());
+        assert_eq!(compressor.finish(), expected.finish());
+    }
+}
+
+#[cfg(test)]
+mod test_continuation {
+
+    use super::*;
+    use crate::common::{as_rows, random_rows};
+    use rand::Rng;
+
+    mod row {
+        crate::common::test_row!(#[tsz(header, continuation)]);
+    }
+
+    mod keyframes {
+        crate::common::test_row!(#[tsz(header)]);
+    }
+
+    ///
+    /// Compresses the rows into packets of random sizes, some of them empty.
+    ///
+    fn compress_packets(rows: &[row::TestRow]) -> Vec<Vec<u8>> {
+        let mut rng = rand::thread_rng();
+        let mut compressor = row::TestRowCompressorImpl::new(0);
+        let mut packets = Vec::new();
+        for row in rows {
+            if rng.gen_ratio(1, 8) {
+                packets.push(compressor.finish());
+            }
+            compressor.compress(*row);
+        }
+        packets.push(compressor.finish());
+        packets
+    }
+
+    #[test]
+    fn test_macro_compress_continuation_packets() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..300));
+            let packets = compress_packets(&rows);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for packet in &packets {
+                decompressor.decompress(packet).unwrap();
+            }
+            assert_eq!(decompressor.rows(), rows);
+
+            // The same packets decode from a stream of chunks
+            let bytes = packets.concat();
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..20)) {
+                decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_continuation_is_smaller() {
+        let rows = random_rows(2000);
+        let mut compressor = row::TestRowCompressorImpl::new(0);
+        let mut keyframes = keyframes::TestRowCompressorImpl::new(0);
+        let keyframe_rows: Vec<keyframes::TestRow> = as_rows(&rows);
+        let (mut continued_len, mut keyframes_len) = (0, 0);
+        for (i, row) in rows.iter().enumerate() {
+            if i % 20 == 19 {
+                continued_len += compressor.finish().len();
+                keyframes_len += keyframes.finish().len();
+            }
+            compressor.compress(*row);
+            keyframes.compress(keyframe_rows[i]);
+        }
+        assert!(
+            continued_len < keyframes_len,
+            "{} vs {}",
+            continued_len,
+            keyframes_len
+        );
+    }
+
+    #[test]
+    fn test_macro_compress_continuation_missing_packet() {
+        use row::*;
+
+        let rows = random_rows(100);
+        let mut compressor = TestRowCompressorImpl::new(0);
+        let mut packets = Vec::new();
+        for chunk in rows.chunks(10) {
+            for row in chunk {
+                compressor.compress(*row);
+            }
+            if packets.len() == 5 {
+                compressor.request_keyframe();
+            }
+            assert_eq!(compressor.sequence() as usize, packets.len());
+            packets.push(compressor.finish());
+        }
+
+        // Without the second packet, the continuation packets after it are rejected until the keyframe
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&packets[0]).unwrap();
+        for packet in &packets[2..6] {
+            assert!(matches!(
+                decompressor.decompress(packet),
+                Err(CodingError::MissingKeyframe)
+            ));
+        }
+        for packet in &packets[6..] {
+            decompressor.decompress(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), [&rows[..10], &rows[60..]].concat());
+
+        // Streaming reports the same gap
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress_chunk(&packets[0]).unwrap();
+        assert!(matches!(
+            decompressor.decompress_chunk(&packets[2]),
+            Err(CodingError::MissingKeyframe)
+        ));
+        for packet in &packets[6..] {
+            decompressor.decompress_chunk(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), [&rows[..10], &rows[60..]].concat());
+    }
+
+    #[test]
+    fn test_macro_compress_continuation_after_dropped_packet() {
+        use row::*;
+
+        // A packet dropped on overflow is followed by a keyframe
+        let rows = random_rows(200);
+        let mut compressor = TestRowArrayCompressorImpl::<64>::new(0);
+        let mut decompressor = TestRowDecompressorImpl::new();
+        let mut expected = Vec::new();
+        let mut packet = [0u8; 1024];
+        let mut dropped = 0;
+        let mut rest = &rows[..];
+        for len in [5, 40].into_iter().cycle() {
+            // Short packets fit, and long packets overflow the buffers
+            let (chunk, tail) = rest.split_at(len.min(rest.len()));
+            rest = tail;
+            if chunk.is_empty() {
+                break;
+            }
+            for row in chunk {
+                compressor.compress(*row);
+            }
+            match compressor.finish_into_slice(&mut packet) {
+                Ok(len) => {
+                    decompressor.decompress(&packet[..len]).unwrap();
+                    expected.extend_from_slice(chunk);
+                }
+                Err(_) => dropped += 1,
+            }
+        }
+        assert!(dropped > 0);
+        assert!(!expected.is_empty());
+        assert_eq!(decompressor.rows(), expected);
+    }
+
+    #[test]
+    fn test_macro_compress_continuation_snapshot() {
+        use row::*;
+
+        let rows = random_rows(300);
+        let mut compressor = TestRowCompressorImpl::new(0);
+        let mut packets = Vec::new();
+        for chunk in rows.chunks(40) {
+            // Restore from a snapshot before every packet
+            let snapshot = compressor.snapshot();
+            compressor = TestRowCompressorImpl::new(0);
+            compressor.restore(&snapshot).unwrap();
+            for row in chunk {
+                compressor.compress(*row);
+            }
+            packets.push(compressor.finish());
+        }
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        for packet in &packets {
+            decompressor.decompress(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), rows);
+    }
+}
+
+#[cfg(test)]
+mod test_varint_rows {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i8,
+            pub b: u64,
+            pub c: Option<i32>,
+            pub d: i128,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod small {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i16,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    #[test]
+    fn test_macro_compress_varint_first_rows() {
+        use small::*;
+
+        // [row count], then [SOC][varint first row][1 nibble of ts], then [SOC][varint first row][1 nibble of a], then [SOC] padding
+        let mut compressor = TestRowCompressorImpl::new(1);
+        compressor.compress(TestRow { ts: 1, a: 2 });
+        let bytes = compressor.finish();
+        assert_eq!(bytes, [0x19, 0x22, 0x92, 0x49]);
+
+        // The second row is also a varint delta, where a 128-bit delta would be written for ts
+        // ts is 11 nibbles rather than 51, and a is 5 nibbles rather than 15, padded to 9 bytes with the row count
+        let mut compressor = TestRowCompressorImpl::new(2);
+        compressor.compress(TestRow { ts: 1000, a: 0 });
+        compressor.compress(TestRow { ts: 2000, a: -1 });
+        let bytes = compressor.finish();
+        assert_eq!(bytes.len(), 9);
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(decompressor.col_ts(), [1000, 2000]);
+        assert_eq!(decompressor.col_a(), [0, -1]);
+    }
+
+    #[test]
+    fn test_macro_compress_varint_and_full_rows() {
+        use row::*;
+
+        // Mix values that are narrow enough for a varint with values that are not
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let n = rng.gen_range(0..5);
+            let bits = rng.gen_range(0..64);
+            let rows = (0..n)
+                .map(|_| TestRow {
+                    ts: rng.gen::<i64>() >> bits,
+                    a: rng.gen::<i8>() >> (bits / 8),
+                    b: rng.gen::<u64>() >> bits,
+                    c: rng.gen::<bool>().then(|| rng.gen::<i32>() >> (bits / 2)),
+                    d: (rng.gen::<i128>() >> (bits * 2)),
+                })
+                .collect::<Vec<_>>();
+
+            let mut compressor = TestRowCompressorImpl::new(n);
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..8)) {
+                decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_varint_rows_overflow() {
+        use small::*;
+
+        // A varint longer than 128 bits is rejected
+        let mut bytes = vec![0x19, 0x2F];
+        bytes.extend([0xFF; 21]);
+        bytes.extend([0xF0, 0x99]);
+        let mut decompressor = TestRowDecompressorImpl::new();
+        let error = decompressor.decompress(&bytes).unwrap_err();
+        let CodingError::Column(column_error) = &error else {
+            panic!("Expected a column error, got {:?}", error);
+        };
+        assert!(matches!(*column_error.error, CodingError::InvalidBits));
+    }
+}
+
+#[cfg(test)]
+mod test_delta_escape {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            #[tsz(delta = "i16")]
+            pub ts: i64,
+            #[tsz(delta = "i8")]
+            pub a: i32,
+            #[tsz(delta = "i8")]
+            pub b: Option<u16>,
+            #[tsz(delta = "i32")]
+            pub c: u64,
+            #[tsz(delta = "i64")]
+            pub d: i128,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod continued {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(continuation)]
+        pub struct TestRow {
+            #[tsz(delta = "i16")]
+            pub ts: i64,
+            #[tsz(delta = "i8")]
+            pub a: i32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    ///
+    /// Rows with small deltas, and the occasional glitch far too wide for the chosen bit-width.
+    ///
+    fn random_rows(n: usize) -> Vec<row::TestRow> {
+        let mut rng = rand::thread_rng();
+        let mut ts = 1_700_000_000_000_000i64;
+        let mut a = 0i32;
+        let mut b = 1000u16;
+        let mut c = u64::MAX / 2;
+        let mut d = 0i128;
+        (0..n)
+            .map(|_| {
+                let glitch = rng.gen_ratio(1, 20);
+                ts = match glitch {
+                    true => ts.wrapping_add(rng.gen::<i64>() >> rng.gen_range(0..64)),
+                    false => ts + rng.gen_range(19_990..20_010),
+                };
+                a = match glitch {
+                    true => rng.gen(),
+                    false => a.wrapping_add(rng.gen_range(-100..100)),
+                };
+                b = match glitch {
+                    true => rng.gen(),
+                    false => b.wrapping_add(rng.gen_range(0..100)),
+                };
+                c = match glitch {
+                    true => rng.gen(),
+                    false => c.wrapping_add(rng.gen_range(0..1 << 20)),
+                };
+                d = match glitch {
+                    true => rng.gen(),
+                    false => d.wrapping_sub(rng.gen_range(0..1 << 40)),
+                };
+                row::TestRow {
+                    ts,
+                    a,
+                    b: rng.gen_ratio(3, 4).then_some(b),
+                    c,
+                    d,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_delta_escape_timestamp_jump() {
+        use row::*;
+
+        // A 50Hz timestamp that resyncs forward by a day, and back again
+        let mut rows = (0..200)
+            .map(|i| TestRow {
+                ts: 1_700_000_000_000_000 + i * 20_000,
+                a: i as i32,
+                b: Some(i as u16),
+                c: i as u64,
+                d: i as i128,
+            })
+            .collect::<Vec<_>>();
+        for row in rows.iter_mut().skip(100) {
+            row.ts += 86_400_000_000;
+        }
+        for row in rows.iter_mut().skip(150) {
+            row.ts -= 86_400_000_000;
+        }
+
+        let mut compressor = TestRowCompressorImpl::new(rows.len());
+        for row in &rows {
+            compressor.compress(*row);
+        }
+        let bytes = compressor.finish();
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(decompressor.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_delta_escape_second_row() {
+        use row::*;
+
+        // The first delta does not fit the chosen bit-width, the rest do
+        for n in 2..30 {
+            let rows = (0..n)
+                .map(|i| TestRow {
+                    ts: if i == 0 { 0 } else { i64::MAX - i },
+                    a: if i == 0 {
+                        i32::MIN
+                    } else {
+                        i32::MAX - i as i32
+                    },
+                    b: Some(if i == 0 { 0 } else { u16::MAX - i as u16 }),
+                    c: if i == 0 { 0 } else { u64::MAX - i as u64 },
+                    d: if i == 0 {
+                        i128::MIN
+                    } else {
+                        i128::MAX - i as i128
+                    },
+                })
+                .collect::<Vec<_>>();
+
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_delta_escape_random() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let rows = random_rows(rng.gen_range(0..400));
+
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..20)) {
+                decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_delta_escape_continuation() {
+        use continued::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..300))
+                .into_iter()
+                .map(|row| TestRow {
+                    ts: row.ts,
+                    a: row.a,
+                })
+                .collect::<Vec<_>>();
+
+            // Packets of random sizes, each escaped delta continuing into the next packet
+            let mut compressor = TestRowCompressorImpl::new(0);
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for row in &rows {
+                if rng.gen_ratio(1, 8) {
+                    decompressor.decompress(&compressor.finish()).unwrap();
+                }
+                compressor.compress(*row);
+            }
+            decompressor.decompress(&compressor.finish()).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_wide_deltas {
+
+    use super::*;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    ///
+    /// Compresses the rows and checks that they decompress.
+    ///
+    fn compress(rows: &[row::TestRow]) -> Vec<u8> {
+        let mut compressor = row::TestRowCompressorImpl::new(rows.len());
+        for row in rows {
+            compressor.compress(*row);
+        }
+        let bytes = compressor.finish();
+
+        let mut decompressor = row::TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(decompressor.rows(), rows);
+        bytes
+    }
+
+    #[test]
+    fn test_macro_compress_i64_wide_deltas_bytes() {
+        use row::*;
+
+        // The same bytes must be written on 32-bit targets as on 64-bit hosts
+
+        // Delta-deltas wider than 32 bits
+        let rows = [0, 1, 1 + (1 << 40), 2 + (1 << 41), 2, 3 - (1 << 50)]
+            .map(|ts| TestRow { ts })
+            .to_vec();
+        assert_eq!(
+            compress(&rows),
+            [
+                0x69, 0x30, 0x22, 0x10, 0x00, 0x00, 0x1f, 0xff, 0xff, 0xff, 0xff, 0xe2, 0x21, 0x00,
+                0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x01, 0x10, 0x00, 0x7f, 0xbf, 0xff, 0xff, 0xff,
+                0xff, 0xd9,
+            ]
+        );
+
+        // Deltas of 3 bits, but for a jump forward and back wider than 32 bits
+        let rows = (0..30i64)
+            .map(|i| TestRow {
+                ts: 2 * i + (i & 1) + if (15..20).contains(&i) { 1 << 40 } else { 0 },
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            compress(&rows),
+            [
+                0xb6, 0x92, 0x02, 0x6f, 0x16, 0x59, 0x65, 0x96, 0xa0, 0x02, 0x01, 0x80, 0x2d, 0x00,
+                0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x06, 0xc0, 0x20, 0x60, 0x20, 0x6d, 0x00, 0x00,
+                0x01, 0xff, 0xff, 0xff, 0xff, 0xfd, 0xe0, 0x60, 0x86, 0x08, 0x6c, 0x02, 0x06, 0x02,
+                0x06,
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod test_wrapping {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            #[tsz(wrapping)]
+            pub counter: u16,
+            #[tsz(wrapping = 24)]
+            pub sample: u32,
+            #[tsz(wrapping)]
+            pub a: i8,
+            #[tsz(wrapping = 12)]
+            pub b: Option<i16>,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod counter {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            #[tsz(wrapping)]
+            pub counter: u16,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod sample {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            #[tsz(wrapping = 24)]
+            pub sample: u32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod unwrapped {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub counter: u16,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+    }
+
+    ///
+    /// Counts from `start` in steps of 1 to 3, wrapping at 16 bits.
+    ///
+    fn counter_rows(start: u16, n: usize) -> Vec<counter::TestRow> {
+        (0..n)
+            .scan(start, |counter, i| {
+                let row = counter::TestRow { counter: *counter };
+                *counter = counter.wrapping_add(1 + (i % 3) as u16);
+                Some(row)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_wrapping_rollover() {
+        use counter::*;
+
+        // The counter rolls over several times, yet costs the same as a counter that does not
+        let rolled = counter_rows(65_000, 100_000);
+        let unrolled = counter_rows(30_000, 10_000);
+        let mut compressor = TestRowCompressorImpl::new(rolled.len());
+        for row in &rolled {
+            compressor.compress(*row);
+        }
+        let rolled_bytes = compressor.finish();
+        let mut compressor = TestRowCompressorImpl::new(unrolled.len());
+        for row in &unrolled {
+            compressor.compress(*row);
+        }
+        let unrolled_bytes = compressor.finish();
+        let mut compressor = TestRowCompressorImpl::new(unrolled.len());
+        for row in &rolled[..unrolled.len()] {
+            compressor.compress(*row);
+        }
+        assert_eq!(compressor.finish().len(), unrolled_bytes.len());
+
+        // Without wrapping, each rollover is a wide delta
+        let mut compressor = unwrapped::TestRowCompressorImpl::new(rolled.len());
+        for row in &rolled {
+            compressor.compress(unwrapped::TestRow {
+                counter: row.counter,
+            });
+        }
+        assert!(rolled_bytes.len() < compressor.finish().len());
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&rolled_bytes).unwrap();
+        assert_eq!(decompressor.rows(), rolled);
+    }
+
+    #[test]
+    fn test_macro_compress_wrapping_declared_rollover() {
+        use sample::*;
+
+        // A 24-bit counter rolling over costs the same as one that does not
+        let compress = |start: u32| {
+            let rows = (0..1000)
+                .map(|i| TestRow {
+                    sample: (start + 7 * i) % (1 << 24),
+                })
+                .collect::<Vec<_>>();
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+            bytes.len()
+        };
+        assert_eq!(compress((1 << 24) - 3500), compress((1 << 23) - 3500));
+    }
+
+    #[test]
+    fn test_macro_compress_wrapping_declared_bits() {
+        use row::*;
+
+        // Counters of their declared bit-width roll over, and decode within that bit-width
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let n = rng.gen_range(0..500);
+            let mut row = TestRow {
+                ts: rng.gen(),
+                counter: rng.gen(),
+                sample: rng.gen_range(0..1 << 24),
+                a: rng.gen(),
+                b: Some(rng.gen_range(-2048..2048)),
+            };
+            let rows = (0..n)
+                .map(|_| {
+                    row.ts = row.ts.wrapping_add(rng.gen_range(0..1000));
+                    row.counter = row.counter.wrapping_add(rng.gen_range(0..100));
+                    row.sample = (row.sample + rng.gen_range(0..1000)) % (1 << 24);
+                    row.a = row.a.wrapping_add(rng.gen_range(-10..10));
+                    let b = row.b.unwrap_or(0) + rng.gen_range(-100..100);
+                    row.b = rng.gen_ratio(4, 5).then_some(((b + 2048) & 0xFFF) - 2048);
+                    row
+                })
+                .collect::<Vec<_>>();
+
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..20)) {
+                decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_declared_bits {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            #[tsz(bits = 19)]
+            pub adc: i32,
+            #[tsz(bits = 12)]
+            pub raw: u16,
+            #[tsz(bits = 40, delta = "i16")]
+            pub wide: i64,
+            #[tsz(bits = 10)]
+            pub b: Option<i16>,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod continued {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(continuation)]
+        pub struct TestRow {
+            #[tsz(bits = 19)]
+            pub adc: i32,
+            #[tsz(bits = 12)]
+            pub raw: u16,
+            #[tsz(bits = 40, delta = "i16")]
+            pub wide: i64,
+            #[tsz(bits = 10)]
+            pub b: Option<i16>,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod adc {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            #[tsz(bits = 19)]
+            pub adc: i32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod undeclared {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub adc: i32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+    }
+
+    mod unsigned {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            #[tsz(bits = 20)]
+            pub adc: u32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+    }
+
+    mod narrower {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            #[tsz(bits = 19)]
+            pub adc: u32,
+        }
+
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    ///
+    /// Random walks within the declared bit-widths, with the occasional jump across the whole range.
+    ///
+    fn random_rows(n: usize) -> Vec<row::TestRow> {
+        let mut rng = rand::thread_rng();
+        let mut row = row::TestRow {
+            adc: rng.gen_range(-(1 << 18)..1 << 18),
+            raw: rng.gen_range(0..1 << 12),
+            wide: rng.gen_range(-(1 << 39)..1 << 39),
+            b: Some(rng.gen_range(-512..512)),
+        };
+        (0..n)
+            .map(|_| {
+                row.adc = match rng.gen_ratio(1, 20) {
+                    true => rng.gen_range(-(1 << 18)..1 << 18),
+                    false => (row.adc + rng.gen_range(-100..100)).clamp(-(1 << 18), (1 << 18) - 1),
+                };
+                row.raw = match rng.gen_ratio(1, 20) {
+                    true => rng.gen_range(0..1 << 12),
+                    false => (row.raw as i32 + rng.gen_range(-10..10)).clamp(0, 4095) as u16,
+                };
+                row.wide = match rng.gen_ratio(1, 20) {
+                    true => rng.gen_range(-(1 << 39)..1 << 39),
+                    false => {
+                        (row.wide + rng.gen_range(-1000..1000)).clamp(-(1 << 39), (1 << 39) - 1)
+                    }
+                };
+                let b = rng.gen_range(-512..512);
+                row.b = rng.gen_ratio(4, 5).then_some(b);
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_declared_bits_are_smaller() {
+        // The first row and first delta are written in 19 and 20 bits, rather than as 7 nibble varints
+        let rows = [-200_000, 200_000];
+        let mut compressor = adc::TestRowCompressorImpl::new(rows.len());
+        for adc in rows {
+            compressor.compress(adc::TestRow { adc });
+        }
+        let bytes = compressor.finish();
+        let mut compressor = undeclared::TestRowCompressorImpl::new(rows.len());
+        for adc in rows {
+            compressor.compress(undeclared::TestRow { adc });
+        }
+        assert_eq!(bytes.len() + 2, compressor.finish().len());
+
+        // The negative first row is sign extended
+        let mut decompressor = adc::TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(
+            decompressor.rows(),
+            rows.map(|adc| adc::TestRow { adc }).to_vec()
+        );
+    }
+
+    #[test]
+    fn test_macro_compress_declared_bits_out_of_range() {
+        use adc::*;
+
+        let mut compressor = TestRowCompressorImpl::new(0);
+        compressor.compress(TestRow { adc: -(1 << 18) });
+        for adc in [1 << 18, -(1 << 18) - 1, i32::MAX] {
+            assert!(!compressor.would_fit(&TestRow { adc }, usize::MAX));
+            assert!(matches!(
+                compressor.try_compress(TestRow { adc }),
+                Err(CodingError::ValueOutOfRange("adc"))
+            ));
+        }
+        compressor
+            .try_compress(TestRow { adc: (1 << 18) - 1 })
+            .unwrap();
+        assert_eq!(compressor.row_count(), 2);
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&compressor.finish()).unwrap();
+        assert_eq!(
+            decompressor.rows(),
+            vec![TestRow { adc: -(1 << 18) }, TestRow { adc: (1 << 18) - 1 }]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "value of adc is outside of its declared bit-width")]
+    fn test_macro_compress_declared_bits_out_of_range_panics() {
+        let mut compressor = adc::TestRowCompressorImpl::new(0);
+        compressor.compress(adc::TestRow { adc: 1 << 18 });
+    }
+
+    #[test]
+    fn test_macro_compress_declared_bits_rejects_wider_values() {
+        // A 20-bit value in the nibbles of a 19-bit column does not decode
+        let mut compressor = unsigned::TestRowCompressorImpl::new(1);
+        compressor.compress(unsigned::TestRow { adc: (1 << 19) + 1 });
+        let bytes = compressor.finish();
+
+        let mut decompressor = narrower::TestRowDecompressorImpl::new();
+        assert!(decompressor.decompress(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_macro_compress_declared_bits() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..500));
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.try_compress(*row).unwrap();
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..20)) {
+                decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(decompressor.rows(), rows);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_declared_bits_continuation() {
+        use continued::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..300))
+                .into_iter()
+                .map(|row| TestRow {
+                    adc: row.adc,
+                    raw: row.raw,
+                    wide: row.wide,
+                    b: row.b,
+                })
+                .collect::<Vec<_>>();
+
+            // Packets of random sizes, each continuing from the previous packet
+            let mut compressor = TestRowCompressorImpl::new(0);
+            let mut decompressor = TestRowDecompressorImpl::new();
+            let mut streamed = TestRowDecompressorImpl::new();
+            for row in &rows {
+                if rng.gen_ratio(1, 8) {
+                    let bytes = compressor.finish();
+                    decompressor.decompress(&bytes).unwrap();
+                    streamed.decompress_chunk(&bytes).unwrap();
+                }
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+            decompressor.decompress(&bytes).unwrap();
+            streamed.decompress_chunk(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+            assert_eq!(streamed.rows(), rows);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_scaled_floats {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            #[tsz(scale = 100.0, offset = -40)]
+            pub temp_c: f32,
+            #[tsz(scale = 1000, offset = 40.5, int = "i16")]
+            pub humidity: f64,
+            #[tsz(scale = 10.0)]
+            pub pressure: Option<f64>,
+            #[tsz(scale = 4, int = "u8", bits = 6)]
+            pub level: f32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod continued {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header, continuation)]
+        pub struct TestRow {
+            pub ts: i64,
+            #[tsz(scale = 100.0, offset = -40)]
+            pub temp_c: f32,
+            #[tsz(scale = 1000, offset = 40.5, int = "i16")]
+            pub humidity: f64,
+            #[tsz(scale = 10.0)]
+            pub pressure: Option<f64>,
+            #[tsz(scale = 4, int = "u8", bits = 6)]
+            pub level: f32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod quantized {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub temp_c: i32,
+            pub humidity: i16,
+            pub pressure: Option<i64>,
+            #[tsz(bits = 6)]
+            pub level: u8,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+    }
+
+    ///
+    /// Readings that drift around typical values, within the range of each quantized column.
+    ///
+    fn random_rows(n: usize) -> Vec<row::TestRow> {
+        let mut rng = rand::thread_rng();
+        let mut row = row::TestRow {
+            ts: rng.gen(),
+            temp_c: rng.gen_range(-40.0..85.0),
+            humidity: rng.gen_range(35.0..45.0),
+            pressure: Some(rng.gen_range(900.0..1100.0)),
+            level: rng.gen_range(0.0..15.0),
+        };
+        (0..n)
+            .map(|_| {
+                row.ts = row.ts.wrapping_add(rng.gen_range(990..1010));
+                row.temp_c = (row.temp_c + rng.gen_range(-0.1..0.1)).clamp(-40.0, 85.0);
+                row.humidity = (row.humidity + rng.gen_range(-0.5..0.5)).clamp(35.0, 45.0);
+                row.pressure = rng
+                    .gen_ratio(9, 10)
+                    .then(|| row.pressure.unwrap_or(1000.0) + rng.gen_range(-1.0..1.0));
+                row.level = rng.gen_range(0.0..15.0);
+                row
+            })
+            .collect()
+    }
+
+    ///
+    /// The rows as they decode, each float the nearest multiple of its quantization step.
+    ///
+    fn quantized_rows(rows: &[row::TestRow]) -> Vec<row::TestRow> {
+        let round = |value: f64, scale: f64, offset: f64| {
+            dequantize(quantize(value, scale, offset), scale, offset)
+        };
+        rows.iter()
+            .map(|row| row::TestRow {
+                ts: row.ts,
+                temp_c: round(row.temp_c as f64, 100.0, -40.0) as f32,
+                humidity: round(row.humidity, 1000.0, 40.5),
+                pressure: row.pressure.map(|pressure| round(pressure, 10.0, 0.0)),
+                level: round(row.level as f64, 4.0, 0.0) as f32,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_scaled_floats() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..500));
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.try_compress(*row).unwrap();
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            let decoded = decompressor.rows();
+            assert_eq!(decoded, quantized_rows(&rows));
+            for (row, decoded) in rows.iter().zip(&decoded) {
+                assert!((row.temp_c - decoded.temp_c).abs() <= 0.005 + 1e-5);
+                assert!((row.humidity - decoded.humidity).abs() <= 0.0005 + 1e-9);
+            }
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..20)) {
+                decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(decompressor.rows(), decoded);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_scaled_floats_match_integers() {
+        // Scaling in the row struct compresses to the same bytes as scaling by hand
+        let rows = random_rows(1000);
+        let mut compressor = row::TestRowCompressorImpl::new(rows.len());
+        for row in &rows {
+            compressor.compress(*row);
+        }
+        let bytes = compressor.finish();
+
+        let mut compressor = quantized::TestRowCompressorImpl::new(rows.len());
+        for row in &rows {
+            compressor.compress(quantized::TestRow {
+                ts: row.ts,
+                temp_c: ((row.temp_c as f64 + 40.0) * 100.0).round() as i32,
+                humidity: ((row.humidity - 40.5) * 1000.0).round() as i16,
+                pressure: row
+                    .pressure
+                    .map(|pressure| (pressure * 10.0).round() as i64),
+                level: (row.level as f64 * 4.0).round() as u8,
+            });
+        }
+        assert_eq!(bytes, compressor.finish());
+    }
+
+    #[test]
+    fn test_macro_compress_scaled_floats_out_of_range() {
+        use row::*;
+
+        let row = TestRow {
+            ts: 0,
+            temp_c: 21.5,
+            humidity: 40.0,
+            pressure: None,
+            level: 1.0,
+        };
+        let mut compressor = TestRowCompressorImpl::new(0);
+        compressor.try_compress(row).unwrap();
+
+        // Floats that cannot be quantized to the column are rejected
+        for (bad, name) in [
+            (
+                TestRow {
+                    temp_c: f32::NAN,
+                    ..row
+                },
+                "temp_c",
+            ),
+            (
+                TestRow {
+                    humidity: 80.0,
+                    ..row
+                },
+                "humidity",
+            ),
+            (
+                TestRow {
+                    pressure: Some(f64::INFINITY),
+                    ..row
+                },
+                "pressure",
+            ),
+            (TestRow { level: -1.0, ..row }, "level"),
+            (TestRow { level: 16.0, ..row }, "level"),
+        ] {
+            assert!(!compressor.would_fit(&bad, usize::MAX));
+            assert!(matches!(
+                compressor.try_compress(bad),
+                Err(CodingError::ValueOutOfRange(n)) if n == name
+            ));
+        }
+        assert_eq!(compressor.row_count(), 1);
+
+        // Otherwise a NaN saturates to 0, decoding to the offset
+        compressor.compress(TestRow {
+            humidity: f64::NAN,
+            ..row
+        });
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&compressor.finish()).unwrap();
+        assert_eq!(decompressor.col_humidity(), &[40.0, 40.5]);
+    }
+
+    #[test]
+    fn test_macro_compress_scaled_floats_continuation() {
+        use continued::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..300));
+            let expected = quantized_rows(&rows);
+
+            // Packets of random sizes, each continuing from the previous packet
+            let mut compressor = TestRowCompressorImpl::new(0);
+            let mut decompressor = TestRowDecompressorImpl::new();
+            let mut streamed = TestRowDecompressorImpl::new();
+            for row in &rows {
+                if rng.gen_ratio(1, 8) {
+                    let bytes = compressor.finish();
+                    decompressor.decompress(&bytes).unwrap();
+                    streamed.decompress_chunk(&bytes).unwrap();
+                }
+                compressor.compress(TestRow {
+                    ts: row.ts,
+                    temp_c: row.temp_c,
+                    humidity: row.humidity,
+                    pressure: row.pressure,
+                    level: row.level,
+                });
+            }
+            let bytes = compressor.finish();
+            decompressor.decompress(&bytes).unwrap();
+            streamed.decompress_chunk(&bytes).unwrap();
+            for decompressor in [decompressor, streamed] {
+                let decoded = decompressor
+                    .rows()
+                    .into_iter()
+                    .map(|row| row::TestRow {
+                        ts: row.ts,
+                        temp_c: row.temp_c,
+                        humidity: row.humidity,
+                        pressure: row.pressure,
+                        level: row.level,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(decoded, expected);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_xor_floats {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub temp_c: f32,
+            pub voltage: f64,
+            pub count: u16,
+            pub pressure: Option<f64>,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod continued {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header, continuation)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub temp_c: f32,
+            pub voltage: f64,
+            pub count: u16,
+            pub pressure: Option<f64>,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod single {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub value: f64,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    const SPECIAL_F32: [f32; 9] = [
+        0.0,
+        -0.0,
+        f32::INFINITY,
+        f32::NEG_INFINITY,
+        f32::NAN,
+        f32::MIN_POSITIVE,
+        f32::MAX,
+        f32::MIN,
+        1e-45,
+    ];
+    const SPECIAL_F64: [f64; 9] = [
+        0.0,
+        -0.0,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+        f64::MIN_POSITIVE,
+        f64::MAX,
+        f64::MIN,
+        5e-324,
+    ];
+
+    ///
+    /// Readings that drift around typical values, with occasional repeats and special values.
+    ///
+    fn random_rows(n: usize) -> Vec<row::TestRow> {
+        let mut rng = rand::thread_rng();
+        let mut row = row::TestRow {
+            ts: rng.gen(),
+            temp_c: rng.gen_range(-40.0..85.0),
+            voltage: rng.gen_range(3.0..4.2),
+            count: rng.gen(),
+            pressure: Some(rng.gen_range(900.0..1100.0)),
+        };
+        (0..n)
+            .map(|_| {
+                row.ts = row.ts.wrapping_add(rng.gen_range(990..1010));
+                if rng.gen_ratio(1, 20) {
+                    row.temp_c = SPECIAL_F32[rng.gen_range(0..SPECIAL_F32.len())];
+                } else if !rng.gen_ratio(1, 4) {
+                    row.temp_c = (row.temp_c + rng.gen_range(-0.1..0.1)).clamp(-40.0, 85.0);
+                }
+                if rng.gen_ratio(1, 20) {
+                    row.voltage = f64::from_bits(rng.gen());
+                } else if rng.gen_ratio(1, 20) {
+                    row.voltage = SPECIAL_F64[rng.gen_range(0..SPECIAL_F64.len())];
+                } else if !rng.gen_ratio(1, 4) {
+                    row.voltage = rng.gen_range(3.0..4.2);
+                }
+                row.count = row.count.wrapping_add(rng.gen_range(0..3));
+                row.pressure = rng
+                    .gen_ratio(9, 10)
+                    .then(|| row.pressure.unwrap_or(1000.0) + rng.gen_range(-1.0..1.0));
+                row
+            })
+            .collect()
+    }
+
+    ///
+    /// The bits of each row, so NaNs compare equal when their bits are.
+    ///
+    fn row_bits(rows: &[row::TestRow]) -> Vec<(i64, u32, u64, u16, Option<u64>)> {
+        rows.iter()
+            .map(|row| {
+                (
+                    row.ts,
+                    row.temp_c.to_bits(),
+                    row.voltage.to_bits(),
+                    row.count,
+                    row.pressure.map(f64::to_bits),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_xor_floats() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..500));
+            let mut compressor = TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let bytes = compressor.finish();
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(row_bits(&decompressor.rows()), row_bits(&rows));
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..20)) {
+                decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(row_bits(&decompressor.rows()), row_bits(&rows));
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_xor_floats_special_values() {
+        use single::*;
+
+        // Every pair of special values, including NaN payloads, decodes bit for bit
+        let mut values = SPECIAL_F64.to_vec();
+        values.push(f64::from_bits(0x7FF0_0000_0000_0001));
+        values.push(f64::from_bits(0xFFF8_DEAD_BEEF_0001));
+        let values = values
+            .iter()
+            .flat_map(|a| values.iter().flat_map(move |b| [*a, *b, *b]))
+            .collect::<Vec<_>>();
+
+        let mut compressor = TestRowCompressorImpl::new(values.len());
+        for value in &values {
+            compressor.compress(TestRow { value: *value });
+        }
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&compressor.finish()).unwrap();
+        let decoded = decompressor.col_value().iter().map(|value| value.to_bits());
+        assert!(decoded.eq(values.iter().map(|value| value.to_bits())));
+    }
+
+    #[test]
+    fn test_macro_compress_xor_floats_size() {
+        use single::*;
+
+        // A repeated value takes a nibble per row
+        let mut compressor = TestRowCompressorImpl::new(1000);
+        for _ in 0..1000 {
+            compressor.compress(TestRow { value: 1013.25 });
+        }
+        let bytes = compressor.finish();
+        assert!(bytes.len() < 520, "{}", bytes.len());
+
+        // Slowly varying values share their sign, exponent and high mantissa nibbles
+        let mut compressor = TestRowCompressorImpl::new(1000);
+        let values = (0..1000).map(|i| 20.0 + i as f64 / 8.0).collect::<Vec<_>>();
+        for value in &values {
+            compressor.compress(TestRow { value: *value });
+        }
+        let bytes = compressor.finish();
+        assert!(bytes.len() < 8 * 1000 / 2, "{}", bytes.len());
+
+        let mut decompressor = TestRowDecompressorImpl::new();
+        decompressor.decompress(&bytes).unwrap();
+        assert_eq!(decompressor.col_value(), &values);
+    }
+
+    #[test]
+    fn test_macro_compress_xor_floats_continuation() {
+        use continued::*;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let rows = random_rows(rng.gen_range(0..300));
+
+            // Packets of random sizes, each continuing from the previous packet
+            let mut compressor = TestRowCompressorImpl::new(0);
+            let mut decompressor = TestRowDecompressorImpl::new();
+            let mut streamed = TestRowDecompressorImpl::new();
+            for row in &rows {
+                if rng.gen_ratio(1, 8) {
+                    let bytes = compressor.finish();
+                    decompressor.decompress(&bytes).unwrap();
+                    streamed.decompress_chunk(&bytes).unwrap();
+                }
+                compressor.compress(TestRow {
+                    ts: row.ts,
+                    temp_c: row.temp_c,
+                    voltage: row.voltage,
+                    count: row.count,
+                    pressure: row.pressure,
+                });
+            }
+            let bytes = compressor.finish();
+            decompressor.decompress(&bytes).unwrap();
+            streamed.decompress_chunk(&bytes).unwrap();
+            for decompressor in [decompressor, streamed] {
+                let decoded = decompressor
+                    .rows()
+                    .into_iter()
+                    .map(|row| row::TestRow {
+                        ts: row.ts,
+                        temp_c: row.temp_c,
+                        voltage: row.voltage,
+                        count: row.count,
+                        pressure: row.pressure,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(row_bits(&decoded), row_bits(&rows));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_crc {
+
+    use super::*;
+    use rand::Rng;
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header, crc)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i16,
+            pub b: Option<u32>,
+            pub c: f32,
+        }
+
+        pub use compress::{TestRowArrayCompressorImpl, TestRowCompressorImpl};
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    mod unchecked {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(header)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i16,
+            pub b: Option<u32>,
+            pub c: f32,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+    }
+
+    mod continued {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        #[tsz(continuation, crc)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i16,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+
+    fn random_rows(n: usize) -> Vec<row::TestRow> {
+        let mut rng = rand::thread_rng();
+        let mut ts: i64 = rng.gen();
+        (0..n)
+            .map(|_| {
+                ts = ts.wrapping_add(rng.gen_range(990..1010));
+                row::TestRow {
+                    ts,
+                    a: rng.gen_range(-100..100),
+                    b: rng.gen_ratio(3, 4).then(|| rng.gen_range(0..1000)),
+                    c: rng.gen_range(-1.0..1.0),
+                }
+            })
+            .collect()
+    }
+
+    fn compress(rows: &[row::TestRow]) -> Vec<u8> {
+        let mut compressor = row::TestRowCompressorImpl::new(rows.len());
+        for row in rows {
+            compressor.compress(*row);
+        }
+        let len = compressor.len_bytes_if_finished();
+        let bytes = compressor.finish();
+        assert_eq!(bytes.len(), len);
+        bytes
+    }
+
+    #[test]
+    fn test_macro_compress_crc_trailer() {
+        use row::*;
+
+        let mut rng = rand::thread_rng();
+        for n in [0, 1, 2, 3, 10, 100, 1000] {
+            let rows = random_rows(n);
+            let bytes = compress(&rows);
+
+            // The packet is the unchecked packet followed by its CRC-32
+            let mut compressor = unchecked::TestRowCompressorImpl::new(n);
+            for row in &rows {
+                compressor.compress(unchecked::TestRow {
+                    ts: row.ts,
+                    a: row.a,
+                    b: row.b,
+                    c: row.c,
+                });
+            }
+            let unchecked = compressor.finish();
+            assert_eq!(&bytes[..bytes.len() - 4], &unchecked[..]);
+            assert_eq!(bytes[bytes.len() - 4..], crc32(&unchecked).to_be_bytes());
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            let mut progress = DecodeProgress::Complete;
+            for chunk in bytes.chunks(rng.gen_range(1..20)) {
+                progress = decompressor.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(progress, DecodeProgress::Complete);
+            assert_eq!(decompressor.rows(), rows);
+
+            // The fixed-capacity compressor writes the same trailer
+            if n > 100 {
+                continue;
+            }
+            let mut compressor = TestRowArrayCompressorImpl::<1024>::new(n);
+            for row in &rows {
+                compressor.try_compress(*row).unwrap();
+            }
+            let mut slice = [0u8; 4096];
+            assert_eq!(compressor.len_bytes_if_finished(), bytes.len());
+            let len = compressor.finish_into_slice(&mut slice).unwrap();
+            assert_eq!(&slice[..len], &bytes[..]);
+        }
+    }
+
+    #[test]
+    fn test_macro_compress_crc_detects_bit_flips() {
+        use row::*;
+
+        let rows = random_rows(100);
+        let bytes = compress(&rows);
+        let previous = random_rows(10);
+        let previous_bytes = compress(&previous);
+
+        // Every flipped bit is rejected, before or after the whole packet arrives
+        for bit in 0..bytes.len() * 8 {
+            let mut corrupted = bytes.clone();
+            corrupted[bit / 8] ^= 1 << (bit % 8);
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&previous_bytes).unwrap();
+            assert!(matches!(
+                decompressor.decompress(&corrupted),
+                Err(CodingError::ChecksumMismatch)
+            ));
+            assert_eq!(decompressor.rows(), previous);
+
+            let mut streamed = TestRowDecompressorImpl::new();
+            streamed.decompress_chunk(&previous_bytes).unwrap();
+            let failed = corrupted
+                .chunks(7)
+                .any(|chunk| streamed.decompress_chunk(chunk).is_err());
+            assert!(failed, "bit {} was not detected", bit);
+            assert_eq!(streamed.rows(), previous);
+        }
+
+        // A packet too short for its trailer
+        let mut decompressor = TestRowDecompressorImpl::new();
+        assert!(matches!(
+            decompressor.decompress(&bytes[..3]),
+            Err(CodingError::Empty)
+        ));
+    }
+
+    #[test]
+    fn test_macro_compress_crc_mtu_packets() {
+        use row::*;
+
+        let rows = random_rows(2000);
+        let mut compressor = TestRowCompressorImpl::new(0);
+        compressor.set_mtu(Some(128));
+        let mut packets = Vec::new();
+        for row in &rows {
+            let mut packet = Vec::new();
+            if compressor.compress_into(*row, &mut packet) {
+                packets.push(packet);
+            }
+        }
+        packets.push(compressor.finish());
+
+        // Every packet, including its trailer, is within the budget
+        let mut decompressor = TestRowDecompressorImpl::new();
+        let mut streamed = TestRowDecompressorImpl::new();
+        for packet in &packets {
+            assert!(packet.len() <= 128, "{}", packet.len());
+            decompressor.decompress(packet).unwrap();
+            streamed.decompress_chunk(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), rows);
+        assert_eq!(streamed.rows(), rows);
+    }
+
+    #[test]
+    fn test_macro_compress_crc_continuation() {
+        use continued::*;
+
+        let mut rng = rand::thread_rng();
+        let mut compressor = TestRowCompressorImpl::new(0);
+        let mut decompressor = TestRowDecompressorImpl::new();
+        let mut streamed = TestRowDecompressorImpl::new();
+        let mut rows = Vec::new();
+        let mut stream = Vec::new();
+        for i in 0..1000 {
+            let row = TestRow {
+                ts: 1000 * i,
+                a: rng.gen_range(-100..100),
+            };
+            if rng.gen_ratio(1, 16) {
+                let bytes = compressor.finish();
+                decompressor.decompress(&bytes).unwrap();
+                stream.extend(bytes);
+            }
+            compressor.compress(row);
+            rows.push(row);
+        }
+        let bytes = compressor.finish();
+        decompressor.decompress(&bytes).unwrap();
+        stream.extend(bytes);
+        for chunk in stream.chunks(rng.gen_range(1..50)) {
+            streamed.decompress_chunk(chunk).unwrap();
+        }
+        assert_eq!(decompressor.rows(), rows);
+        assert_eq!(streamed.rows(), rows);
+    }
+}
+
+#[cfg(test)]
+mod test_row_count {
+
+    use super::*;
+    use rand::Rng;
+    use tsz_compress::prelude::halfvec::{HalfVec, HalfWord};
+
+    mod row {
+        use tsz_compress::prelude::*;
+        #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+        pub struct TestRow {
+            pub ts: i64,
+            pub a: i16,
+        }
+
+        pub use compress::TestRowCompressorImpl;
+        pub use decompress::TestRowDecompressorImpl;
+    }
+    use row::*;
+
+    ///
+    /// Replaces the row count of a packet with a nibble varint of the claimed rows.
+    ///
+    fn with_row_count(bytes: &[u8], rows: u64) -> Vec<u8> {
+        let mut iter = HalfIter::new(bytes);
+        read_row_count(&mut iter).unwrap();
+        let skip = iter.nibble_offset();
+
+        let mut halves = HalfVec::new(bytes.len() + 12);
+        write_nibble_varint(&mut halves, rows);
+        for nibble in HalfIter::new(bytes).skip(skip) {
+            halves.push(HalfWord::Half(nibble));
+        }
+        let mut out = Vec::new();
+        HalfVec::finish(&mut out, core::iter::once(&halves));
+        out
+    }
+
+    #[test]
+    fn test_macro_compress_row_count_varint_lengths() {
+        let mut rng = rand::thread_rng();
+        for n in [1, 7, 8, 63, 64, 511, 512, 4095, 4096, 1 << 18] {
+            let rows = (0..n)
+                .map(|i| TestRow {
+                    ts: 1000 * i as i64 + rng.gen_range(-2..=2),
+                    a: rng.gen_range(-100..100),
+                })
+                .collect::<Vec<_>>();
+            let mut compressor = TestRowCompressorImpl::new(n);
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let len = compressor.len_bytes_if_finished();
+            let bytes = compressor.finish();
+            assert_eq!(bytes.len(), len);
+
+            // The row count is 3 bits per nibble, so small packets pay a nibble or two
+            let mut iter = HalfIter::new(&bytes);
+            assert_eq!(read_row_count(&mut iter).unwrap(), n);
+            assert_eq!(iter.nibble_offset(), nibble_varint_len(n as u64));
+
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), rows);
+
+            let mut streamed = TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..16)) {
+                streamed.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(streamed.rows(), rows);
+        }
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_macro_compress_row_count_beyond_u32() {
+        // Row counts wider than 32 bits survive the varint
+        for rows in [
+            u32::MAX as u64,
+            1 << 32,
+            1 << 40,
+            (1 << 61) + 12345,
+            u64::MAX,
+        ] {
+            let mut halves = HalfVec::new(12);
+            write_nibble_varint(&mut halves, rows);
+            let mut bytes = Vec::new();
+            HalfVec::finish(&mut bytes, core::iter::once(&halves));
+            let mut iter = HalfIter::new(&bytes);
+            assert_eq!(read_row_count(&mut iter).unwrap() as u64, rows);
+        }
+
+        // A packet claiming more rows than it holds is rejected with the decoded row count, without reserving for them
+        let previous = [TestRow { ts: 1, a: 2 }, TestRow { ts: 3, a: 4 }];
+        let mut compressor = TestRowCompressorImpl::new(2);
+        for row in &previous {
+            compressor.compress(*row);
+        }
+        let bytes = compressor.finish();
+        for claimed in [1 << 32, 1 << 40, u64::MAX] {
+            let claimed_bytes = with_row_count(&bytes, claimed);
+            let mut decompressor = TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            let error = decompressor.decompress(&claimed_bytes).unwrap_err();
+            assert!(
+                matches!(error, CodingError::InvalidRowCount(rows) if rows as u64 == claimed),
+                "{:?}",
+                error
+            );
+            assert_eq!(decompressor.rows(), previous);
+
+            let mut streamed = TestRowDecompressorImpl::new();
+            streamed.decompress_chunk(&bytes).unwrap();
+            assert!(streamed.decompress_chunk(&claimed_bytes).is_err());
+            assert_eq!(streamed.rows(), previous);
+        }
+
+        // A row count cut short is not enough bits rather than an empty packet
+        let mut decompressor = TestRowDecompressorImpl::new();
+        assert!(matches!(
+            decompressor.decompress(&[0x8F]),
+            Err(CodingError::NotEnoughBits)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test_lookahead {
+
+    use super::*;
+    use crate::common::as_rows;
+    use rand::Rng;
+
+    mod row {
+        crate::common::test_row!(#[tsz(lookahead)]);
+    }
+
+    mod greedy {
+        crate::common::test_row!();
+    }
+
+    ///
+    /// Slowly changing rows with occasional spikes.
+    ///
+    fn random_rows(rng: &mut impl Rng, n: usize) -> Vec<row::TestRow> {
+        let mut ts = rng.gen_range(0..1 << 40);
+        (0..n)
+            .map(|_| {
+                ts += match rng.gen_ratio(1, 20) {
+                    true => rng.gen_range(0..1 << 20),
+                    false => 1000 + rng.gen_range(-2..=2),
+                };
+                row::TestRow {
+                    ts,
+                    a: match rng.gen_ratio(1, 10) {
+                        true => rng.gen(),
+                        false => rng.gen_range(-3..3),
+                    },
+                    b: rng.gen_bool(0.8).then(|| rng.gen_range(0..4)),
+                    c: rng.gen_range(0..8),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_macro_compress_lookahead_round_trip() {
+        let mut rng = rand::thread_rng();
+        let mut lookahead_len = 0;
+        let mut greedy_len = 0;
+        for _ in 0..200 {
+            let n = rng.gen_range(1..300);
+            let rows = random_rows(&mut rng, n);
+            let mut compressor = row::TestRowCompressorImpl::new(rows.len());
+            for row in &rows {
+                compressor.compress(*row);
+            }
+            let len = compressor.len_bytes_if_finished();
+            let bytes = compressor.finish();
+            assert_eq!(bytes.len(), len);
+            lookahead_len += bytes.len();
+
+            // The packing is chosen differently, but the words are the same
+            // The same rows for the compressor and decompressor without lookahead
+            let expected: Vec<greedy::TestRow> = as_rows(&rows);
+            let mut decompressor = greedy::TestRowDecompressorImpl::new();
+            decompressor.decompress(&bytes).unwrap();
+            assert_eq!(decompressor.rows(), expected);
+
+            let mut streamed = greedy::TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..16)) {
+                streamed.decompress_chunk(chunk).unwrap();
+            }
+            assert_eq!(streamed.rows(), expected);
+
+            let mut compressor = greedy::TestRowCompressorImpl::new(rows.len());
+            for row in &expected {
+                compressor.compress(*row);
+            }
+            greedy_len += compressor.finish().len();
+        }
+
+        // Fewer words are broken up around the spikes
+        assert!(
+            lookahead_len < greedy_len,
+            "{} {}",
+            lookahead_len,
+            greedy_len
+        );
+    }
+
+    #[test]
+    fn test_macro_compress_lookahead_mtu_packets() {
+        let mut rng = rand::thread_rng();
+        let rows = random_rows(&mut rng, 2000);
+        let mut compressor = row::TestRowCompressorImpl::new(0);
+        compressor.set_mtu(Some(64));
+        let mut packets = Vec::new();
+        for row in &rows {
+            let mut packet = Vec::new();
+            if compressor.compress_into(*row, &mut packet) {
+                packets.push(packet);
+            }
+        }
+        packets.push(compressor.finish());
+
+        let mut decompressor = greedy::TestRowDecompressorImpl::new();
+        for packet in &packets {
+            assert!(packet.len() <= 64, "{}", packet.len());
+            decompressor.decompress(packet).unwrap();
+        }
+        assert_eq!(decompressor.rows(), as_rows::<_, greedy::TestRow>(&rows));
+    }
+
+    #[test]
+    fn test_macro_compress_lookahead_into_slice() {
+        let mut rng = rand::thread_rng();
+        let rows = random_rows(&mut rng, 100);
+
+        // The same rows compress to the same bytes with either storage
+        let mut compressor = row::TestRowCompressorImpl::new(rows.len());
+        let mut array_compressor = row::TestRowArrayCompressorImpl::<512>::new(rows.len());
+        for row in &rows {
+            compressor.compress(*row);
+            array_compressor.try_compress(*row).unwrap();
+        }
+        let bytes = compressor.finish();
+
+        let mut slice = [0u8; 1024];
+        let len = array_compressor.finish_into_slice(&mut slice).unwrap();
+        assert_eq!(&slice[..len], &bytes[..]);
+
+        let mut decompressor = greedy::TestRowDecompressorImpl::new();
+        decompressor.decompress(&slice[..len]).unwrap();
+        assert_eq!(decompressor.rows(), as_rows::<_, greedy::TestRow>(&rows));
+    }
+}
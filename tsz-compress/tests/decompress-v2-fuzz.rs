@@ -0,0 +1,344 @@
+#![allow(unused)]
+use tsz_compress::prelude::*;
+
+extern crate alloc;
+
+use rand::Rng;
+
+mod row {
+    use tsz_compress::prelude::*;
+    #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+    pub struct TestRow {
+        pub ts: i64,
+        pub a: i8,
+        pub b: u16,
+        pub c: i32,
+        pub d: Option<u64>,
+        pub e: i128,
+    }
+
+    pub use compress::TestRowCompressorImpl;
+    pub use decompress::TestRowDecompressorImpl;
+}
+
+mod header_row {
+    use tsz_compress::prelude::*;
+    #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+    #[tsz(header)]
+    pub struct TestRow {
+        pub ts: i64,
+        pub a: Option<i16>,
+    }
+
+    pub use compress::TestRowCompressorImpl;
+    pub use decompress::TestRowDecompressorImpl;
+}
+
+mod single_row {
+    use tsz_compress::prelude::*;
+    #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+    pub struct TestRow {
+        pub a: i64,
+    }
+
+    pub use compress::TestRowCompressorImpl;
+    pub use decompress::TestRowDecompressorImpl;
+}
+
+mod nullable_row {
+    use tsz_compress::prelude::*;
+    #[derive(Debug, Copy, Clone, PartialEq, CompressV2, DecompressV2)]
+    pub struct TestRow {
+        pub a: Option<i64>,
+    }
+
+    pub use compress::TestRowCompressorImpl;
+    pub use decompress::TestRowDecompressorImpl;
+}
+
+///
+/// Generates rows that exercise every word of the encoding: runs, small and large deltas, and nulls.
+///
+fn random_rows(rng: &mut impl Rng, n: usize) -> Vec<row::TestRow> {
+    let mut row = row::TestRow {
+        ts: rng.gen(),
+        a: rng.gen(),
+        b: rng.gen(),
+        c: rng.gen(),
+        d: None,
+        e: rng.gen(),
+    };
+    (0..n)
+        .map(|i| {
+            row.ts = row.ts.wrapping_add(1000);
+            if rng.gen_bool(0.5) {
+                row.a = row.a.wrapping_add(rng.gen_range(-3..=3));
+                row.b = rng.gen();
+                row.c = row.c.wrapping_add(rng.gen_range(-100_000..100_000));
+                let bits = rng.gen_range(1..64);
+                row.d = rng.gen::<bool>().then(|| rng.gen_range(0..1u64 << bits));
+                row.e = row.e.wrapping_add(rng.gen::<i64>() as i128);
+            }
+            row
+        })
+        .collect()
+}
+
+fn compress(rows: &[row::TestRow]) -> Vec<u8> {
+    let mut compressor = row::TestRowCompressorImpl::new(rows.len());
+    for row in rows {
+        compressor.compress(*row);
+    }
+    compressor.finish()
+}
+
+///
+/// A two-nibble row count of at most 63 rows, keeping the row count small,
+/// as a run may legitimately decode up to the row count.
+///
+fn small_row_count(rng: &mut impl Rng) -> u8 {
+    0b1000_0000 | (rng.gen_range(0..8) << 4) | rng.gen_range(0..8)
+}
+
+///
+/// Replaces the nibble varint row count at the start of a packet without a header.
+///
+fn with_row_count(bytes: &[u8], rows: u64) -> Vec<u8> {
+    let nibbles = bytes.iter().flat_map(|byte| [byte >> 4, byte & 0xF]);
+    let mut columns = nibbles.skip_while(|nibble| nibble & 0b1000 != 0).skip(1);
+
+    let mut halves = alloc::vec::Vec::new();
+    let len = (64 - rows.leading_zeros() as usize).div_ceil(3).max(1);
+    for i in (0..len).rev() {
+        let more = if i > 0 { 0b1000 } else { 0 };
+        halves.push(more | ((rows >> (3 * i)) & 0b111) as u8);
+    }
+    halves.extend(columns);
+    if halves.len() % 2 == 1 {
+        halves.push(0b1001);
+    }
+    halves
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect()
+}
+
+///
+/// Decompresses the bytes after a packet of known rows,
+/// checking that an error leaves the known rows untouched.
+///
+fn check_decompress(prefix: &[row::TestRow], prefix_bytes: &[u8], bytes: &[u8]) {
+    let mut decompressor = row::TestRowDecompressorImpl::new();
+    decompressor.decompress(prefix_bytes).unwrap();
+
+    match decompressor.decompress(bytes) {
+        Ok(()) => {
+            // Every column must have been extended by the same number of rows
+            let rows = decompressor.rows();
+            assert_eq!(&rows[..prefix.len()], prefix);
+            let len = rows.len();
+            assert_eq!(decompressor.col_ts().len(), len);
+            assert_eq!(decompressor.col_a().len(), len);
+            assert_eq!(decompressor.col_b().len(), len);
+            assert_eq!(decompressor.col_c().len(), len);
+            assert_eq!(decompressor.col_d().len(), len);
+            assert_eq!(decompressor.col_e().len(), len);
+        }
+        Err(_) => {
+            assert_eq!(decompressor.rows(), prefix);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_decompress_fuzz {
+
+    use super::*;
+
+    #[test]
+    fn test_decompress_random_bytes() {
+        let mut rng = rand::thread_rng();
+        let prefix = random_rows(&mut rng, 20);
+        let prefix_bytes = compress(&prefix);
+
+        for _ in 0..100_000 {
+            let len = rng.gen_range(0..128);
+            let mut bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+
+            // Keep the row count small, as a run may legitimately decode up to the row count
+            if !bytes.is_empty() {
+                bytes[0] = small_row_count(&mut rng);
+            }
+
+            check_decompress(&prefix, &prefix_bytes, &bytes);
+        }
+    }
+
+    #[test]
+    fn test_decompress_random_column_tags() {
+        let mut rng = rand::thread_rng();
+        let prefix = random_rows(&mut rng, 20);
+        let prefix_bytes = compress(&prefix);
+
+        // Well-formed starts of columns followed by random words
+        for _ in 0..100_000 {
+            let mut bytes = vec![small_row_count(&mut rng)];
+            bytes.push(0b1001_0000 | rng.gen_range(0b0110..=0b0111));
+            bytes.extend((0..rng.gen_range(0..64)).map(|_| rng.gen::<u8>()));
+            check_decompress(&prefix, &prefix_bytes, &bytes);
+        }
+    }
+
+    #[test]
+    fn test_decompress_truncated() {
+        let mut rng = rand::thread_rng();
+        let prefix = random_rows(&mut rng, 20);
+        let prefix_bytes = compress(&prefix);
+
+        for n in [1, 2, 3, 10, 11, 50, 200] {
+            let rows = random_rows(&mut rng, n);
+            let bytes = compress(&rows);
+            for len in 0..bytes.len() {
+                check_decompress(&prefix, &prefix_bytes, &bytes[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompress_corrupted() {
+        let mut rng = rand::thread_rng();
+        let prefix = random_rows(&mut rng, 20);
+        let prefix_bytes = compress(&prefix);
+
+        for _ in 0..200 {
+            let n = rng.gen_range(0..200);
+            let rows = random_rows(&mut rng, n);
+            let mut bytes = compress(&rows);
+
+            // Flip a few bits after the row count
+            if bytes.len() > 2 {
+                for _ in 0..rng.gen_range(1..4) {
+                    let index = rng.gen_range(2..bytes.len());
+                    bytes[index] ^= 1 << rng.gen_range(0..8);
+                }
+            }
+
+            check_decompress(&prefix, &prefix_bytes, &bytes);
+        }
+    }
+
+    #[test]
+    fn test_decompress_row_count_mismatch() {
+        let mut rng = rand::thread_rng();
+        let prefix = random_rows(&mut rng, 20);
+        let prefix_bytes = compress(&prefix);
+
+        for n in [2, 10, 100, 1000] {
+            let rows = random_rows(&mut rng, n);
+            let mut decompressor = row::TestRowDecompressorImpl::new();
+            decompressor
+                .decompress(&with_row_count(&compress(&rows), n as u64))
+                .unwrap();
+            assert_eq!(decompressor.rows(), rows);
+
+            let n = n as u64;
+            for claimed in [0, n - 1, n + 1, n * 2, u32::MAX as u64, 1 << 40, u64::MAX] {
+                let bytes = with_row_count(&compress(&rows), claimed);
+
+                let mut decompressor = row::TestRowDecompressorImpl::new();
+                decompressor.decompress(&prefix_bytes).unwrap();
+                assert!(decompressor.decompress(&bytes).is_err());
+                assert_eq!(decompressor.rows(), prefix);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decompress_oversized_runs() {
+        // 2^36 rows, then a column of a first row, a first delta, and a run of 2^35 repeated deltas
+        let bytes = [
+            0x98, 0x88, 0x88, 0x88, 0x88, 0x88, 0x09, 0x20, 0x20, 0x1c, 0x88, 0x88, 0x88, 0x88,
+            0x88, 0x00, 0x99,
+        ];
+        let mut decompressor = single_row::TestRowDecompressorImpl::new();
+        assert!(matches!(
+            decompressor.decompress(&bytes),
+            Err(CodingError::InvalidRowCount(rows)) if rows as u64 == 1 << 36
+        ));
+        assert!(decompressor.rows().is_empty());
+
+        let mut streamed = single_row::TestRowDecompressorImpl::new();
+        assert!(matches!(
+            streamed.decompress_chunk(&bytes),
+            Err(CodingError::InvalidRowCount(_))
+        ));
+        assert!(streamed.rows().is_empty());
+
+        // The same run within a packet of at most MAX_PACKET_ROWS rows is bounded by the row count
+        let bytes = with_row_count(&bytes, MAX_PACKET_ROWS as u64);
+        let mut decompressor = single_row::TestRowDecompressorImpl::new();
+        assert!(decompressor.decompress(&bytes).is_err());
+        assert!(decompressor.rows().is_empty());
+
+        // 2^36 rows of an absent value
+        let bytes = [
+            0x98, 0x88, 0x88, 0x88, 0x88, 0x88, 0x09, 0x09, 0x88, 0x88, 0x88, 0x88, 0x88, 0x80,
+            0x99,
+        ];
+        let mut decompressor = nullable_row::TestRowDecompressorImpl::new();
+        assert!(matches!(
+            decompressor.decompress(&bytes),
+            Err(CodingError::InvalidRowCount(rows)) if rows as u64 == 1 << 36
+        ));
+        let mut streamed = nullable_row::TestRowDecompressorImpl::new();
+        assert!(streamed.decompress_chunk(&bytes).is_err());
+        assert!(streamed.rows().is_empty());
+    }
+
+    #[test]
+    fn test_decompress_header_random_bytes() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..100_000 {
+            // A valid header for i64 and the presence and values of Option<i16>, then random bytes
+            let mut bytes = vec![0x51, 3, 0x33, 0x70, 0x11];
+            bytes.push(small_row_count(&mut rng));
+            bytes.extend((0..rng.gen_range(0..64)).map(|_| rng.gen::<u8>()));
+
+            let mut decompressor = header_row::TestRowDecompressorImpl::new();
+            if decompressor.decompress(&bytes).is_err() {
+                assert!(decompressor.rows().is_empty());
+            }
+            assert_eq!(decompressor.col_ts().len(), decompressor.col_a().len());
+        }
+    }
+
+    #[test]
+    fn test_decompress_stream_corrupted() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..200 {
+            let n = rng.gen_range(0..200);
+            let rows = random_rows(&mut rng, n);
+            let mut bytes = compress(&rows);
+
+            // Flip a few bits after the row count
+            if bytes.len() > 2 {
+                for _ in 0..rng.gen_range(1..4) {
+                    let index = rng.gen_range(2..bytes.len());
+                    bytes[index] ^= 1 << rng.gen_range(0..8);
+                }
+            }
+
+            // Feed the packet in random chunks, the columns never fall behind the rows
+            let mut decompressor = row::TestRowDecompressorImpl::new();
+            for chunk in bytes.chunks(rng.gen_range(1..16)) {
+                let _ = decompressor.decompress_chunk(chunk);
+                let len = decompressor.rows().len();
+                assert!(decompressor.col_ts().len() >= len);
+                assert!(decompressor.col_d().len() >= len);
+                assert!(decompressor.col_e().len() >= len);
+            }
+        }
+    }
+}
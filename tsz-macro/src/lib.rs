@@ -485,7 +485,7 @@ pub fn derive_decompressible(item: TokenStream) -> TokenStream {
     .into()
 }
 
-fn get_fields_of_struct(input: syn::DeriveInput) -> Vec<(syn::Ident, syn::Type, Option<String>)> {
+fn get_fields_of_struct(input: syn::DeriveInput) -> Vec<(syn::Ident, syn::Type, FieldOptions)> {
     let fields = match input.data {
         syn::Data::Struct(syn::DataStruct { fields, .. }) => fields,
         _ => panic!("Expected fields in derive(Builder) struct"),
@@ -495,116 +495,226 @@ fn get_fields_of_struct(input: syn::DeriveInput) -> Vec<(syn::Ident, syn::Type,
         _ => panic!("Expected named fields in derive(Builder) struct"),
     };
 
-    // Get the tsz attributes for each field attribute
-    let delta_attributes = named_fields
-        .iter()
-        .map(|field| {
-            let filtered_attrs: Vec<_> = field
-                .attrs
-                .iter()
-                .filter(|attr| attr.path().is_ident("tsz"))
-                .collect();
-            Option::from(filtered_attrs).filter(|v| !v.is_empty())
+    named_fields
+        .into_iter()
+        .map(|f| {
+            let options = get_options_of_field(&f);
+            (f.ident.unwrap(), f.ty, options)
         })
-        .collect::<Vec<_>>();
+        .collect::<Vec<_>>() // (ident, ty, options)
+}
 
-    // Get delta column types from each tsz field attribute
-    let mut delta_user_col_tys: Vec<Option<String>> = Vec::new();
-    for delta_attribute in delta_attributes {
-        if let Some(delta_attr) = delta_attribute {
-            for attr in delta_attr {
-                // There should only be one tsz attribute per field: delta
-                if let Meta::List(meta_list) = attr.meta.clone() {
-                    let tokens = meta_list.tokens.into_iter().peekable();
-                    let mut identifier = String::new();
-                    let mut punct = String::new();
-                    let mut literal = String::new();
-
-                    for token in tokens {
-                        if let TokenTree::Ident(ident) = &token {
-                            identifier = ident.to_string();
-                        } else if let TokenTree::Punct(p) = &token {
-                            punct = p.to_string();
-                        } else if let TokenTree::Literal(lit) = &token {
-                            literal = lit.to_string();
+///
+/// Field-level options from the `#[tsz(...)]` attributes on a field.
+///
+#[derive(Default, Clone)]
+struct FieldOptions {
+    /// The user chosen delta bit-width, as the string literal of `delta = "..."`
+    delta: Option<String>,
+    /// Compute the deltas modulo the column width, or `wrapping_bits` if declared
+    wrapping: bool,
+    /// The declared bit-width of a wrapping counter, from `wrapping = N`
+    wrapping_bits: Option<u32>,
+    /// The declared bit-width of the values, from `bits = N`
+    bits: Option<u32>,
+    /// The quantization of a float column, from `scale = ...`, `offset = ...` and `int = "..."`
+    scale: Option<f64>,
+    offset: Option<f64>,
+    int: Option<String>,
+}
+
+fn get_options_of_field(field: &syn::Field) -> FieldOptions {
+    let mut options = FieldOptions::default();
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("tsz"))
+    {
+        if let Meta::List(meta_list) = attr.meta.clone() {
+            // Each option is an identifier, optionally followed by `= literal`
+            let tokens = meta_list.tokens.into_iter().collect_vec();
+            for option in
+                tokens.split(|token| matches!(token, TokenTree::Punct(p) if p.as_char() == ','))
+            {
+                match option {
+                    [] => {}
+                    [TokenTree::Ident(ident)] => match ident.to_string().as_str() {
+                        "wrapping" => options.wrapping = true,
+                        _ => panic!("Unexpected field attribute"),
+                    },
+                    [TokenTree::Ident(ident), TokenTree::Punct(p), TokenTree::Literal(literal)]
+                        if p.as_char() == '=' =>
+                    {
+                        let float = || {
+                            literal
+                                .to_string()
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|value| value.is_finite())
+                                .expect("Expected a finite number")
+                        };
+                        match ident.to_string().as_str() {
+                            "delta" => options.delta = Some(literal.to_string()),
+                            "wrapping" => {
+                                options.wrapping = true;
+                                options.wrapping_bits = Some(
+                                    literal
+                                        .to_string()
+                                        .parse()
+                                        .expect("Expected the wrapping bit-width as an integer"),
+                                );
+                            }
+                            "bits" => {
+                                options.bits = Some(
+                                    literal
+                                        .to_string()
+                                        .parse()
+                                        .expect("Expected the bit-width as an integer"),
+                                );
+                            }
+                            "scale" => options.scale = Some(float()),
+                            "offset" => options.offset = Some(float()),
+                            "int" => options.int = Some(literal.to_string()),
+                            _ => panic!("Unexpected field attribute"),
                         }
                     }
-
-                    match (identifier.as_str(), punct.as_str()) {
-                        ("delta", "=") => delta_user_col_tys.push(Some(literal)),
-                        ("delta", _) => panic!("Unexpected field operator"),
-                        _ => panic!("Unexpected delta bit-width attribute"),
+                    // Negative offsets are a minus sign ahead of the literal
+                    [TokenTree::Ident(ident), TokenTree::Punct(p), TokenTree::Punct(minus), TokenTree::Literal(literal)]
+                        if p.as_char() == '=' && minus.as_char() == '-' && ident == "offset" =>
+                    {
+                        options.offset = Some(
+                            -literal
+                                .to_string()
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|value| value.is_finite())
+                                .expect("Expected a finite number"),
+                        );
                     }
+                    [TokenTree::Ident(_), ..] => panic!("Unexpected field operator"),
+                    _ => panic!("Unexpected field attribute"),
                 }
             }
-        } else {
-            delta_user_col_tys.push(None);
         }
     }
-
-    named_fields
-        .into_iter()
-        .enumerate()
-        .map(|(i, f)| {
-            let attr = &delta_user_col_tys[i];
-            (f.ident.unwrap(), f.ty, attr.clone())
-        })
-        .collect::<Vec<_>>() // (ident, ty, delta_bit_width)
+    if options.wrapping && options.delta.is_some() {
+        panic!("A wrapping column computes its deltas at the column width, it cannot also choose a delta bit-width");
+    }
+    if options.wrapping && options.bits.is_some() {
+        panic!("A wrapping column declares its bit-width with `wrapping = N`");
+    }
+    if options.scale.is_none() && (options.offset.is_some() || options.int.is_some()) {
+        panic!("The offset and integer of a float column are only used with a scale");
+    }
+    if options.scale == Some(0.0) {
+        panic!("The scale of a float column cannot be 0");
+    }
+    if options.scale.is_some() && options.wrapping {
+        panic!("A scaled float column cannot wrap");
+    }
+    options
 }
 
 ///
-/// CompressV2 is a procedural macro that will inspect the fields of
-/// a struct and generate a StructCompressor with statically sized columnar
-/// compression for the fields.
+/// Struct-level options from the `#[tsz(...)]` attributes on the struct.
 ///
-#[proc_macro_derive(CompressV2, attributes(tsz))]
-pub fn derive_compressv2(tokens: TokenStream) -> TokenStream {
-    let input = parse_macro_input!(tokens as syn::DeriveInput);
-    let ident = input.ident.clone();
-
-    // We will define a struct by this name
-    let compressor_ident = format_ident!("{}CompressorImpl", input.ident);
+#[derive(Default)]
+struct StructOptions {
+    /// Emit and expect a self-describing stream header
+    header: bool,
+    /// Continue the deltas of each packet from the previous packet, see `consts::sequence`
+    continuation: bool,
+    /// Append a CRC-32 of the packet after the columns, checked by the decompressor
+    crc: bool,
+    /// Look ahead over more queued deltas for the packing with the fewest nibbles
+    lookahead: bool,
+}
 
-    // We will compress each of the fields as columns
-    let columns = get_fields_of_struct(input);
-    let (col_idents, col_tys, col_attrs): (Vec<_>, Vec<_>, Vec<_>) = multiunzip(columns);
-    let col_delta_comp_queue_idents = col_idents
-        .iter()
-        .map(|ident| format_ident!("{}_delta_compressor_queue", ident))
-        .collect_vec();
-    let col_delta_delta_comp_queue_idents = col_idents
+fn get_options_of_struct(input: &syn::DeriveInput) -> StructOptions {
+    let mut options = StructOptions::default();
+    for attr in input
+        .attrs
         .iter()
-        .map(|ident| format_ident!("{}_delta_delta_compressor_queue", ident))
-        .collect_vec();
-    let col_delta_buf_idents = col_idents
-        .iter()
-        .map(|ident| format_ident!("{}_delta_output_buffer", ident))
-        .collect_vec();
-    let col_delta_delta_buf_idents = col_idents
-        .iter()
-        .map(|ident| format_ident!("{}_delta_delta_output_buffer", ident))
-        .collect_vec();
-    let num_columns = col_idents.len();
+        .filter(|attr| attr.path().is_ident("tsz"))
+    {
+        if let Meta::List(meta_list) = attr.meta.clone() {
+            for token in meta_list.tokens {
+                match &token {
+                    TokenTree::Ident(ident) => match ident.to_string().as_str() {
+                        "header" => options.header = true,
+                        "continuation" => options.continuation = true,
+                        "crc" => options.crc = true,
+                        "lookahead" => options.lookahead = true,
+                        _ => panic!("Unexpected struct attribute"),
+                    },
+                    TokenTree::Punct(p) if p.as_char() == ',' => {}
+                    _ => panic!("Unexpected struct attribute"),
+                }
+            }
+        }
+    }
+    options
+}
 
-    // Get the delta types for each column: If user specified, use that, otherwise use default
-    let delta_col_tys = col_attrs
+///
+/// The `T` of an `Option<T>` column type, or None if the column is not nullable.
+///
+fn get_option_inner_ty(ty: &syn::Type) -> Option<syn::Type> {
+    let syn::Type::Path(syn::TypePath { path, .. }) = ty else {
+        return None;
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+            Some(syn::GenericArgument::Type(inner)) => Some(inner.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+///
+/// The delta type for each column: If user specified, use that, otherwise use default
+///
+/// Wrapping columns use the signed type of the same width, so the deltas wrap with the column.
+///
+fn get_delta_col_names(col_tys: &[syn::Type], col_attrs: &[FieldOptions]) -> Vec<&'static str> {
+    col_attrs
         .iter()
-        .zip(&col_tys)
-        .map(|(attr, ty)| match attr.as_ref() {
-            Some(s) if s == "\"i8\"" => quote! { i8 },
-            Some(s) if s == "\"i16\"" => quote! { i16 },
-            Some(s) if s == "\"i32\"" => quote! { i32 },
-            Some(s) if s == "\"i64\"" => quote! { i64 },
+        .zip(col_tys)
+        .map(|(attr, ty)| match attr.delta.as_ref() {
+            Some(s) if s == "\"i8\"" => "i8",
+            Some(s) if s == "\"i16\"" => "i16",
+            Some(s) if s == "\"i32\"" => "i32",
+            Some(s) if s == "\"i64\"" => "i64",
+            None if attr.wrapping => match get_col_bits(ty) {
+                8 => "i8",
+                16 => "i16",
+                32 => "i32",
+                64 => "i64",
+                _ => "i128",
+            },
             None => match ty {
                 // Default Deltas
                 syn::Type::Path(syn::TypePath { path, .. }) => {
                     let segment = path.segments.first().unwrap();
                     let ident = segment.ident.clone();
                     match ident.to_string().as_str() {
-                        "i8" => quote! { i16 },
-                        "i16" => quote! { i32 },
-                        "i32" => quote! { i64 },
-                        "i64" => quote! { i64 },
+                        "i8" => "i16",
+                        "i16" => "i32",
+                        "i32" => "i64",
+                        "i64" => "i64",
+                        "u8" => "i16",
+                        "u16" => "i32",
+                        "u32" => "i64",
+                        "u64" => "i64",
+                        "i128" => "i128",
+                        // XOR columns keep the bits of the previous value and the window of the XOR
+                        "f32" => "i32",
+                        "f64" => "i64",
                         _ => panic!("Unsupported type"),
                     }
                 }
@@ -612,189 +722,1171 @@ pub fn derive_compressv2(tokens: TokenStream) -> TokenStream {
             },
             _ => panic!("Unsupported type"),
         })
-        .collect::<Vec<_>>();
+        .collect::<Vec<_>>()
+}
 
-    let double_col_tys = col_tys
+///
+/// The bit-width of a column type.
+///
+fn get_col_bits(ty: &syn::Type) -> u32 {
+    match ty {
+        syn::Type::Path(syn::TypePath { path, .. }) => {
+            let segment = path.segments.first().unwrap();
+            match segment.ident.to_string().as_str() {
+                "i8" | "u8" => 8,
+                "i16" | "u16" => 16,
+                "i32" | "u32" | "f32" => 32,
+                "i64" | "u64" | "f64" => 64,
+                "i128" => 128,
+                _ => panic!("Unsupported type"),
+            }
+        }
+        _ => panic!("Unsupported type"),
+    }
+}
+
+///
+/// Returns true if the column type is a float.
+///
+fn is_float_col(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(syn::TypePath { path, .. }) if path.is_ident("f32") || path.is_ident("f64"))
+}
+
+///
+/// The declared bit-width of each `#[tsz(wrapping = N)]` column narrower than the column.
+///
+fn get_wrapping_bits(col_tys: &[syn::Type], col_attrs: &[FieldOptions]) -> Vec<Option<u32>> {
+    col_attrs
         .iter()
-        .map(|ty| match ty {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! { i16 },
-                    "i16" => quote! { i32 },
-                    "i32" => quote! { i64 },
-                    "i64" => quote! { i128 },
-                    "i128" => quote! { i128 }, // Note i128 is not doubled
-                    _ => panic!("Unsupported type"),
-                }
+        .zip(col_tys)
+        .map(|(attr, ty)| {
+            let bits = attr.wrapping_bits?;
+            if bits == 0 || bits > get_col_bits(ty) {
+                panic!("The wrapping bit-width must be no wider than the column");
             }
-            _ => panic!("Unsupported type"),
+            (bits < get_col_bits(ty)).then_some(bits)
         })
-        .collect::<Vec<_>>();
+        .collect()
+}
 
-    // todo, make choice based on macro attributes for field, default to delta-delta and delta
-    let col_delta_buf = col_tys
+///
+/// The declared bit-width of each `#[tsz(bits = N)]` column narrower than the column.
+///
+fn get_declared_bits(col_tys: &[syn::Type], col_attrs: &[FieldOptions]) -> Vec<Option<u32>> {
+    col_attrs
         .iter()
-        .map(|ty| match ty {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! { Some(::tsz_compress::prelude::halfvec::HalfVec::new(prealloc_rows)) },
-                    "i16" => quote! { Some(::tsz_compress::prelude::halfvec::HalfVec::new(prealloc_rows)) },
-                    "i32" => quote! { Some(::tsz_compress::prelude::halfvec::HalfVec::new(prealloc_rows)) },
-                    "i64" => quote! { Some(::tsz_compress::prelude::halfvec::HalfVec::new(prealloc_rows)) },
-                    // "i64" => quote! { None },
-                    "i128" => quote! { None },
-                    _ => panic!("Unsupported type"),
-                }
+        .zip(col_tys)
+        .map(|(attr, ty)| {
+            let bits = attr.bits?;
+            let col_bits = get_col_bits(ty);
+            if col_bits > 64 {
+                panic!("A declared bit-width is only supported on columns of up to 64 bits");
             }
-            _ => panic!("Unsupported type"),
+            if bits == 0 || bits > col_bits {
+                panic!("The declared bit-width must be no wider than the column");
+            }
+            (bits < col_bits).then_some(bits)
         })
-        .collect::<Vec<_>>();
-    let col_delta_delta_buf = col_tys
+        .collect()
+}
+
+///
+/// The quantization of a `#[tsz(scale = ...)]` float column.
+///
+struct Quantization {
+    /// The float type of the field
+    float_ty: syn::Type,
+    scale: f64,
+    offset: f64,
+}
+
+///
+/// The column types with each scaled float replaced by the integer it is quantized to,
+/// and the quantization of each scaled float column.
+///
+fn get_quantized_cols(
+    col_tys: &[syn::Type],
+    col_attrs: &[FieldOptions],
+) -> (Vec<syn::Type>, Vec<Option<Quantization>>) {
+    col_tys
         .iter()
-        .map(|ty| match ty {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! { None },
-                    "i16" => quote! { None },
-                    "i32" => quote! { None },
-                    "i64" => quote! { None },
-                    // "i64" => quote! { Some(::tsz_compress::prelude::halfvec::HalfVec::new(prealloc_rows)) },
-                    "i128" => quote! { Some(::tsz_compress::prelude::halfvec::HalfVec::new(prealloc_rows)) },
-                    _ => panic!("Unsupported type"),
+        .zip(col_attrs)
+        .map(|(ty, attr)| {
+            let float = match ty {
+                syn::Type::Path(syn::TypePath { path, .. }) => {
+                    let segment = path.segments.first().unwrap();
+                    match segment.ident.to_string().as_str() {
+                        "f32" => Some("i32"),
+                        "f64" => Some("i64"),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            match (float, attr.scale) {
+                (Some(default_int), Some(scale)) => {
+                    let int = match attr.int.as_deref() {
+                        None => default_int,
+                        Some("\"i8\"") => "i8",
+                        Some("\"i16\"") => "i16",
+                        Some("\"i32\"") => "i32",
+                        Some("\"i64\"") => "i64",
+                        Some("\"u8\"") => "u8",
+                        Some("\"u16\"") => "u16",
+                        Some("\"u32\"") => "u32",
+                        Some("\"u64\"") => "u64",
+                        Some(_) => panic!("Unsupported integer for a float column"),
+                    };
+                    let quantization = Quantization {
+                        float_ty: ty.clone(),
+                        scale,
+                        offset: attr.offset.unwrap_or(0.0),
+                    };
+                    (syn::parse_str(int).unwrap(), Some(quantization))
                 }
+                (Some(_), None) => {
+                    if attr.delta.is_some() || attr.wrapping || attr.bits.is_some() {
+                        panic!("A float column without a scale is XOR encoded, it cannot choose a delta, wrap or declare a bit-width");
+                    }
+                    (ty.clone(), None)
+                }
+                (None, Some(_)) => panic!("Only float columns can be scaled"),
+                (None, None) => (ty.clone(), None),
             }
-            _ => panic!("Unsupported type"),
         })
-        .collect::<Vec<_>>();
+        .unzip()
+}
 
-    let write_first = col_tys
+///
+/// The schema code of a column or delta type, used by the stream header.
+///
+fn get_schema_code(name: &str) -> proc_macro2::TokenStream {
+    match name {
+        "i8" => quote! { ::tsz_compress::prelude::consts::schema::I8 },
+        "i16" => quote! { ::tsz_compress::prelude::consts::schema::I16 },
+        "i32" => quote! { ::tsz_compress::prelude::consts::schema::I32 },
+        "i64" => quote! { ::tsz_compress::prelude::consts::schema::I64 },
+        "i128" => quote! { ::tsz_compress::prelude::consts::schema::I128 },
+        "u8" => quote! { ::tsz_compress::prelude::consts::schema::U8 },
+        "u16" => quote! { ::tsz_compress::prelude::consts::schema::U16 },
+        "u32" => quote! { ::tsz_compress::prelude::consts::schema::U32 },
+        "u64" => quote! { ::tsz_compress::prelude::consts::schema::U64 },
+        "f32" => quote! { ::tsz_compress::prelude::consts::schema::F32 },
+        "f64" => quote! { ::tsz_compress::prelude::consts::schema::F64 },
+        _ => panic!("Unsupported type"),
+    }
+}
+
+///
+/// The schema byte of each column without the encoding bit: the column type and the delta type.
+///
+fn get_schema_col_codes(
+    col_tys: &[syn::Type],
+    delta_col_names: &[&str],
+) -> Vec<proc_macro2::TokenStream> {
+    col_tys
         .iter()
-        .map(|ty| match ty {
+        .zip(delta_col_names)
+        .map(|(ty, delta)| match ty {
             syn::Type::Path(syn::TypePath { path, .. }) => {
                 let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! { ::tsz_compress::prelude::write_i8_bits },
-                    "i16" => quote! { ::tsz_compress::prelude::write_i16_bits },
-                    "i32" => quote! { ::tsz_compress::prelude::write_i32_bits },
-                    "i64" => quote! { ::tsz_compress::prelude::write_i64_bits },
-                    "i128" => quote! { ::tsz_compress::prelude::write_i128_bits },
-                    _ => panic!("Unsupported type"),
-                }
+                let col = segment.ident.to_string();
+                let col_code = get_schema_code(col.as_str());
+                // XOR columns have no delta type
+                let delta_code = match col.as_str() {
+                    "f32" | "f64" => get_schema_code(col.as_str()),
+                    _ => get_schema_code(delta),
+                };
+                quote! { ((#col_code << 4) | #delta_code) }
             }
             _ => panic!("Unsupported type"),
         })
-        .collect::<Vec<_>>();
-    let write_second = col_tys
+        .collect::<Vec<_>>()
+}
+
+///
+/// One column of the compressor: the field, its resolved type and options,
+/// and the names of the compressor fields that hold its state.
+///
+struct ColumnSpec {
+    /// The field of the row
+    ident: syn::Ident,
+    /// The compressed type, which is the `T` of an `Option<T>` and the integer of a scaled float
+    ty: syn::Type,
+    /// The name of the compressed type
+    ty_name: String,
+    /// An `Option<T>` field, compressing only the present values of `T`
+    nullable: bool,
+    /// The quantization of a scaled float column
+    quantization: Option<Quantization>,
+    /// The declared bit-width of the values, if narrower than the column
+    declared_bits: Option<u32>,
+    /// Compute the deltas modulo the column width, or `wrapping_bits` if declared
+    wrapping: bool,
+    /// The declared bit-width of a wrapping counter, if narrower than the column
+    wrapping_bits: Option<u32>,
+    /// The nibbles of a float column without a scale, which is XOR encoded
+    xor_nibbles: Option<u32>,
+    /// The delta type, as named by `get_delta_col_names`
+    delta_name: &'static str,
+    delta_ty: syn::Ident,
+    /// The delta-delta is one bit-width wider than the delta, wrapping at 64 bits (or 128 bits for i128)
+    delta_delta_ty: proc_macro2::TokenStream,
+    /// The first delta is taken at double the bit-width of the column, except for i128
+    double_ty: proc_macro2::TokenStream,
+    delta_queue: syn::Ident,
+    delta_delta_queue: syn::Ident,
+    delta_buf: syn::Ident,
+    delta_delta_buf: syn::Ident,
+    prev: syn::Ident,
+    prev_double: syn::Ident,
+    prev_delta: syn::Ident,
+    /// Set when a delta narrower than the column overflowed, escaping the next row
+    escape: syn::Ident,
+    /// Set when the next packet can continue the deltas of the column
+    continues: syn::Ident,
+    present_rows: syn::Ident,
+    presence_buf: syn::Ident,
+    presence_run: syn::Ident,
+    presence_present: syn::Ident,
+}
+
+///
+/// The column of each field, with its type and options resolved once.
+///
+fn get_column_specs(columns: Vec<(syn::Ident, syn::Type, FieldOptions)>) -> Vec<ColumnSpec> {
+    let (col_idents, col_tys, col_attrs): (Vec<_>, Vec<_>, Vec<_>) = multiunzip(columns);
+
+    // Option<T> columns are nullable, compressing only the present values of T
+    let col_nullable = col_tys
         .iter()
-        .map(|ty| match ty {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! { ::tsz_compress::prelude::write_i16_bits },
-                    "i16" => quote! { ::tsz_compress::prelude::write_i32_bits },
-                    "i32" => quote! { ::tsz_compress::prelude::write_i64_bits },
-                    "i64" => quote! { ::tsz_compress::prelude::write_i128_bits },
-                    "i128" => quote! { ::tsz_compress::prelude::write_i128_bits },
-                    _ => panic!("Unsupported type"),
+        .map(|ty| get_option_inner_ty(ty).is_some())
+        .collect_vec();
+    let col_tys = col_tys
+        .iter()
+        .map(|ty| get_option_inner_ty(ty).unwrap_or_else(|| ty.clone()))
+        .collect_vec();
+
+    // Scaled float columns compress the integers they are quantized to
+    let (col_tys, col_quantizations) = get_quantized_cols(&col_tys, &col_attrs);
+    let delta_col_names = get_delta_col_names(&col_tys, &col_attrs);
+    let col_declared_bits = get_declared_bits(&col_tys, &col_attrs);
+    let col_wrapping_bits = get_wrapping_bits(&col_tys, &col_attrs);
+
+    izip!(
+        col_idents,
+        col_tys,
+        col_attrs,
+        col_nullable,
+        col_quantizations,
+        delta_col_names,
+        col_declared_bits,
+        col_wrapping_bits,
+    )
+    .map(
+        |(ident, ty, attr, nullable, quantization, delta_name, declared_bits, wrapping_bits)| {
+            let ty_name = match &ty {
+                syn::Type::Path(syn::TypePath { path, .. }) => {
+                    path.segments.first().unwrap().ident.to_string()
                 }
+                _ => panic!("Unsupported type"),
+            };
+            let xor_nibbles = match ty_name.as_str() {
+                "f32" => Some(8u32),
+                "f64" => Some(16u32),
+                _ => None,
+            };
+            let delta_delta_ty = match delta_name {
+                "i8" => quote! { i16 },
+                "i16" => quote! { i32 },
+                "i128" => quote! { i128 },
+                _ => quote! { i64 },
+            };
+            let double_ty = match ty_name.as_str() {
+                "i8" | "u8" => quote! { i16 },
+                "i16" | "u16" => quote! { i32 },
+                "i32" | "u32" => quote! { i64 },
+                "i64" | "u64" => quote! { i128 },
+                "i128" => quote! { i128 }, // Note i128 is not doubled
+                "f32" | "f64" => quote! { i64 }, // Note XOR columns have no deltas
+                _ => panic!("Unsupported type"),
+            };
+            ColumnSpec {
+                ty_name,
+                nullable,
+                quantization,
+                declared_bits,
+                wrapping: attr.wrapping,
+                wrapping_bits,
+                xor_nibbles,
+                delta_name,
+                delta_ty: format_ident!("{}", delta_name),
+                delta_delta_ty,
+                double_ty,
+                delta_queue: format_ident!("{}_delta_compressor_queue", ident),
+                delta_delta_queue: format_ident!("{}_delta_delta_compressor_queue", ident),
+                delta_buf: format_ident!("{}_delta_output_buffer", ident),
+                delta_delta_buf: format_ident!("{}_delta_delta_output_buffer", ident),
+                prev: format_ident!("prev_{}", ident),
+                prev_double: format_ident!("prev_double_{}", ident),
+                prev_delta: format_ident!("prev_delta_{}", ident),
+                escape: format_ident!("{}_escape_next", ident),
+                continues: format_ident!("{}_continues", ident),
+                present_rows: format_ident!("{}_present_rows", ident),
+                presence_buf: format_ident!("{}_presence_output_buffer", ident),
+                presence_run: format_ident!("{}_presence_run", ident),
+                presence_present: format_ident!("{}_presence_present", ident),
+                ident,
+                ty,
             }
+        },
+    )
+    .collect()
+}
+
+impl ColumnSpec {
+    ///
+    /// The value of the column in `row`, quantizing a scaled float.
+    ///
+    fn row_value(&self) -> proc_macro2::TokenStream {
+        let Self { ident, ty, .. } = self;
+        match (&self.quantization, self.nullable) {
+            (Some(Quantization { scale, offset, .. }), true) => quote! {
+                row.#ident.map(|value| ::tsz_compress::prelude::quantize(value as f64, #scale, #offset) as #ty)
+            },
+            (Some(Quantization { scale, offset, .. }), false) => quote! {
+                (::tsz_compress::prelude::quantize(row.#ident as f64, #scale, #offset) as #ty)
+            },
+            (None, _) => quote! { row.#ident },
+        }
+    }
+
+    ///
+    /// Writes the first row, and each escaped row, in the bit-width of the column.
+    ///
+    /// A declared bit-width writes just enough nibbles for it.
+    ///
+    fn write_first(&self) -> proc_macro2::TokenStream {
+        if let Some(bits) = self.declared_bits {
+            return quote! { ::tsz_compress::prelude::write_bits(outbuf, value as u64, #bits); };
+        }
+        let write_first = match self.ty_name.as_str() {
+            "i8" => quote! { ::tsz_compress::prelude::write_i8_bits },
+            "i16" => quote! { ::tsz_compress::prelude::write_i16_bits },
+            "i32" => quote! { ::tsz_compress::prelude::write_i32_bits },
+            "i64" => quote! { ::tsz_compress::prelude::write_i64_bits },
+            "i128" => quote! { ::tsz_compress::prelude::write_i128_bits },
+            "u8" => quote! { ::tsz_compress::prelude::write_u8_bits },
+            "u16" => quote! { ::tsz_compress::prelude::write_u16_bits },
+            "u32" => quote! { ::tsz_compress::prelude::write_u32_bits },
+            "u64" => quote! { ::tsz_compress::prelude::write_u64_bits },
+            // XOR columns write their own first row
+            "f32" => quote! { ::tsz_compress::prelude::write_u32_bits },
+            "f64" => quote! { ::tsz_compress::prelude::write_u64_bits },
+            _ => panic!("Unsupported type"),
+        };
+        quote! { #write_first(outbuf, value); }
+    }
+
+    ///
+    /// Writes the first delta in double the bit-width of the column.
+    ///
+    /// A declared bit-width writes the first delta in one bit more.
+    ///
+    fn write_second(&self) -> proc_macro2::TokenStream {
+        if let Some(bits) = self.declared_bits {
+            let bits = bits + 1;
+            return quote! { ::tsz_compress::prelude::write_bits(outbuf, delta as u64, #bits); };
+        }
+        let write_second = match self.ty_name.as_str() {
+            "i8" | "u8" => quote! { ::tsz_compress::prelude::write_i16_bits },
+            "i16" | "u16" => quote! { ::tsz_compress::prelude::write_i32_bits },
+            "i32" | "u32" => quote! { ::tsz_compress::prelude::write_i64_bits },
+            "i64" | "u64" | "i128" => quote! { ::tsz_compress::prelude::write_i128_bits },
+            // XOR columns have no second row
+            "f32" => quote! { ::tsz_compress::prelude::write_i64_bits },
+            "f64" => quote! { ::tsz_compress::prelude::write_i128_bits },
             _ => panic!("Unsupported type"),
+        };
+        quote! { #write_second(outbuf, delta); }
+    }
+
+    ///
+    /// The nibbles of the first row and the first delta, as written by `write_first` and `write_second`.
+    ///
+    fn row_nibbles(&self) -> (usize, usize) {
+        if let Some(bits) = self.declared_bits {
+            return (bits.div_ceil(4) as usize, (bits + 1).div_ceil(4) as usize);
+        }
+        match self.ty_name.as_str() {
+            "i8" | "u8" => (2, 4),
+            "i16" | "u16" => (4, 8),
+            "i32" | "u32" | "f32" => (8, 16),
+            "i64" | "u64" | "f64" => (16, 32),
+            "i128" => (32, 32),
+            _ => panic!("Unsupported type"),
+        }
+    }
+
+    ///
+    /// Sign extends each delta of a wrapping column from its declared bit-width.
+    ///
+    fn wrap_delta(&self) -> proc_macro2::TokenStream {
+        match self.wrapping_bits {
+            Some(bits) => {
+                let shift = get_col_bits(&self.ty) - bits;
+                quote! { let delta = (delta << #shift) >> #shift; }
+            }
+            None => quote! {},
+        }
+    }
+
+    ///
+    /// Returns true if a delta can overflow the delta type, so each delta is checked and escaped if it does not fit.
+    ///
+    /// The deltas of values of a declared bit-width need one bit more, but always wrap at the column width.
+    ///
+    fn is_narrow_delta(&self) -> bool {
+        let delta_bits = match self.delta_name {
+            "i8" => 8,
+            "i16" => 16,
+            "i32" => 32,
+            "i64" => 64,
+            _ => 128,
+        };
+        let col_bits = get_col_bits(&self.ty);
+        delta_bits
+            < self
+                .declared_bits
+                .map_or(col_bits, |bits| (bits + 1).min(col_bits))
+    }
+
+    ///
+    /// The shift that truncates a value to its declared bit-width, sign extending signed columns.
+    ///
+    fn declared_shift(&self) -> Option<u32> {
+        self.declared_bits.map(|bits| get_col_bits(&self.ty) - bits)
+    }
+
+    ///
+    /// Checks that the value of the column is within its declared bit-width, and that a scaled float fits its integer.
+    ///
+    /// Returns None for columns that accept every value.
+    ///
+    fn range_check(&self) -> Option<proc_macro2::TokenStream> {
+        let Self { ident, ty, .. } = self;
+        let shift = self.declared_shift();
+        if shift.is_none() && self.quantization.is_none() {
+            return None;
+        }
+        let name = ident.to_string();
+        let value = match self.nullable {
+            true => quote! { row.#ident },
+            false => quote! { Some(row.#ident) },
+        };
+        // Casting to the integer column saturates a quantized float outside of the column, and NaN
+        let quantized_check = match &self.quantization {
+            Some(Quantization { scale, offset, .. }) => quote! {
+                let quantized = ::tsz_compress::prelude::quantize(value as f64, #scale, #offset);
+                if !(quantized >= <#ty>::MIN as f64 && quantized < <#ty>::MAX as f64 + 1.0) {
+                    return Err(::tsz_compress::prelude::CodingError::ValueOutOfRange(#name));
+                }
+                let value = quantized as #ty;
+            },
+            None => quote! {},
+        };
+        let declared_check = match shift {
+            Some(shift) => quote! {
+                if (value << #shift) >> #shift != value {
+                    return Err(::tsz_compress::prelude::CodingError::ValueOutOfRange(#name));
+                }
+            },
+            None => quote! {},
+        };
+        Some(quote! {
+            if let Some(value) = #value {
+                #quantized_check
+                #declared_check
+            }
         })
-        .collect::<Vec<_>>();
-    let prev_col_idents = col_idents
+    }
+
+    ///
+    /// Queues the delta of an integer column, emitting the queue when it is full.
+    ///
+    /// i128 columns are only delta-delta encoded, and float columns write their own XOR.
+    ///
+    fn delta_comp_block(&self) -> proc_macro2::TokenStream {
+        let Self {
+            delta_buf,
+            delta_queue,
+            ..
+        } = self;
+        match self.ty_name.as_str() {
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => quote! {
+                debug_assert!(self.#delta_buf.is_some());
+                let outbuf = unsafe { self.#delta_buf.as_mut().unwrap_unchecked() };
+                self.#delta_queue.push(delta);
+                if self.#delta_queue.is_full() {
+                    self.#delta_queue.emit_delta_bits(outbuf);
+                }
+            },
+            "i128" | "f32" | "f64" => quote! {},
+            _ => panic!("Unsupported type"),
+        }
+    }
+
+    ///
+    /// Queues the delta-delta of an integer column, emitting the queue when it is full.
+    ///
+    fn delta_delta_comp_block(&self) -> proc_macro2::TokenStream {
+        let Self {
+            delta_delta_buf,
+            delta_delta_queue,
+            prev_delta,
+            delta_delta_ty,
+            ..
+        } = self;
+        match self.ty_name.as_str() {
+            "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => quote! {
+                debug_assert!(self.#delta_delta_buf.is_some());
+                let outbuf = unsafe { self.#delta_delta_buf.as_mut().unwrap_unchecked() };
+                let delta_delta = (delta as #delta_delta_ty).wrapping_sub(self.#prev_delta as #delta_delta_ty);
+                self.#delta_delta_queue.push(delta_delta);
+                if self.#delta_delta_queue.is_full() {
+                    self.#delta_delta_queue.emit_delta_delta_bits(outbuf);
+                }
+            },
+            "i128" => quote! {
+                debug_assert!(self.#delta_delta_buf.is_some());
+                let outbuf = unsafe { self.#delta_delta_buf.as_mut().unwrap_unchecked() };
+                // The queue cannot hold 128-bit values, so write each delta-delta directly
+                let delta_delta = (delta as i128).wrapping_sub(self.#prev_delta as i128);
+                ::tsz_compress::prelude::write_i128_delta_delta_bits(outbuf, delta_delta);
+            },
+            "f32" | "f64" => quote! {},
+            _ => panic!("Unsupported type"),
+        }
+    }
+
+    ///
+    /// Compresses the value of the column in `row`, by the number of values the column has seen.
+    ///
+    fn compress_block(&self, options: &StructOptions) -> proc_macro2::TokenStream {
+        let Self {
+            ident: col_ident,
+            delta_buf: col_delta_buf_ident,
+            delta_delta_buf: col_delta_delta_buf_ident,
+            delta_queue: col_delta_comp_queue_ident,
+            delta_delta_queue: col_delta_delta_comp_queue_ident,
+            prev: prev_col_ident,
+            prev_double: prev_double_col_ident,
+            prev_delta: prev_delta_ident,
+            escape: col_escape_ident,
+            continues: col_continues_ident,
+            delta_ty: delta_col_ty,
+            double_ty: double_col_ty,
+            ..
+        } = self;
+        let row_value = self.row_value();
+        let write_first = self.write_first();
+        let write_second = self.write_second();
+        let (first_nibbles, second_nibbles) = self.row_nibbles();
+        let wrap_delta = self.wrap_delta();
+        let delta_comp_block = self.delta_comp_block();
+        let delta_delta_comp_block = self.delta_delta_comp_block();
+        let narrow_delta = self.is_narrow_delta();
+        // A continued column starts with a tag in place of the first and second rows
+        let (continue_block, continues) = if options.continuation {
+            (
+                quote! {
+                    if rows == 1 && self.#col_continues_ident {
+                        if let Some(outbuf) = self.#col_delta_buf_ident.as_mut() {
+                            outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                            outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::CONTINUE_DELTA));
+                        }
+                        if let Some(outbuf) = self.#col_delta_delta_buf_ident.as_mut() {
+                            outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                            outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::CONTINUE_DELTA_DELTA));
+                        }
+                    }
+                },
+                quote! { rows > 2 || self.#col_continues_ident },
+            )
+        } else {
+            (quote! {}, quote! { rows > 2 })
+        };
+        let delta_block = quote! {
+            // The new delta  and delta-delta
+            // Unsigned columns are reinterpreted as signed, so the delta must wrap
+            let delta = col.wrapping_sub(self.#prev_col_ident);
+            #wrap_delta
+
+            // Do delta compression if configured
+            #delta_comp_block
+
+            // Do delta-delta compression if configured
+            #delta_delta_comp_block
+
+            // Update the previous values
+            self.#prev_col_ident = col;
+            self.#prev_delta_ident = delta;
+        };
+        let delta_block = if narrow_delta {
+            quote! {
+                // The delta of the full bit-width values, which wraps only for i128
+                let wide_delta = (value as #double_col_ty).wrapping_sub(self.#prev_double_col_ident);
+                self.#prev_double_col_ident = value as #double_col_ty;
+                if self.#col_escape_ident
+                    || wide_delta < #delta_col_ty::MIN as #double_col_ty
+                    || wide_delta > #delta_col_ty::MAX as #double_col_ty
+                {
+                    // The delta does not fit the chosen bit-width, so restart the column at the full bit-width value
+                    if let Some(outbuf) = self.#col_delta_buf_ident.as_mut() {
+                        while !self.#col_delta_comp_queue_ident.is_flushed() {
+                            self.#col_delta_comp_queue_ident.flush_delta_bits(outbuf);
+                        }
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::ESCAPE_DELTA));
+                        #write_first
+                    }
+                    if let Some(outbuf) = self.#col_delta_delta_buf_ident.as_mut() {
+                        while self.#col_delta_delta_comp_queue_ident.len() > 0 {
+                            self.#col_delta_delta_comp_queue_ident.emit_delta_delta_bits(outbuf);
+                        }
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::ESCAPE_DELTA_DELTA));
+                        #write_first
+                    }
+
+                    // The escaped row has no delta
+                    self.#col_escape_ident = false;
+                    self.#prev_col_ident = col;
+                    self.#prev_delta_ident = 0;
+                } else {
+                    #delta_block
+                }
+            }
+        } else {
+            delta_block
+        };
+        // A first delta too wide for the chosen bit-width cannot be the previous delta of a delta-delta
+        let second_row_escape = if narrow_delta {
+            quote! {
+                self.#col_escape_ident = delta < #delta_col_ty::MIN as #double_col_ty || delta > #delta_col_ty::MAX as #double_col_ty;
+                self.#prev_double_col_ident = col;
+            }
+        } else {
+            quote! {}
+        };
+        // A wrapping column wraps its first delta too
+        let second_delta = if self.wrapping {
+            quote! {
+                let delta = (value as #delta_col_ty).wrapping_sub(self.#prev_double_col_ident as #delta_col_ty);
+                #wrap_delta
+                let delta = delta as #double_col_ty;
+            }
+        } else {
+            quote! { let delta = col.wrapping_sub(self.#prev_double_col_ident); }
+        };
+        // A value outside of its declared bit-width is a bug in the caller, but cannot be written as it is
+        let declared_value = match self.declared_shift() {
+            Some(shift) => {
+                let message = format!(
+                    "value of {} is outside of its declared bit-width",
+                    col_ident
+                );
+                quote! {
+                    debug_assert!((value << #shift) >> #shift == value, #message);
+                    let value = (value << #shift) >> #shift;
+                }
+            }
+            None => quote! {},
+        };
+        let col_block = match self.xor_nibbles {
+            Some(nibbles) => {
+                let bits_ty = format_ident!("u{}", 4 * nibbles);
+                let bits = 4 * nibbles;
+                // An XOR column continues from its second row, it has no first delta
+                let xor_continues = match options.continuation {
+                    true => quote! { rows > 1 || self.#col_continues_ident },
+                    false => quote! { rows > 1 },
+                };
+                quote! {
+                    #continue_block
+                    let bits = value.to_bits();
+                    debug_assert!(self.#col_delta_buf_ident.is_some());
+                    let outbuf = unsafe { self.#col_delta_buf_ident.as_mut().unwrap_unchecked() };
+                    if #xor_continues {
+                        // The window of meaningful nibbles of the last XOR is kept as the previous delta
+                        let xor = (bits ^ self.#prev_col_ident as #bits_ty) as u64;
+                        self.#prev_delta_ident = ::tsz_compress::prelude::write_xor_bits(outbuf, xor, self.#prev_delta_ident as u16, #nibbles) as #delta_col_ty;
+                    } else {
+                        // Write out the bits of the float
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::FIRST_ROW));
+                        ::tsz_compress::prelude::write_bits(outbuf, bits as u64, #bits);
+                        self.#prev_delta_ident = 0;
+                    }
+                    self.#prev_col_ident = bits as #delta_col_ty;
+                }
+            }
+            None => quote! {
+            #declared_value
+            #continue_block
+            if #continues {
+                let col = value as #delta_col_ty;
+                #delta_block
+            } else if rows == 1 {
+                // Write out the value as a varint if it is shorter, otherwise in the exact bit-width of the column.
+                if let Some(outbuf) = self.#col_delta_buf_ident.as_mut() {
+                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                    if !::tsz_compress::prelude::write_varint_row(outbuf, ::tsz_compress::prelude::consts::headers::FIRST_ROW_VARINT, value as i128, #first_nibbles) {
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::FIRST_ROW));
+                        #write_first
+                    }
+                }
+                if let Some(outbuf) = self.#col_delta_delta_buf_ident.as_mut() {
+                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                    if !::tsz_compress::prelude::write_varint_row(outbuf, ::tsz_compress::prelude::consts::headers::FIRST_ROW_DELTA_DELTA_VARINT, value as i128, #first_nibbles) {
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::FIRST_ROW_DELTA_DELTA));
+                        #write_first
+                    }
+                }
+                self.#prev_double_col_ident = value as #double_col_ty;
+            } else if rows == 2 {
+                // Write out the delta as a varint if it is shorter, otherwise in the next exact bit-width of the column,
+                // regardless of chosen delta bit-width.
+                // Up cast to double bit-width always for the first delta
+                // i128 is not doubled, so its first delta may wrap
+                let col = value as #double_col_ty;
+                #second_delta
+                if let Some(outbuf) = self.#col_delta_buf_ident.as_mut() {
+                    if !::tsz_compress::prelude::write_varint_row(outbuf, ::tsz_compress::prelude::consts::headers::SECOND_ROW_VARINT, delta as i128, #second_nibbles) {
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::SECOND_ROW));
+                        #write_second
+                    }
+                }
+                if let Some(outbuf) = self.#col_delta_delta_buf_ident.as_mut() {
+                    if !::tsz_compress::prelude::write_varint_row(outbuf, ::tsz_compress::prelude::consts::headers::SECOND_ROW_VARINT, delta as i128, #second_nibbles) {
+                        outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::SECOND_ROW));
+                        #write_second
+                    }
+                }
+
+                // Use choice of bit-width for delta/delta-delta compression
+                self.#prev_delta_ident = delta as #delta_col_ty;
+                self.#prev_col_ident = col as #delta_col_ty;
+                #second_row_escape
+            }
+            },
+        };
+
+        if self.nullable {
+            let Self {
+                present_rows: present_rows_ident,
+                presence_buf: presence_buf_ident,
+                presence_run: presence_run_ident,
+                presence_present: presence_present_ident,
+                ..
+            } = self;
+            quote! {
+                // Close the run when the column switches between present and absent
+                if row.#col_ident.is_some() != self.#presence_present_ident {
+                    ::tsz_compress::prelude::write_nibble_varint(&mut self.#presence_buf_ident, self.#presence_run_ident as u64);
+                    self.#presence_present_ident = !self.#presence_present_ident;
+                    self.#presence_run_ident = 0;
+                }
+                self.#presence_run_ident += 1;
+
+                // Only the present values are compressed
+                if let Some(value) = #row_value {
+                    self.#present_rows_ident += 1;
+                    let rows = self.#present_rows_ident;
+                    #col_block
+                }
+            }
+        } else {
+            quote! {
+                {
+                    let value = #row_value;
+                    let rows = self.rows;
+                    #col_block
+                }
+            }
+        }
+    }
+}
+
+///
+/// CompressV2 is a procedural macro that will inspect the fields of
+/// a struct and generate a StructCompressor with statically sized columnar
+/// compression for the fields.
+///
+#[proc_macro_derive(CompressV2, attributes(tsz))]
+pub fn derive_compressv2(tokens: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(tokens as syn::DeriveInput);
+    let ident = input.ident.clone();
+
+    // We will define a struct by this name
+    let compressor_ident = format_ident!("{}CompressorImpl", input.ident);
+    let array_compressor_ident = format_ident!("{}ArrayCompressorImpl", input.ident);
+    let storage_compressor_ident = format_ident!("{}StorageCompressorImpl", input.ident);
+    let checkpoint_ident = format_ident!("{}CompressorCheckpoint", input.ident);
+
+    // We will compress each of the fields as columns
+    let options = get_options_of_struct(&input);
+    let columns = get_column_specs(get_fields_of_struct(input));
+    let num_columns = columns.len();
+
+    // The fields of the compressor that hold the state of each column
+    let col_delta_comp_queue_idents = columns.iter().map(|col| &col.delta_queue).collect_vec();
+    let col_delta_delta_comp_queue_idents = columns
+        .iter()
+        .map(|col| &col.delta_delta_queue)
+        .collect_vec();
+    let col_delta_buf_idents = columns.iter().map(|col| &col.delta_buf).collect_vec();
+    let col_delta_delta_buf_idents = columns.iter().map(|col| &col.delta_delta_buf).collect_vec();
+    let prev_col_idents = columns.iter().map(|col| &col.prev).collect_vec();
+    let prev_double_col_idents = columns.iter().map(|col| &col.prev_double).collect_vec();
+    let prev_delta_idents = columns.iter().map(|col| &col.prev_delta).collect_vec();
+    let col_escape_idents = columns.iter().map(|col| &col.escape).collect_vec();
+    let delta_col_tys = columns.iter().map(|col| &col.delta_ty).collect_vec();
+    let double_col_tys = columns.iter().map(|col| &col.double_ty).collect_vec();
+
+    // Nullable columns also track the runs of present and absent rows
+    let nullable_columns = columns.iter().filter(|col| col.nullable).collect_vec();
+    let col_present_rows_idents = nullable_columns
         .iter()
-        .map(|ident| format_ident!("prev_{}", ident))
+        .map(|col| &col.present_rows)
         .collect_vec();
-    let prev_double_col_idents = col_idents
+    let col_presence_buf_idents = nullable_columns
         .iter()
-        .map(|ident| format_ident!("prev_double_{}", ident))
+        .map(|col| &col.presence_buf)
         .collect_vec();
-    let prev_delta_idents = col_idents
+    let col_presence_run_idents = nullable_columns
         .iter()
-        .map(|ident| format_ident!("prev_delta_{}", ident))
+        .map(|col| &col.presence_run)
         .collect_vec();
+    let col_presence_present_idents = nullable_columns
+        .iter()
+        .map(|col| &col.presence_present)
+        .collect_vec();
+    let num_physical_columns = num_columns + nullable_columns.len();
 
-    // Do delta compression
-    let delta_comp_block = izip!(col_tys.iter(), col_delta_buf_idents.iter(), col_delta_comp_queue_idents.iter())
-        .map(|(ty, col_delta_buf_idents,  col_delta_comp_queue_idents)|  match ty {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! {
-                        debug_assert!(self.#col_delta_buf_idents.is_some());
-                        let outbuf = unsafe { self.#col_delta_buf_idents.as_mut().unwrap_unchecked() };
-                        self.#col_delta_comp_queue_idents.push(delta);
-                        if self.#col_delta_comp_queue_idents.is_full() {
-                            self.#col_delta_comp_queue_idents.emit_delta_bits(outbuf);
-                        }
-                    },
-                    "i16" => quote! {
-                        debug_assert!(self.#col_delta_buf_idents.is_some());
-                        let outbuf = unsafe { self.#col_delta_buf_idents.as_mut().unwrap_unchecked() };
-                        self.#col_delta_comp_queue_idents.push(delta);
-                        if self.#col_delta_comp_queue_idents.is_full() {
-                            self.#col_delta_comp_queue_idents.emit_delta_bits(outbuf);
-                        }
-                    },
-                    "i32" => quote! {
-                        debug_assert!(self.#col_delta_buf_idents.is_some());
-                        let outbuf = unsafe { self.#col_delta_buf_idents.as_mut().unwrap_unchecked() };
-                        self.#col_delta_comp_queue_idents.push(delta);
-                        if self.#col_delta_comp_queue_idents.is_full() {
-                            self.#col_delta_comp_queue_idents.emit_delta_bits(outbuf);
-                        }
-                    },
-                    "i64" => quote! {
-                        debug_assert!(self.#col_delta_buf_idents.is_some());
-                        let outbuf = unsafe { self.#col_delta_buf_idents.as_mut().unwrap_unchecked() };
-                        self.#col_delta_comp_queue_idents.push(delta);
-                        if self.#col_delta_comp_queue_idents.is_full() {
-                            self.#col_delta_comp_queue_idents.emit_delta_bits(outbuf);
-                        }
-                    },
-                    "i128" => quote! { },
-                    _ => panic!("Unsupported type"),
-                }
+    // Continuation columns remember whether the next packet can continue their deltas
+    let col_continues_idents = columns.iter().map(|col| &col.continues).collect_vec();
+    let col_packet_rows = columns
+        .iter()
+        .map(|col| match col.nullable {
+            true => {
+                let present_rows_ident = &col.present_rows;
+                quote! { self.#present_rows_ident }
             }
-            _ => panic!("Unsupported type"),
+            false => quote! { self.rows },
+        })
+        .collect_vec();
+
+    // i128 columns are always delta-delta encoded, and float columns are XOR encoded in their delta buffer
+    let new_buf = quote! { Some(::tsz_compress::prelude::halfvec::HalfVec::with_storage(Storage::with_capacity(prealloc_rows))) };
+    let col_delta_buf = columns
+        .iter()
+        .map(|col| match get_col_bits(&col.ty) == 128 {
+            true => quote! { None },
+            false => new_buf.clone(),
+        })
+        .collect::<Vec<_>>();
+    let col_delta_delta_buf = columns
+        .iter()
+        .map(|col| match is_float_col(&col.ty) {
+            true => quote! { None },
+            false => new_buf.clone(),
         })
         .collect::<Vec<_>>();
 
-    // Do delta-delta compression
-    let delta_delta_comp_block = izip!(col_tys.iter(), col_delta_delta_buf_idents.iter(), prev_delta_idents.iter(), col_delta_delta_comp_queue_idents.iter())
-        .map(|(ty, col_delta_delta_buf_idents, prev_delta_idents, col_delta_delta_comp_queue_idents)|  match ty {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! { },
-                    "i16" => quote! { },
-                    "i32" => quote! { },
-                    "i64" => quote! { },
-                    "i128" => quote! {
-                        debug_assert!(self.#col_delta_delta_buf_idents.is_some());
-                        let outbuf = unsafe { self.#col_delta_delta_buf_idents.as_mut().unwrap_unchecked() };
-                        let delta_delta = delta - self.#prev_delta_idents;
-                        self.#col_delta_delta_comp_queue_idents.push(delta_delta);
-                        if self.#col_delta_delta_comp_queue_idents.is_full() {
-                            self.#col_delta_delta_comp_queue_idents.emit_delta_delta_bits(outbuf);
-                        }
+    // Values outside of a declared bit-width, or scaled floats outside of their integer, are refused by try_compress
+    let col_range_checks = columns
+        .iter()
+        .filter_map(ColumnSpec::range_check)
+        .collect_vec();
+
+    // Compress each column by the number of values it has seen
+    let col_compress_blocks = columns
+        .iter()
+        .map(|col| col.compress_block(&options))
+        .collect_vec();
+
+    // Optionally describe the columns and their chosen encoding ahead of the row count
+    let header_nibbles = if options.header {
+        2 * (2 + num_physical_columns)
+    } else {
+        0
+    };
+    let header_len = header_nibbles / 2;
+    let sequence_len = if options.continuation { 2 } else { 0 };
+    let prefix_len = header_len + sequence_len;
+    let crc_len = if options.crc { 4usize } else { 0 };
+
+    // Deltas wait in a longer queue when looking ahead for the packing with the fewest nibbles
+    let delta_queue_len = if options.lookahead { 16usize } else { 10 };
+    let col_tys = columns.iter().map(|col| col.ty.clone()).collect_vec();
+    let delta_col_names = columns.iter().map(|col| col.delta_name).collect_vec();
+    let schema_col_codes = get_schema_col_codes(&col_tys, &delta_col_names);
+    let schema_presence_codes = columns
+        .iter()
+        .map(|col| match col.nullable {
+            true => quote! { ::tsz_compress::prelude::consts::schema::PRESENCE << 4, },
+            false => quote! {},
+        })
+        .collect_vec();
+    let header_words = if options.header {
+        if num_physical_columns > u8::MAX as usize {
+            panic!("A stream header supports at most 255 columns");
+        }
+        quote! {
+            let columns = [
+                #(
+                    #schema_presence_codes
+                    #schema_col_codes | match (self.#col_delta_buf_idents.as_ref(), self.#col_delta_delta_buf_idents.as_ref()) {
+                        (Some(delta_buffer), Some(delta_delta_buffer)) if delta_delta_buffer.len() < delta_buffer.len() => ::tsz_compress::prelude::consts::schema::DELTA_DELTA,
+                        (Some(_), _) => 0,
+                        (None, _) => ::tsz_compress::prelude::consts::schema::DELTA_DELTA,
                     },
-                    _ => panic!("Unsupported type"),
-                }
+                )*
+            ];
+            ::tsz_compress::prelude::write_header(&mut prefix, &columns);
+        }
+    } else {
+        quote! {}
+    };
+
+    // The presence column of a nullable column precedes its values
+    let col_presence_words = columns
+        .iter()
+        .map(|col| match col.nullable {
+            true => {
+                let presence_buf_ident = &col.presence_buf;
+                quote! { Some(&self.#presence_buf_ident).into_iter(), }
             }
-            _ => panic!("Unsupported type"),
+            false => quote! {},
         })
-        .collect::<Vec<_>>();
+        .collect_vec();
+
+    // Flush the queues so that every word is in the buffers
+    let finish_flush = quote! {
+        // Guarantee that at least the column start nibble is emitted
+        #(
+            if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
+                if outbuf.is_empty() {
+                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                }
+            }
+            if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
+                if outbuf.is_empty() {
+                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                }
+            }
+        )*
+
+        // Flush any pending samples in the queues
+        // All of the bits are concatenated with a 1001 tag indicating the start of a new column
+        #(
+            if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
+                while !self.#col_delta_comp_queue_idents.is_flushed() {
+                    self.#col_delta_comp_queue_idents.flush_delta_bits(outbuf);
+                }
+            }
+            if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
+                while self.#col_delta_delta_comp_queue_idents.len() > 0 {
+                    self.#col_delta_delta_comp_queue_idents.emit_delta_delta_bits(outbuf);
+                }
+            }
+        )*
+
+        // Close the last run of each presence column
+        if self.rows > 0 {
+            #(
+                ::tsz_compress::prelude::write_nibble_varint(&mut self.#col_presence_buf_idents, self.#col_presence_run_idents as u64);
+            )*
+        }
+
+    };
+
+    // Continuation packets count the packets, marking those that continue from the previous packet
+    let write_sequence = if options.continuation {
+        quote! {
+            let sequence = match self.continued {
+                true => self.sequence | ::tsz_compress::prelude::consts::sequence::CONTINUATION,
+                false => self.sequence,
+            };
+            prefix[#header_len..].copy_from_slice(&sequence.to_be_bytes());
+        }
+    } else {
+        quote! {}
+    };
+
+    // The next packet continues the columns with a delta, unless a keyframe was requested
+    // A packet that is dropped or never sent leaves nothing to continue from
+    let (finish_continue, finish_keyframe) = if options.continuation {
+        (
+            quote! {
+                if self.keyframe_requested {
+                    #( self.#col_continues_idents = false; )*
+                    self.continued = false;
+                    self.keyframe_requested = false;
+                } else {
+                    #( self.#col_continues_idents = self.#col_continues_idents || #col_packet_rows >= 2; )*
+                    self.continued = true;
+                }
+                self.sequence = self.sequence.wrapping_add(1) & ::tsz_compress::prelude::consts::sequence::MASK;
+            },
+            quote! {
+                #( self.#col_continues_idents = false; )*
+                self.continued = false;
+                self.keyframe_requested = false;
+            },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    // The state kept between continuation packets
+    let (
+        continuation_fields,
+        continuation_init,
+        continuation_write,
+        continuation_read,
+        continuation_copy,
+        continuation_methods,
+    ) = if options.continuation {
+        (
+            quote! {
+                #( #col_continues_idents: bool,)*
+                continued: bool,
+                keyframe_requested: bool,
+                sequence: u16,
+            },
+            quote! {
+                #( #col_continues_idents: false,)*
+                continued: false,
+                keyframe_requested: false,
+                sequence: 0,
+            },
+            quote! {
+                #( writer.write_bool(self.#col_continues_idents); )*
+                writer.write_bool(self.continued);
+                writer.write_bool(self.keyframe_requested);
+                writer.write_usize(self.sequence as usize);
+            },
+            quote! {
+                #( self.#col_continues_idents = reader.read_bool()?; )*
+                self.continued = reader.read_bool()?;
+                self.keyframe_requested = reader.read_bool()?;
+                self.sequence = reader.read_usize()? as u16 & ::tsz_compress::prelude::consts::sequence::MASK;
+            },
+            quote! {
+                #( #col_continues_idents: self.#col_continues_idents,)*
+                continued: self.continued,
+                keyframe_requested: self.keyframe_requested,
+                sequence: self.sequence,
+            },
+            quote! {
+                ///
+                /// Makes the packet after the one being compressed a keyframe,
+                /// which decodes without the packets before it.
+                ///
+                /// The first packet, and the packet after a dropped packet, are always keyframes.
+                ///
+                pub fn request_keyframe(&mut self) {
+                    self.keyframe_requested = true;
+                }
+
+                ///
+                /// The sequence number of the packet being compressed.
+                ///
+                pub fn sequence(&self) -> u16 {
+                    self.sequence
+                }
+            },
+        )
+    } else {
+        (
+            quote! {},
+            quote! {},
+            quote! {},
+            quote! {},
+            quote! {},
+            quote! {},
+        )
+    };
+
+    // The byte aligned header and sequence field, then the row count and the words of the columns,
+    // picking the smaller of the delta and delta-delta buffers for each column
+    let finish_words = quote! {
+        let mut prefix = [0u8; #prefix_len];
+        #header_words
+        #write_sequence
+
+        // Write the number of rows as a nibble varint ahead of the columns
+        // The decompressor will read this value and reserve space for the rows
+        ::tsz_compress::prelude::write_nibble_varint(&mut self.row_count_buffer, self.rows as u64);
+
+        // Create an iterator over the words to be written
+        // Only use one encoding mechanism per column, preferring delta on ties
+        let words = [
+            Some(&self.row_count_buffer).into_iter(),
+            #(
+                #col_presence_words
+                match (self.#col_delta_buf_idents.as_ref(), self.#col_delta_delta_buf_idents.as_ref()) {
+                    (Some(delta_buffer), Some(delta_delta_buffer)) if delta_delta_buffer.len() < delta_buffer.len() => Some(delta_delta_buffer),
+                    (Some(delta_buffer), _) => Some(delta_buffer),
+                    (None, delta_delta_buffer) => delta_delta_buffer,
+                }.into_iter(),
+            )*
+        ].into_iter().flatten();
+    };
+
+    // The checksum of every byte of the packet follows the columns
+    let (finish_start, finish_crc) = if options.crc {
+        (
+            quote! { let start = output_bytes.len(); },
+            quote! {
+                let crc = ::tsz_compress::prelude::crc32(&output_bytes[start..]);
+                output_bytes.extend_from_slice(&crc.to_be_bytes());
+            },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    let finish_slice_crc = if options.crc {
+        quote! {
+            let crc = ::tsz_compress::prelude::crc32(&output_bytes[..written]);
+            output_bytes[written..written + 4].copy_from_slice(&crc.to_be_bytes());
+            let written = written + 4;
+        }
+    } else {
+        quote! {}
+    };
+
+    // Leave the buffers in a reserved, cleared state for the next packet
+    let finish_clear = quote! {
+        #(
+            if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
+                outbuf.clear();
+            }
+            if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
+                outbuf.clear();
+            }
+        )*
+        #(
+            self.#col_presence_buf_idents.clear();
+            self.#col_presence_buf_idents.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+            self.#col_presence_run_idents = 0;
+            self.#col_presence_present_idents = true;
+            self.#col_present_rows_idents = 0;
+        )*
+        self.row_count_buffer.clear();
+        self.rows = 0;
+    };
 
     let finish_into_thin = if cfg!(feature = "thin-vec") {
         quote! {
@@ -805,118 +1897,302 @@ pub fn derive_compressv2(tokens: TokenStream) -> TokenStream {
             /// Leaving the intermediate buffers in a reserved, cleared state.
             ///
             fn finish_into_thin(&mut self, output_bytes: &mut ::thin_vec::ThinVec<u8>) {
-                // Only use one encoding mechanism
-                #(
-                    if let (Some(delta_buffer), Some(delta_delta_buffer)) = (&self.#col_delta_buf_idents, &self.#col_delta_delta_buf_idents) {
-                        // Prefer delta on ties
-                        if delta_delta_buffer.len() >= delta_buffer.len() {
-                            self.#col_delta_delta_buf_idents = None;
-                        } else {
-                            self.#col_delta_buf_idents = None;
-                        }
+                #finish_flush
+
+                // A packet missing the words of an overflowed buffer is dropped
+                if !self.is_overflowed() {
+                    #finish_words
+
+                    // Pack the words into nibbles
+                    #finish_start
+                    output_bytes.extend_from_slice(&prefix);
+                    ::tsz_compress::prelude::halfvec::HalfVec::finish_thin(output_bytes, words);
+                    #finish_crc
+                    #finish_continue
+                } else {
+                    #finish_keyframe
+                }
+
+                // Clear the buffers for re-use
+                #finish_clear
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // The growable buffers of the heap, which are left out without alloc
+    let finish_into_alloc = if cfg!(feature = "alloc") {
+        quote! {
+            ///
+            /// Consumes the compressor state, appending compressed bytes
+            /// to the provided buffer and reserving space if needed.
+            ///
+            /// Leaving the intermediate buffers in a reserved, cleared state.
+            ///
+            fn finish_into(&mut self, output_bytes: &mut Vec<u8>) {
+                #finish_flush
+
+                // A packet missing the words of an overflowed buffer is dropped
+                if !self.is_overflowed() {
+                    #finish_words
+
+                    // Pack the words into nibbles
+                    #finish_start
+                    output_bytes.extend_from_slice(&prefix);
+                    ::tsz_compress::prelude::halfvec::HalfVec::finish(output_bytes, words);
+                    #finish_crc
+                    #finish_continue
+                } else {
+                    #finish_keyframe
+                }
+
+                // Clear the buffers for re-use
+                #finish_clear
+            }
+
+            #finish_into_thin
+        }
+    } else {
+        quote! {}
+    };
+
+    // Without alloc, there is no growable storage for the default compressor
+    let compressor_alias = if cfg!(feature = "alloc") {
+        quote! {
+            /// A Compressor type implementing TszCompressV2 and TszCompressV2Ext with buffers that grow on the heap.
+            pub type #compressor_ident = #storage_compressor_ident<::tsz_compress::prelude::halfvec::HalfByteVec>;
+        }
+    } else {
+        quote! {}
+    };
+
+    let compressor_struct = quote! {
+        pub mod compress {
+            use super::*;
+            mod private {
+                use super::*;
+                use ::tsz_compress::prelude::*;
+                /// A Compressor type implementing TszCompressV2 and TszCompressV2Ext, with buffers of words kept in `Storage`.
+                #[derive(Debug)]
+                pub struct #storage_compressor_ident<Storage: ::tsz_compress::prelude::halfvec::HalfBytes> {
+                    #( #col_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue<#delta_queue_len>,)*
+                    #( #col_delta_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue<2>,)*
+                    #( #col_delta_buf_idents: Option<::tsz_compress::prelude::halfvec::HalfVec<Storage>>,)*
+                    #( #col_delta_delta_buf_idents: Option<::tsz_compress::prelude::halfvec::HalfVec<Storage>>,)*
+                    #( #prev_double_col_idents: #double_col_tys,)*
+                    #( #prev_col_idents: #delta_col_tys,)*
+                    #( #prev_delta_idents: #delta_col_tys,)*
+                    #( #col_escape_idents: bool,)*
+                    #( #col_present_rows_idents: usize,)*
+                    #( #col_presence_buf_idents: ::tsz_compress::prelude::halfvec::HalfVec<Storage>,)*
+                    #( #col_presence_run_idents: usize,)*
+                    #( #col_presence_present_idents: bool,)*
+                    row_count_buffer: ::tsz_compress::prelude::halfvec::HalfVec<Storage>,
+                    rows: usize,
+                    mtu: Option<usize>,
+                    #continuation_fields
+                }
+
+                /// The compressor state before a row, with the output buffers as lengths in nibbles.
+                #[derive(Debug, Clone, Copy)]
+                pub struct #checkpoint_ident {
+                    #( #col_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue<#delta_queue_len>,)*
+                    #( #col_delta_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue<2>,)*
+                    #( #col_delta_buf_idents: usize,)*
+                    #( #col_delta_delta_buf_idents: usize,)*
+                    #( #prev_double_col_idents: #double_col_tys,)*
+                    #( #prev_col_idents: #delta_col_tys,)*
+                    #( #prev_delta_idents: #delta_col_tys,)*
+                    #( #col_escape_idents: bool,)*
+                    #( #col_present_rows_idents: usize,)*
+                    #( #col_presence_buf_idents: usize,)*
+                    #( #col_presence_run_idents: usize,)*
+                    #( #col_presence_present_idents: bool,)*
+                    rows: usize,
+                }
+
+                impl<Storage: ::tsz_compress::prelude::halfvec::HalfBytes> #storage_compressor_ident<Storage> {
+                    #continuation_methods
+
+                    ///
+                    /// Writes every field of the compressor, identified by the types of its columns.
+                    ///
+                    fn write_snapshot(&self, writer: &mut ::tsz_compress::prelude::SnapshotWriter<'_>) {
+                        writer.write_magic(&[#( #schema_presence_codes #schema_col_codes, )*]);
+                        writer.write_usize(self.rows);
+                        writer.write_bool(self.mtu.is_some());
+                        writer.write_usize(self.mtu.unwrap_or(0));
+                        #continuation_write
+                        #(
+                            self.#col_delta_comp_queue_idents.write_snapshot(writer);
+                            self.#col_delta_delta_comp_queue_idents.write_snapshot(writer);
+                            if let Some(outbuf) = self.#col_delta_buf_idents.as_ref() {
+                                outbuf.write_snapshot(writer);
+                            }
+                            if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_ref() {
+                                outbuf.write_snapshot(writer);
+                            }
+                            writer.write_i128(self.#prev_double_col_idents as i128);
+                            writer.write_i128(self.#prev_col_idents as i128);
+                            writer.write_i128(self.#prev_delta_idents as i128);
+                            writer.write_bool(self.#col_escape_idents);
+                        )*
+                        #(
+                            writer.write_usize(self.#col_present_rows_idents);
+                            self.#col_presence_buf_idents.write_snapshot(writer);
+                            writer.write_usize(self.#col_presence_run_idents);
+                            writer.write_bool(self.#col_presence_present_idents);
+                        )*
+                    }
+
+                    ///
+                    /// Reads every field of the compressor in the order of `write_snapshot`.
+                    ///
+                    fn read_snapshot(&mut self, reader: &mut ::tsz_compress::prelude::SnapshotReader<'_>) -> Result<(), ::tsz_compress::prelude::CodingError> {
+                        reader.read_magic(&[#( #schema_presence_codes #schema_col_codes, )*])?;
+                        self.rows = reader.read_usize()?;
+                        let has_mtu = reader.read_bool()?;
+                        let mtu = reader.read_usize()?;
+                        self.mtu = has_mtu.then_some(mtu);
+                        #continuation_read
+                        #(
+                            self.#col_delta_comp_queue_idents = ::tsz_compress::prelude::CompressionQueue::read_snapshot(reader)?;
+                            self.#col_delta_delta_comp_queue_idents = ::tsz_compress::prelude::CompressionQueue::read_snapshot(reader)?;
+                            if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
+                                outbuf.read_snapshot(reader)?;
+                            }
+                            if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
+                                outbuf.read_snapshot(reader)?;
+                            }
+                            self.#prev_double_col_idents = reader.read_i128()? as #double_col_tys;
+                            self.#prev_col_idents = reader.read_i128()? as #delta_col_tys;
+                            self.#prev_delta_idents = reader.read_i128()? as #delta_col_tys;
+                            self.#col_escape_idents = reader.read_bool()?;
+                        )*
+                        #(
+                            self.#col_present_rows_idents = reader.read_usize()?;
+                            self.#col_presence_buf_idents.read_snapshot(reader)?;
+                            self.#col_presence_run_idents = reader.read_usize()?;
+                            self.#col_presence_present_idents = reader.read_bool()?;
+                        )*
+                        Ok(())
+                    }
+
+                    ///
+                    /// Empties the queues and buffers as if the compressor was new.
+                    ///
+                    fn reset(&mut self) {
+                        #(
+                            self.#col_delta_comp_queue_idents = ::tsz_compress::prelude::CompressionQueue::new();
+                            self.#col_delta_delta_comp_queue_idents = ::tsz_compress::prelude::CompressionQueue::new();
+                            self.#prev_double_col_idents = 0;
+                            self.#prev_col_idents = 0;
+                            self.#prev_delta_idents = 0;
+                            self.#col_escape_idents = false;
+                        )*
+                        #finish_keyframe
+                        #finish_clear
                     }
-                )*
 
-                // Guarantee that at least the column start nibble is emitted
-                #(
-                    if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
-                        if outbuf.is_empty() {
-                            outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
-                        }
+                    ///
+                    /// Returns true if a word was refused by a full buffer since the last finish.
+                    ///
+                    fn is_overflowed(&self) -> bool {
+                        false
+                        #( || self.#col_delta_buf_idents.as_ref().is_some_and(|outbuf| outbuf.is_overflowed()) )*
+                        #( || self.#col_delta_delta_buf_idents.as_ref().is_some_and(|outbuf| outbuf.is_overflowed()) )*
+                        #( || self.#col_presence_buf_idents.is_overflowed() )*
                     }
-                    if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
-                        if outbuf.is_empty() {
-                            outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
-                        }
+
+                    ///
+                    /// Returns true if the buffers have room for every word emitted on finish.
+                    ///
+                    /// An empty column still needs its start of column.
+                    ///
+                    fn can_finish(&self) -> bool {
+                        #(
+                            if let Some(outbuf) = self.#col_delta_buf_idents.as_ref() {
+                                if outbuf.is_overflowed() || outbuf.remaining_len() < 1 + self.#col_delta_comp_queue_idents.flushed_delta_len() {
+                                    return false;
+                                }
+                            }
+                            if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_ref() {
+                                if outbuf.is_overflowed() || outbuf.remaining_len() < 1 + self.#col_delta_delta_comp_queue_idents.flushed_delta_delta_len() {
+                                    return false;
+                                }
+                            }
+                        )*
+                        #(
+                            let outbuf = &self.#col_presence_buf_idents;
+                            if outbuf.is_overflowed() || outbuf.remaining_len() < ::tsz_compress::prelude::nibble_varint_len(self.#col_presence_run_idents as u64) {
+                                return false;
+                            }
+                        )*
+                        self.row_count_buffer.remaining_len() >= ::tsz_compress::prelude::nibble_varint_len(self.rows as u64)
                     }
-                )*
 
-                // Flush any pending samples in the queues
-                // All of the bits are concatenated with a 1001 tag indicating the start of a new column
-                #(
-                    self.#col_delta_buf_idents.as_mut().map(|outbuf| {
-                        while self.#col_delta_comp_queue_idents.len() > 0 {
-                            self.#col_delta_comp_queue_idents.flush_delta_bits(outbuf);
-                        }
-                        });
-                    self.#col_delta_delta_buf_idents.as_mut().map(|outbuf| {
-                        while self.#col_delta_delta_comp_queue_idents.len() > 0 {
-                            self.#col_delta_delta_comp_queue_idents.emit_delta_delta_bits(outbuf);
+                    ///
+                    /// A copy of the state whose buffers count the words of the rows compressed into it, without keeping them.
+                    ///
+                    fn to_counting(&self) -> #storage_compressor_ident<::tsz_compress::prelude::halfvec::HalfCount> {
+                        #storage_compressor_ident {
+                            #( #col_delta_comp_queue_idents: self.#col_delta_comp_queue_idents,)*
+                            #( #col_delta_delta_comp_queue_idents: self.#col_delta_delta_comp_queue_idents,)*
+                            #( #col_delta_buf_idents: self.#col_delta_buf_idents.as_ref().map(|outbuf| outbuf.to_counting()),)*
+                            #( #col_delta_delta_buf_idents: self.#col_delta_delta_buf_idents.as_ref().map(|outbuf| outbuf.to_counting()),)*
+                            #( #prev_double_col_idents: self.#prev_double_col_idents,)*
+                            #( #prev_col_idents: self.#prev_col_idents,)*
+                            #( #prev_delta_idents: self.#prev_delta_idents,)*
+                            #( #col_escape_idents: self.#col_escape_idents,)*
+                            #( #col_present_rows_idents: self.#col_present_rows_idents,)*
+                            #( #col_presence_buf_idents: self.#col_presence_buf_idents.to_counting(),)*
+                            #( #col_presence_run_idents: self.#col_presence_run_idents,)*
+                            #( #col_presence_present_idents: self.#col_presence_present_idents,)*
+                            row_count_buffer: self.row_count_buffer.to_counting(),
+                            rows: self.rows,
+                            mtu: self.mtu,
+                            #continuation_copy
                         }
-                    });
-                )*
-
-                // Write the number of rows as a 32-bit integer
-                // The decompressor will read this value and reserve space for the rows
-                // SAFETY: The number of rows may be more than 2^32, but the decompressor will
-                //         reserve at most 2^32 rows.
-                let mut rows = ::tsz_compress::prelude::halfvec::HalfVec::new(8);
-                ::tsz_compress::prelude::write_i32_bits(&mut rows, self.rows as u32 as i32);
-
-                // Create an iterator over the words to be written
-                let rows = Some(rows);
-                let words = [
-                    rows.as_ref().into_iter(),
-                    #(
-                        self.#col_delta_buf_idents.as_ref().into_iter(),
-                        self.#col_delta_delta_buf_idents.as_ref().into_iter(),
-                    )*
-                ].into_iter().flatten();
-
-                // Pack the words into nibbles
-                ::tsz_compress::prelude::halfvec::HalfVec::finish_thin(output_bytes, words);
-
-                // Clear the buffers for re-use
-                #(
-                    self.#col_delta_buf_idents.as_mut().map(|outbuf| {
-                        outbuf.clear();
-                    });
-                    self.#col_delta_delta_buf_idents.as_mut().map(|outbuf| {
-                        outbuf.clear();
-                    });
-                    self.rows = 0;
-                )*
-            }
-        }
-    } else {
-        quote! {}
-    };
+                    }
 
-    let compressor_struct = quote! {
-        pub mod compress {
-            use super::*;
-            mod private {
-                use super::*;
-                use ::tsz_compress::prelude::*;
-                /// A Compressor type implementing TszCompressV2.
-                #[derive(Debug)]
-                pub struct #compressor_ident {
-                    #( #col_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue<10>,)*
-                    #( #col_delta_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue<2>,)*
-                    #( #col_delta_buf_idents: Option<::tsz_compress::prelude::halfvec::HalfVec>,)*
-                    #( #col_delta_delta_buf_idents: Option<::tsz_compress::prelude::halfvec::HalfVec>,)*
-                    #( #prev_double_col_idents: #double_col_tys,)*
-                    #( #prev_col_idents: #delta_col_tys,)*
-                    #( #prev_delta_idents: #delta_col_tys,)*
-                    rows: usize,
+                    ///
+                    /// Checks that every value of the row is within its declared bit-width.
+                    ///
+                    fn check_range(row: &#ident) -> Result<(), ::tsz_compress::prelude::CodingError> {
+                        #( #col_range_checks )*
+                        Ok(())
+                    }
                 }
 
-                impl ::tsz_compress::prelude::TszCompressV2 for #compressor_ident {
+                impl<Storage: ::tsz_compress::prelude::halfvec::HalfBytes> ::tsz_compress::prelude::TszCompressV2 for #storage_compressor_ident<Storage> {
                     type T = #ident;
 
                     /// Sets up two compression queues: one for delta compression and one for delta-delta compression,
                     /// along with their respective output buffers. Initializes counters for the number of column values
                     /// emitted during the delta and delta-delta compression processes.
                     fn new(prealloc_rows: usize) -> Self {
-                        #compressor_ident {
-                            #( #col_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue::<10>::new(),)*
+                        #storage_compressor_ident {
+                            #( #col_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue::<#delta_queue_len>::new(),)*
                             #( #col_delta_delta_comp_queue_idents: ::tsz_compress::prelude::CompressionQueue::<2>::new(),)*
                             #( #col_delta_buf_idents: #col_delta_buf,)*
                             #( #col_delta_delta_buf_idents: #col_delta_delta_buf,)*
                             #( #prev_double_col_idents: 0,)*
                             #( #prev_col_idents: 0,)*
                             #( #prev_delta_idents: 0,)*
+                            #( #col_escape_idents: false,)*
+                            #( #col_present_rows_idents: 0,)*
+                            #( #col_presence_buf_idents: {
+                                let mut outbuf = ::tsz_compress::prelude::halfvec::HalfVec::with_storage(Storage::with_capacity(8));
+                                outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
+                                outbuf
+                            },)*
+                            #( #col_presence_run_idents: 0,)*
+                            #( #col_presence_present_idents: true,)*
+                            row_count_buffer: ::tsz_compress::prelude::halfvec::HalfVec::with_storage(Storage::with_capacity(11)),
                             rows: 0,
+                            mtu: None,
+                            #continuation_init
                         }
                     }
 
@@ -925,64 +2201,23 @@ pub fn derive_compressv2(tokens: TokenStream) -> TokenStream {
                     fn compress(&mut self, row: Self::T) {
                         // Enqueues delta and delta-delta values
                         self.rows += 1;
+                        #( #col_compress_blocks )*
+                    }
 
-                        if self.rows > 2 {
-                            #(
-                                // The new delta  and delta-delta
-                                let col = row.#col_idents as #delta_col_tys;
-                                let delta = col - self.#prev_col_idents;
-
-                                // Do delta compression if configured
-                                #delta_comp_block
-
-                                // Do delta-delta compression if configured
-                                #delta_delta_comp_block
-
-                                // Update the previous values
-                                self.#prev_col_idents = col;
-                                self.#prev_delta_idents = delta;
-                            )*
-                        } else if self.rows == 1 {
-                            /// Write out the full value in the exact bit-width of the column.
-                            #(
-                                if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::FIRST_ROW));
-                                    #write_first(outbuf, row.#col_idents);
-                                }
-                                if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::FIRST_ROW));
-                                    #write_first(outbuf, row.#col_idents);
-                                }
-                                self.#prev_double_col_idents = row.#col_idents as #double_col_tys;
-                            )*
-                        } else if self.rows == 2 {
-                            /// Write out the full value in the next exact bit-width of the column, regardless of chosen delta bit-width.
-                            /// SAFETY: If the bit-width is configurable, then bits at rest will be uninterpretable.
-                            #(
-                                // Up cast to double bit-width always for the first delta
-                                let col = row.#col_idents as #double_col_tys;
-                                let delta = col - self.#prev_double_col_idents;
-                                if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::SECOND_ROW));
-                                    #write_second(outbuf, delta);
-                                }
-                                if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::SECOND_ROW));
-                                    #write_second(outbuf, delta);
-                                }
-
-                                // Use choice of bit-width for delta/delta-delta compression
-                                self.#prev_delta_idents = delta as #delta_col_tys;
-                                self.#prev_col_idents = col as #delta_col_tys;
-                            )*;
+                    fn try_compress(&mut self, row: Self::T) -> Result<(), ::tsz_compress::prelude::CodingError> {
+                        Self::check_range(&row)?;
+                        let checkpoint = self.checkpoint();
+                        self.compress(row);
+                        if self.can_finish() {
+                            return Ok(());
                         }
+                        self.rollback(checkpoint);
+                        Err(::tsz_compress::prelude::CodingError::BufferFull)
                     }
 
-
                     fn len(&self) -> usize {
                         let mut finished_nibble_count = 0;
+                        #( finished_nibble_count += self.#col_presence_buf_idents.len(); )*
                         #(
                             if let (Some(delta_buffer), Some(delta_delta_buffer)) = (&self.#col_delta_buf_idents, &self.#col_delta_delta_buf_idents) {
                                 finished_nibble_count += delta_buffer.len().min(delta_delta_buffer.len());
@@ -1030,130 +2265,473 @@ pub fn derive_compressv2(tokens: TokenStream) -> TokenStream {
                         self.rows
                     }
 
-                    ///
-                    /// Consumes the compressor state, appending compressed bytes
-                    /// to the provided buffer and reserving space if needed.
-                    ///
-                    /// Leaving the intermediate buffers in a reserved, cleared state.
-                    ///
-                    fn finish_into(&mut self, output_bytes: &mut Vec<u8>) {
-                        // Only use one encoding mechanism
+                    #finish_into_alloc
+                }
+
+                impl<Storage: ::tsz_compress::prelude::halfvec::HalfBytes> ::tsz_compress::prelude::TszCompressV2Ext for #storage_compressor_ident<Storage> {
+                    fn len_bytes_if_finished(&self) -> usize {
+                        // The header, any sequence field and the row count
+                        let mut nibbles = 2 * #prefix_len + ::tsz_compress::prelude::nibble_varint_len(self.rows as u64);
                         #(
-                            if let (Some(delta_buffer), Some(delta_delta_buffer)) = (&self.#col_delta_buf_idents, &self.#col_delta_delta_buf_idents) {
-                                // Prefer delta on ties
-                                if delta_delta_buffer.len() >= delta_buffer.len() {
-                                    self.#col_delta_delta_buf_idents = None;
-                                } else {
-                                    self.#col_delta_buf_idents = None;
-                                }
+                            // An empty column is a single start of column nibble
+                            let delta_len = self.#col_delta_buf_idents.as_ref().map(|outbuf| outbuf.len().max(1) + self.#col_delta_comp_queue_idents.flushed_delta_len());
+                            let delta_delta_len = self.#col_delta_delta_buf_idents.as_ref().map(|outbuf| outbuf.len().max(1) + self.#col_delta_delta_comp_queue_idents.flushed_delta_delta_len());
+                            nibbles += match (delta_len, delta_delta_len) {
+                                (Some(delta_len), Some(delta_delta_len)) => delta_len.min(delta_delta_len),
+                                (Some(len), None) | (None, Some(len)) => len,
+                                (None, None) => 0,
+                            };
+                        )*
+
+                        // The presence columns, including the run that is closed on finish
+                        #(
+                            nibbles += self.#col_presence_buf_idents.len();
+                            if self.rows > 0 {
+                                nibbles += ::tsz_compress::prelude::nibble_varint_len(self.#col_presence_run_idents as u64);
                             }
                         )*
 
-                        // Guarantee that at least the column start nibble is emitted
+                        // Odd nibbles are padded to a byte, then followed by any checksum
+                        nibbles.div_ceil(2) + #crc_len
+                    }
+
+                    type Checkpoint = #checkpoint_ident;
+
+                    fn checkpoint(&self) -> Self::Checkpoint {
+                        #checkpoint_ident {
+                            #( #col_delta_comp_queue_idents: self.#col_delta_comp_queue_idents,)*
+                            #( #col_delta_delta_comp_queue_idents: self.#col_delta_delta_comp_queue_idents,)*
+                            #( #col_delta_buf_idents: self.#col_delta_buf_idents.as_ref().map_or(0, |outbuf| outbuf.len()),)*
+                            #( #col_delta_delta_buf_idents: self.#col_delta_delta_buf_idents.as_ref().map_or(0, |outbuf| outbuf.len()),)*
+                            #( #prev_double_col_idents: self.#prev_double_col_idents,)*
+                            #( #prev_col_idents: self.#prev_col_idents,)*
+                            #( #prev_delta_idents: self.#prev_delta_idents,)*
+                            #( #col_escape_idents: self.#col_escape_idents,)*
+                            #( #col_present_rows_idents: self.#col_present_rows_idents,)*
+                            #( #col_presence_buf_idents: self.#col_presence_buf_idents.len(),)*
+                            #( #col_presence_run_idents: self.#col_presence_run_idents,)*
+                            #( #col_presence_present_idents: self.#col_presence_present_idents,)*
+                            rows: self.rows,
+                        }
+                    }
+
+                    fn rollback(&mut self, checkpoint: Self::Checkpoint) {
                         #(
+                            self.#col_delta_comp_queue_idents = checkpoint.#col_delta_comp_queue_idents;
+                            self.#col_delta_delta_comp_queue_idents = checkpoint.#col_delta_delta_comp_queue_idents;
                             if let Some(outbuf) = self.#col_delta_buf_idents.as_mut() {
-                                if outbuf.is_empty() {
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
-                                }
+                                outbuf.truncate(checkpoint.#col_delta_buf_idents);
                             }
                             if let Some(outbuf) = self.#col_delta_delta_buf_idents.as_mut() {
-                                if outbuf.is_empty() {
-                                    outbuf.push(::tsz_compress::prelude::halfvec::HalfWord::Half(::tsz_compress::prelude::consts::headers::START_OF_COLUMN));
-                                }
+                                outbuf.truncate(checkpoint.#col_delta_delta_buf_idents);
                             }
+                            self.#prev_double_col_idents = checkpoint.#prev_double_col_idents;
+                            self.#prev_col_idents = checkpoint.#prev_col_idents;
+                            self.#prev_delta_idents = checkpoint.#prev_delta_idents;
+                            self.#col_escape_idents = checkpoint.#col_escape_idents;
                         )*
-
-                        // Flush any pending samples in the queues
-                        // All of the bits are concatenated with a 1001 tag indicating the start of a new column
                         #(
-                            self.#col_delta_buf_idents.as_mut().map(|outbuf| {
-                                while self.#col_delta_comp_queue_idents.len() > 0 {
-                                    self.#col_delta_comp_queue_idents.flush_delta_bits(outbuf);
-                                }
-                             });
-                            self.#col_delta_delta_buf_idents.as_mut().map(|outbuf| {
-                                while self.#col_delta_delta_comp_queue_idents.len() > 0 {
-                                    self.#col_delta_delta_comp_queue_idents.emit_delta_delta_bits(outbuf);
-                                }
-                            });
+                            self.#col_present_rows_idents = checkpoint.#col_present_rows_idents;
+                            self.#col_presence_buf_idents.truncate(checkpoint.#col_presence_buf_idents);
+                            self.#col_presence_run_idents = checkpoint.#col_presence_run_idents;
+                            self.#col_presence_present_idents = checkpoint.#col_presence_present_idents;
                         )*
+                        self.rows = checkpoint.rows;
+                    }
 
-                        // Write the number of rows as a 32-bit integer
-                        // The decompressor will read this value and reserve space for the rows
-                        // SAFETY: The number of rows may be more than 2^32, but the decompressor will
-                        //         reserve at most 2^32 rows.
-                        let mut rows = ::tsz_compress::prelude::halfvec::HalfVec::new(8);
-                        ::tsz_compress::prelude::write_i32_bits(&mut rows, self.rows as u32 as i32);
-
-                        // Create an iterator over the words to be written
-                        let rows = Some(rows);
-                        let words = [
-                            rows.as_ref().into_iter(),
-                            #(
-                                self.#col_delta_buf_idents.as_ref().into_iter(),
-                                self.#col_delta_delta_buf_idents.as_ref().into_iter(),
-                            )*
-                        ].into_iter().flatten();
-
-                        // Pack the words into nibbles
-                        ::tsz_compress::prelude::halfvec::HalfVec::finish(output_bytes, words);
+                    fn snapshot_len(&self) -> usize {
+                        let mut writer = ::tsz_compress::prelude::SnapshotWriter::new(&mut []);
+                        self.write_snapshot(&mut writer);
+                        writer.len()
+                    }
 
-                        // Clear the buffers for re-use
-                        #(
-                            self.#col_delta_buf_idents.as_mut().map(|outbuf| {
-                                outbuf.clear();
-                            });
-                            self.#col_delta_delta_buf_idents.as_mut().map(|outbuf| {
-                                outbuf.clear();
-                            });
-                            self.rows = 0;
-                        )*
+                    fn snapshot_into(&self, output_bytes: &mut [u8]) -> Result<usize, ::tsz_compress::prelude::CodingError> {
+                        let mut writer = ::tsz_compress::prelude::SnapshotWriter::new(output_bytes);
+                        self.write_snapshot(&mut writer);
+                        writer.finish()
+                    }
+
+                    fn restore(&mut self, snapshot: &[u8]) -> Result<(), ::tsz_compress::prelude::CodingError> {
+                        // A partly read snapshot leaves the compressor empty rather than inconsistent
+                        let mut reader = ::tsz_compress::prelude::SnapshotReader::new(snapshot);
+                        let restored = self.read_snapshot(&mut reader).and_then(|_| reader.finish());
+                        if restored.is_err() {
+                            self.reset();
+                        }
+                        restored
+                    }
+
+                    fn would_fit(&self, row: &Self::T, budget_bytes: usize) -> bool {
+                        if Self::check_range(row).is_err() {
+                            return false;
+                        }
+
+                        // Compress the row into a copy of the state that only counts its words
+                        let mut counting = self.to_counting();
+                        counting.compress(*row);
+                        counting.can_finish() && counting.len_bytes_if_finished() <= budget_bytes
+                    }
+
+                    #[inline(always)]
+                    fn mtu(&self) -> Option<usize> {
+                        self.mtu
+                    }
+
+                    fn set_mtu(&mut self, mtu: Option<usize>) {
+                        self.mtu = mtu;
                     }
 
-                    #finish_into_thin
+                    fn finish_into_slice(&mut self, output_bytes: &mut [u8]) -> Result<usize, ::tsz_compress::prelude::CodingError> {
+                        // Keep the state if the packet cannot fit
+                        let len = self.len_bytes_if_finished();
+                        if !self.is_overflowed() && output_bytes.len() < len {
+                            return Err(::tsz_compress::prelude::CodingError::BufferFull);
+                        }
+                        #finish_flush
+
+                        // A packet missing the words of an overflowed buffer is dropped
+                        if self.is_overflowed() {
+                            #finish_keyframe
+                            #finish_clear
+                            return Err(::tsz_compress::prelude::CodingError::BufferFull);
+                        }
+                        #finish_words
+
+                        // Pack the words into nibbles after the header and sequence field
+                        output_bytes[..#prefix_len].copy_from_slice(&prefix);
+                        let written = #prefix_len + ::tsz_compress::prelude::halfvec::HalfVec::finish_into_slice(&mut output_bytes[#prefix_len..], words);
+                        #finish_slice_crc
+                        debug_assert_eq!(written, len);
+                        #finish_continue
+
+                        // Clear the buffers for re-use
+                        #finish_clear
+                        Ok(written)
+                    }
                 }
             }
 
-            pub use private::#compressor_ident;
+            #compressor_alias
+
+            /// A Compressor type implementing TszCompressV2 and TszCompressV2Ext with buffers of at most `N` words that never allocate.
+            pub type #array_compressor_ident<const N: usize> = #storage_compressor_ident<::tsz_compress::prelude::halfvec::HalfArray<N>>;
+
+            pub use private::{#checkpoint_ident, #storage_compressor_ident};
         }
     };
 
     compressor_struct.into()
 }
 
-#[proc_macro_derive(DecompressV2)]
+#[proc_macro_derive(DecompressV2, attributes(tsz))]
 pub fn derive_decompressv2(tokens: TokenStream) -> TokenStream {
     let input = parse_macro_input!(tokens as syn::DeriveInput);
 
+    // The decompressor collects rows into growable columns
+    if !cfg!(feature = "alloc") {
+        return quote! {
+            compile_error!("DecompressV2 requires the alloc feature of tsz-compress");
+        }
+        .into();
+    }
+
     // We will define a struct by this name
     let ident = input.ident.clone();
     let decompressor_ident = format_ident!("{}DecompressorImpl", ident);
 
+    let options = get_options_of_struct(&input);
     let columns = get_fields_of_struct(input);
-    let (col_idents, col_tys, _col_attrs): (Vec<_>, Vec<_>, Vec<_>) = multiunzip(columns);
+    let (col_idents, col_tys, col_attrs): (Vec<_>, Vec<_>, Vec<_>) = multiunzip(columns);
+
+    // Option<T> columns are decoded as a presence column and the present values of T
+    let col_nullable = col_tys
+        .iter()
+        .map(|ty| get_option_inner_ty(ty).is_some())
+        .collect_vec();
+    let col_value_tys = col_tys
+        .iter()
+        .map(|ty| get_option_inner_ty(ty).unwrap_or_else(|| ty.clone()))
+        .collect_vec();
+
+    // Scaled float columns decode the integers they are quantized to, then reconstruct the floats
+    let (col_value_tys, col_quantizations) = get_quantized_cols(&col_value_tys, &col_attrs);
+    let nullable_col_idents = col_idents
+        .iter()
+        .zip(&col_nullable)
+        .filter(|(_, nullable)| **nullable)
+        .map(|(ident, _)| ident)
+        .collect_vec();
+    let nullable_col_value_tys = col_value_tys
+        .iter()
+        .zip(&col_nullable)
+        .filter(|(_, nullable)| **nullable)
+        .map(|(ty, _)| ty)
+        .collect_vec();
+    let col_presence_runs_idents = nullable_col_idents
+        .iter()
+        .map(|ident| format_ident!("{}_presence_runs", ident))
+        .collect_vec();
+    let col_present_values_idents = nullable_col_idents
+        .iter()
+        .map(|ident| format_ident!("{}_present_values", ident))
+        .collect_vec();
 
     let col_vec_idents = col_idents
         .iter()
         .map(|ident| format_ident!("col_{}", ident))
         .collect_vec();
 
-    let decode_idents = col_tys
+    // The quantized integers of each scaled float column are kept alongside the floats
+    let col_decode_idents = izip!(col_vec_idents.iter(), col_quantizations.iter())
+        .map(|(col_vec_ident, quantization)| match quantization {
+            Some(_) => format_ident!("{}_quantized", col_vec_ident),
+            None => col_vec_ident.clone(),
+        })
+        .collect_vec();
+    let (col_quantized_idents, col_quantized_tys): (Vec<_>, Vec<_>) = izip!(
+        col_decode_idents.iter(),
+        col_value_tys.iter(),
+        col_nullable.iter(),
+        col_quantizations.iter()
+    )
+    .filter(|(_, _, _, quantization)| quantization.is_some())
+    .map(|(col_decode_ident, ty, nullable, _)| match nullable {
+        true => (col_decode_ident, quote! { Option<#ty> }),
+        false => (col_decode_ident, quote! { #ty }),
+    })
+    .unzip();
+    let col_dequantize_blocks = izip!(
+        col_vec_idents.iter(),
+        col_decode_idents.iter(),
+        col_nullable.iter(),
+        col_quantizations.iter()
+    )
+    .map(|(col_vec_ident, col_decode_ident, nullable, quantization)| {
+        let Some(Quantization {
+            float_ty,
+            scale,
+            offset,
+        }) = quantization
+        else {
+            return quote! {};
+        };
+        let dequantize = quote! { ::tsz_compress::prelude::dequantize(value as f64, #scale, #offset) as #float_ty };
+        let dequantize = match nullable {
+            true => quote! { value.map(|value| #dequantize) },
+            false => dequantize,
+        };
+        quote! {
+            let start = self.#col_vec_ident.len();
+            self.#col_vec_ident.extend(self.#col_decode_ident[start..].iter().map(|&value| #dequantize));
+        }
+    })
+    .collect_vec();
+
+    // Check and skip the stream header if configured
+    let (read_header, check_stream_header) = if options.header {
+        let delta_col_names = get_delta_col_names(&col_value_tys, &col_attrs);
+        let schema_col_codes = get_schema_col_codes(&col_value_tys, &delta_col_names);
+        let schema_presence_codes = col_nullable
+            .iter()
+            .map(|nullable| match nullable {
+                true => quote! { ::tsz_compress::prelude::consts::schema::PRESENCE << 4, },
+                false => quote! {},
+            })
+            .collect_vec();
+        (
+            quote! {
+                let bytes = read_header(bytes, &[ #( #schema_presence_codes #schema_col_codes, )* ])?;
+            },
+            quote! {
+                read_header(header, &[ #( #schema_presence_codes #schema_col_codes, )* ]).map(|_| ())
+            },
+        )
+    } else {
+        (quote! {}, quote! { Ok(()) })
+    };
+
+    // The stream decodes presence columns as columns of their own
+    let stream_columns = izip!(col_idents.iter(), col_nullable.iter())
+        .enumerate()
+        .flat_map(|(index, (col_ident, nullable))| {
+            let name = col_ident.to_string();
+            let count = if *nullable { 2 } else { 1 };
+            core::iter::repeat_n(quote! { (#index, #name) }, count)
+        })
+        .collect_vec();
+    let stream_header_len = match options.header {
+        true => 2 + stream_columns.len(),
+        false => 0,
+    };
+    let col_cursor_idents = col_idents
+        .iter()
+        .map(|ident| format_ident!("{}_cursor", ident))
+        .collect_vec();
+    let col_presence_cursor_idents = nullable_col_idents
+        .iter()
+        .map(|ident| format_ident!("{}_presence_cursor", ident))
+        .collect_vec();
+
+    // Wrapping columns of a declared bit-width reduce the values they decode to that bit-width
+    let col_wrap_bits = get_wrapping_bits(&col_value_tys, &col_attrs);
+    let wrap_blocks = izip!(col_decode_idents.iter(), col_wrap_bits.iter())
+        .map(|(col_vec_ident, bits)| match bits {
+            Some(bits) => quote! { wrap_column(&mut self.#col_vec_ident[start_rows..], #bits); },
+            None => quote! {},
+        })
+        .collect_vec();
+
+    // Columns of a declared bit-width check and extend the values written at that bit-width
+    let col_declared_bits = get_declared_bits(&col_value_tys, &col_attrs);
+    let col_declared_bits_args = col_declared_bits
         .iter()
-        .map(|ty| match ty {
-            syn::Type::Path(syn::TypePath { path, .. }) => {
-                let segment = path.segments.first().unwrap();
-                let ident = segment.ident.clone();
-                match ident.to_string().as_str() {
-                    "i8" => quote! { decode_i8 },
-                    "i16" => quote! { decode_i16 },
-                    "i32" => quote! { decode_i32 },
-                    "i64" => quote! { decode_i64 },
-                    _ => panic!("Unsupported type"),
+        .map(|bits| match bits {
+            Some(bits) => quote! { Some(#bits) },
+            None => quote! { None },
+        })
+        .collect_vec();
+
+    // Each column steps its cursor until it holds the rows of the packet
+    let mut stream_column = 0usize;
+    let stream_step_arms = izip!(col_idents.iter(), col_decode_idents.iter(), col_value_tys.iter(), col_nullable.iter(), col_wrap_bits.iter(), col_declared_bits_args.iter(), col_dequantize_blocks.iter())
+        .map(|(col_ident, col_vec_ident, col_value_ty, nullable, wrap_bits, declared_bits, dequantize_block)| {
+            let cursor_ident = format_ident!("{}_cursor", col_ident);
+            let wrap_step = |values_ident: &syn::Ident| match wrap_bits {
+                Some(bits) => (
+                    quote! { let start = self.#values_ident.len(); },
+                    quote! { wrap_column(&mut self.#values_ident[start..], #bits); },
+                ),
+                None => (quote! {}, quote! {}),
+            };
+            let arm = stream_column;
+            if *nullable {
+                stream_column += 2;
+                let values_arm = arm + 1;
+                let presence_runs_ident = format_ident!("{}_presence_runs", col_ident);
+                let present_values_ident = format_ident!("{}_present_values", col_ident);
+                let presence_cursor_ident = format_ident!("{}_presence_cursor", col_ident);
+                let (wrap_start, wrap_step) = wrap_step(&present_values_ident);
+                quote! {
+                    #arm => step_presence(iter, rows, &mut self.#presence_cursor_ident, &mut self.#presence_runs_ident),
+                    #values_arm => {
+                        let end = self.#presence_cursor_ident.present();
+                        let done = self.#present_values_ident.len() >= end;
+                        if !done {
+                            #wrap_start
+                            <#col_value_ty as StepColumn>::step(iter, &mut self.#cursor_ident, end, #declared_bits, &mut self.#present_values_ident)?;
+                            #wrap_step
+                        }
+                        merge_presence_partial(&self.#presence_runs_ident, &self.#present_values_ident, &mut self.#presence_cursor_ident, &mut self.#col_vec_ident)?;
+                        #dequantize_block
+                        Ok(done)
+                    }
+                }
+            } else {
+                stream_column += 1;
+                let (wrap_start, wrap_step) = wrap_step(col_vec_ident);
+                quote! {
+                    #arm => {
+                        let end = self.stream_start_rows + rows;
+                        if self.#col_vec_ident.len() >= end {
+                            return Ok(true);
+                        }
+                        #wrap_start
+                        <#col_value_ty as StepColumn>::step(iter, &mut self.#cursor_ident, end, #declared_bits, &mut self.#col_vec_ident)?;
+                        #wrap_step
+                        #dequantize_block
+                        Ok(false)
+                    }
                 }
             }
-            _ => panic!("Unsupported type"),
         })
-        .collect::<Vec<_>>();
+        .collect_vec();
+
+    // Nullable columns merge the present values into the runs of the presence column
+    // Errors record the column and the nibble offset in the packet
+    let decode_blocks = izip!(col_idents.iter(), col_decode_idents.iter(), col_nullable.iter(), col_declared_bits_args.iter())
+        .enumerate()
+        .map(|(index, (col_ident, col_vec_ident, nullable, declared_bits))| {
+            let name = col_ident.to_string();
+            let in_column = quote! {
+                .map_err(|error| error.in_column(#index, #name, packet_offset + iter.nibble_offset()))?
+            };
+            let cursor_ident = format_ident!("{}_cursor", col_ident);
+            if *nullable {
+                let presence_runs_ident = format_ident!("{}_presence_runs", col_ident);
+                let present_values_ident = format_ident!("{}_present_values", col_ident);
+                quote! {
+                    self.#presence_runs_ident.clear();
+                    self.#present_values_ident.clear();
+                    decode_presence(&mut iter, rows, &mut self.#presence_runs_ident) #in_column;
+                    let present = self.#presence_runs_ident.iter().step_by(2).sum::<usize>();
+                    decode_column(&mut iter, present, &mut self.#cursor_ident, #declared_bits, &mut self.#present_values_ident) #in_column;
+                    merge_presence(&self.#presence_runs_ident, &self.#present_values_ident, &mut self.#col_vec_ident) #in_column;
+                }
+            } else {
+                quote! {
+                    decode_column(&mut iter, rows, &mut self.#cursor_ident, #declared_bits, &mut self.#col_vec_ident) #in_column;
+                }
+            }
+        })
+        .collect_vec();
+
+    let first_col_vec_ident = &col_vec_idents[0];
+
+    // Continuation packets check their sequence field, and continue the cursors unless they are keyframes
+    let (sequence_field, sequence_init, read_sequence, begin_sequence, forget_sequence) = if options
+        .continuation
+    {
+        (
+            quote! { sequence: Option<u16>, },
+            quote! { sequence: None, },
+            quote! {
+                if bytes.len() < 2 {
+                    return Err(CodingError::Empty);
+                }
+                let continues = check_sequence(&mut self.sequence, u16::from_be_bytes([bytes[0], bytes[1]]))?;
+                let bytes = &bytes[2..];
+                if !continues {
+                    #( self.#col_cursor_idents = ColumnCursor::default(); )*
+                }
+            },
+            quote! {
+                match check_sequence(&mut self.sequence, sequence.unwrap_or(0))? {
+                    true => { #( self.#col_cursor_idents.next_packet(); )* }
+                    false => { #( self.#col_cursor_idents = ColumnCursor::default(); )* }
+                }
+            },
+            quote! {
+                self.sequence = None;
+                #( self.#col_cursor_idents = ColumnCursor::default(); )*
+            },
+        )
+    } else {
+        // Every other packet is a keyframe
+        (
+            quote! {},
+            quote! {},
+            quote! { #( self.#col_cursor_idents = ColumnCursor::default(); )* },
+            quote! { #( self.#col_cursor_idents = ColumnCursor::default(); )* },
+            quote! {},
+        )
+    };
+    let continuation = options.continuation;
+    let crc = options.crc;
+
+    // The packet is only decoded once the checksum matches
+    let check_crc = if options.crc {
+        quote! {
+            let Some(crc_start) = bytes.len().checked_sub(4) else {
+                return Err(CodingError::Empty);
+            };
+            let (bytes, trailer) = bytes.split_at(crc_start);
+            if crc32(bytes).to_be_bytes() != trailer {
+                return Err(CodingError::ChecksumMismatch);
+            }
+        }
+    } else {
+        quote! {}
+    };
 
     let decompressor_tokens = quote! {
         pub mod decompress {
@@ -1166,6 +2744,14 @@ pub fn derive_decompressv2(tokens: TokenStream) -> TokenStream {
                 #[derive(Debug)]
                 pub struct #decompressor_ident {
                     #( #col_vec_idents: Vec<#col_tys>, )*
+                    #( #col_quantized_idents: Vec<#col_quantized_tys>, )*
+                    #( #col_presence_runs_idents: Vec<usize>, )*
+                    #( #col_present_values_idents: Vec<#nullable_col_value_tys>, )*
+                    #( #col_cursor_idents: ColumnCursor<<#col_value_tys as StepColumn>::Wide>, )*
+                    #( #col_presence_cursor_idents: PresenceCursor, )*
+                    stream_state: StreamState,
+                    stream_start_rows: usize,
+                    #sequence_field
                 }
 
                 impl #decompressor_ident {
@@ -1175,56 +2761,47 @@ pub fn derive_decompressv2(tokens: TokenStream) -> TokenStream {
                             &self.#col_vec_idents
                         }
                     )*
-                }
-
-                impl ::tsz_compress::prelude::TszDecompressV2 for #decompressor_ident {
-                    type T = #ident;
 
-                    /// Initialize a decompressor with a vector for each column.
-                    fn new() -> Self {
-                        #decompressor_ident {
-                            #( #col_vec_idents: Vec::new(), )*
-                        }
-                    }
+                    /// Decompress a packet, possibly leaving partially decoded rows behind on error.
+                    fn decompress_packet(&mut self, bytes: &[u8]) -> Result<(), CodingError> {
+                        #check_crc
+                        let packet_len = bytes.len();
+                        #read_header
+                        #read_sequence
+                        let packet_offset = (packet_len - bytes.len()) * 2;
 
-                    /// Decompress tsz-compressed bytes, extending the columns with the decompressed values.
-                    fn decompress(&mut self, bytes: &[u8]) -> Result<(), CodingError> {
                         // Require at least the row count and 1 column
-                        if bytes.len() < core::mem::size_of::<i32>() + 1 {
+                        if bytes.is_empty() {
                             return Err(CodingError::Empty);
                         }
 
-                        // Read the row count, accepting a reservation up to 2^32 rows
-                        // SAFETY: The decompressor will reserve at most 2^32 rows, but there may be more if overflow occurs.
-                        let row_bytes: &[u8; 4] = bytes[..4].try_into().map_err(|_|CodingError::NotEnoughBits)?;
-                        let rows = read_full_i32(row_bytes) as u32;
-                        let bytes = &bytes[core::mem::size_of::<i32>()..];
+                        // Iterate over the bits
+                        let mut iter = HalfIter::new(bytes);
 
-                        // At best we can emit 3 bits per row not counting any metadata for one column
-                        if rows as usize > bytes.len() * 8 / 3 {
-                            return Err(CodingError::InvalidRowCount(rows as usize));
-                        }
+                        // Read the row count, which bounds the values decoded per column
+                        let rows = read_row_count(&mut iter)?;
+                        check_row_count(rows)?;
 
-                        // Reserve space for the rows if there is enough remaining capacity
+                        // Runs of repeated deltas and absent values cost almost nothing, so the decoded row count is only trusted
+                        // for a reservation as far as 3 bits per row could fit in the nibbles after the row count
+                        let reservation_rows = rows.min((bytes.len() * 2 - iter.nibble_offset()) * 4 / 3);
                         #(
-                            let remaining = (self.#col_vec_idents.capacity() - self.#col_vec_idents.len()) as isize;
-                            let reservation = rows as isize - remaining;
-                            if reservation > 0 {
-                                self.#col_vec_idents.reserve(reservation as usize);
-                            }
+                            self.#col_vec_idents.try_reserve(reservation_rows).map_err(|_| CodingError::AllocationFailed)?;
                         )*
-
-                        // Iterate over the bits
-                        let mut iter = HalfIter::new(bytes);
+                        #(
+                            self.#col_quantized_idents.try_reserve(reservation_rows).map_err(|_| CodingError::AllocationFailed)?;
+                        )*
+                        let start_rows = self.#first_col_vec_ident.len();
 
                         // Expect a headers::START_OF_COLUMN tag indicating the start of a new column
                         if iter.next() != Some(::tsz_compress::prelude::consts::headers::START_OF_COLUMN) {
-                            #( self.#col_vec_idents.clear(); )*
                             return Err(CodingError::InvalidInitialColumnTag);
                         }
 
                         // Read the column bytes into a vector one after the other
-                        #( #decode_idents(&mut iter, &mut self.#col_vec_idents)?; )*
+                        #( #decode_blocks )*
+                        #( #wrap_blocks )*
+                        #( #col_dequantize_blocks )*
 
                         // Pad nibbles to byte-alignment
                         match iter.next() {
@@ -1233,20 +2810,62 @@ pub fn derive_decompressv2(tokens: TokenStream) -> TokenStream {
                         }
 
                         // Make sure all the columns are the same length
-                        let elems = [ #( self.#col_vec_idents.len(), )* ];
+                        let elems = [ #( self.#col_vec_idents.len() - start_rows, )* ];
                         if !elems.iter().all(|elem| *elem == elems[0]) {
-                            #( self.#col_vec_idents.clear(); )*
-                            return Err(CodingError::ColumnLengthMismatch(ColumnLengths { expected_rows: rows as usize, column_lengths: elems.to_vec() }));
+                            return Err(CodingError::ColumnLengthMismatch(ColumnLengths { expected_rows: rows, column_lengths: elems.to_vec() }));
+                        }
+
+                        // Make sure the row count matches the decoded rows
+                        if elems[0] != rows {
+                            return Err(CodingError::InvalidRowCount(rows));
                         }
 
                         Ok(())
                     }
+                }
+
+                impl ::tsz_compress::prelude::TszDecompressV2 for #decompressor_ident {
+                    type T = #ident;
+
+                    /// Initialize a decompressor with a vector for each column.
+                    fn new() -> Self {
+                        #decompressor_ident {
+                            #( #col_vec_idents: Vec::new(), )*
+                            #( #col_quantized_idents: Vec::new(), )*
+                            #( #col_presence_runs_idents: Vec::new(), )*
+                            #( #col_present_values_idents: Vec::new(), )*
+                            #( #col_cursor_idents: ColumnCursor::default(), )*
+                            #( #col_presence_cursor_idents: PresenceCursor::default(), )*
+                            stream_state: StreamState::default(),
+                            stream_start_rows: 0,
+                            #sequence_init
+                        }
+                    }
+
+                    /// Decompress tsz-compressed bytes, extending the columns with the decompressed values.
+                    ///
+                    /// On error, the columns are left as they were before the call.
+                    fn decompress(&mut self, bytes: &[u8]) -> Result<(), CodingError> {
+                        let start_rows = self.#first_col_vec_ident.len();
+                        let result = self.decompress_packet(bytes);
+                        if result.is_err() {
+                            #( self.#col_vec_idents.truncate(start_rows); )*
+                            #( self.#col_quantized_idents.truncate(start_rows); )*
+                            #forget_sequence
+                        }
+                        result
+                    }
+
+                    /// Decompress the next chunk of a stream of packets
+                    fn decompress_chunk(&mut self, chunk: &[u8]) -> Result<DecodeProgress, CodingError> {
+                        decompress_stream_chunk(self, chunk)
+                    }
 
                     /// Rotate the columns into rows
                     fn rows(&self) -> Vec<Self::T> {
-                        // Create the rows from columns
+                        // Create the rows from columns, which may differ in length while streaming
                         let elems = [ #( self.#col_vec_idents.len(), )* ];
-                        let len = elems[0];
+                        let len = elems.iter().copied().min().unwrap_or(0);
                         let mut rows = Vec::with_capacity(len);
                         for i in 0..len {
                             rows.push(#ident {
@@ -1259,6 +2878,51 @@ pub fn derive_decompressv2(tokens: TokenStream) -> TokenStream {
                     /// Clear the internal state
                     fn clear(&mut self) {
                         #( self.#col_vec_idents.clear(); )*
+                        #( self.#col_quantized_idents.clear(); )*
+                        self.stream_state = StreamState::default();
+                        #forget_sequence
+                    }
+                }
+
+                impl ::tsz_compress::prelude::StreamColumns for #decompressor_ident {
+                    const HEADER_LEN: usize = #stream_header_len;
+                    const COLUMNS: &'static [(usize, &'static str)] = &[ #( #stream_columns, )* ];
+                    const CONTINUATION: bool = #continuation;
+                    const CRC: bool = #crc;
+
+                    fn check_header(header: &[u8]) -> Result<(), CodingError> {
+                        #check_stream_header
+                    }
+
+                    fn stream_state(&mut self) -> &mut StreamState {
+                        &mut self.stream_state
+                    }
+
+                    fn begin_packet(&mut self, rows: usize, sequence: Option<u16>) -> Result<(), CodingError> {
+                        // The columns could never hold more rows than this
+                        check_row_count(rows)?;
+                        if rows > usize::MAX - self.#first_col_vec_ident.len() {
+                            return Err(CodingError::InvalidRowCount(rows));
+                        }
+                        self.stream_start_rows = self.#first_col_vec_ident.len();
+                        #begin_sequence
+                        #( self.#col_presence_cursor_idents = PresenceCursor::default(); )*
+                        #( self.#col_presence_runs_idents.clear(); )*
+                        #( self.#col_present_values_idents.clear(); )*
+                        Ok(())
+                    }
+
+                    fn step_column(&mut self, column: usize, rows: usize, iter: &mut HalfIter<'_>) -> Result<bool, CodingError> {
+                        match column {
+                            #( #stream_step_arms )*
+                            _ => Ok(true),
+                        }
+                    }
+
+                    fn abort_packet(&mut self) {
+                        #( self.#col_vec_idents.truncate(self.stream_start_rows); )*
+                        #( self.#col_quantized_idents.truncate(self.stream_start_rows); )*
+                        #forget_sequence
                     }
                 }
             }